@@ -0,0 +1,156 @@
+// Validates the `@(...)` attributes of items against the known ones
+
+use crate::data::err::{InvalidAttributeError, UnknownAttributeError};
+use crate::ErrorCollector;
+use std::collections::HashMap;
+use w_parse::attr::Attribute;
+use w_parse::item::named::{ItemNamed, NamedKind};
+use w_parse::types::r#enum::TyEnum;
+use w_parse::types::r#struct::TyStruct;
+use w_parse::types::ItemTy;
+use w_parse::util::NameTyPair;
+
+/// What an attribute is attached to
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum AttrTarget {
+    Type,
+    Func,
+    Field,
+    Variant,
+    Param,
+}
+
+impl AttrTarget {
+    fn name(self) -> &'static str {
+        match self {
+            AttrTarget::Type => "type definitions",
+            AttrTarget::Func => "functions",
+            AttrTarget::Field => "struct fields",
+            AttrTarget::Variant => "enum variants",
+            AttrTarget::Param => "function parameters",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AttrSpec {
+    pub targets: &'static [AttrTarget],
+    /// The exact amount of arguments, `@name` and `@name()` both count as none
+    pub args: usize,
+}
+
+pub struct AttrRegistry {
+    known: HashMap<&'static str, AttrSpec>,
+}
+
+impl Default for AttrRegistry {
+    fn default() -> Self {
+        let mut registry = AttrRegistry {
+            known: HashMap::new(),
+        };
+
+        registry.register(
+            "no_copy",
+            AttrSpec {
+                targets: &[AttrTarget::Type],
+                args: 0,
+            },
+        );
+        registry.register(
+            "extern",
+            AttrSpec {
+                targets: &[AttrTarget::Func],
+                args: 1,
+            },
+        );
+
+        registry
+    }
+}
+
+impl AttrRegistry {
+    pub fn register(&mut self, name: &'static str, spec: AttrSpec) {
+        self.known.insert(name, spec);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&AttrSpec> {
+        self.known.get(name)
+    }
+
+    /// Checks the attributes of an item and of all the fields and variants it defines
+    pub fn check_item(&self, item: &ItemNamed, errs: &ErrorCollector) {
+        match &item.kind {
+            NamedKind::Func(func) => {
+                self.check(&item.attrs, AttrTarget::Func, errs);
+                self.check_params(&func.func.args, errs);
+            }
+            NamedKind::Type(ty) => {
+                // `name :: @extern("...") func();` declares a function without a body
+                let target = match &ty.ty {
+                    ItemTy::Func(_) => AttrTarget::Func,
+                    _ => AttrTarget::Type,
+                };
+                self.check(&item.attrs, target, errs);
+                self.check_ty(&ty.ty, errs);
+            }
+        }
+    }
+
+    pub fn check(&self, attrs: &[Attribute], target: AttrTarget, errs: &ErrorCollector) {
+        for attr in attrs {
+            let name = attr.name.sym.as_str();
+            let spec = match self.get(name) {
+                Some(spec) => spec,
+                None => {
+                    errs.add_error(UnknownAttributeError(attr.name.clone()));
+                    continue;
+                }
+            };
+
+            if !spec.targets.contains(&target) {
+                errs.add_error(InvalidAttributeError {
+                    attr: attr.name.clone(),
+                    msg: format!("`{name}` can't be used on {}", target.name()).into(),
+                });
+            }
+
+            let args = attr.args.as_ref().map_or(0, |args| args.values.len());
+            if args != spec.args {
+                errs.add_error(InvalidAttributeError {
+                    attr: attr.name.clone(),
+                    msg: format!(
+                        "`{name}` takes {} argument(s) but {args} were given",
+                        spec.args
+                    )
+                    .into(),
+                });
+            }
+        }
+    }
+
+    fn check_ty(&self, ty: &ItemTy, errs: &ErrorCollector) {
+        match ty {
+            ItemTy::Struct(TyStruct { fields, .. }) => {
+                for field in fields {
+                    self.check(&field.attrs, AttrTarget::Field, errs);
+                    self.check_ty(&field.ty, errs);
+                }
+            }
+            ItemTy::Enum(TyEnum { variants, .. }) => {
+                for variant in variants {
+                    self.check(&variant.attrs, AttrTarget::Variant, errs);
+                    for ty in variant.fields.iter().flat_map(|tp| &tp.types) {
+                        self.check_ty(ty, errs);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn check_params(&self, params: &[NameTyPair], errs: &ErrorCollector) {
+        for param in params {
+            self.check(&param.attrs, AttrTarget::Param, errs);
+        }
+    }
+}
@@ -0,0 +1,189 @@
+// Checks that the casts of function bodies convert between types that have a conversion
+
+use crate::data::err::InvalidCastError;
+use crate::data::types::TypeKind;
+use crate::pattern::resolve_ref;
+use crate::{ErrorCollector, Module, PathBuf};
+use std::borrow::Cow;
+use w_parse::expr::block::ExprBlock;
+use w_parse::expr::cast::ExprCast;
+use w_parse::expr::path::ExprPath;
+use w_parse::expr::unary::{ExprUnary, UnOp};
+use w_parse::expr::Expr;
+use w_parse::types::array::TyArray;
+use w_parse::types::ItemTy;
+use w_parse::visit::{walk_expr, Visitor};
+
+/// Reports casts between types that have no conversion. Legal casts are:
+///
+/// - integers and floats to any integer or float, widening or narrowing
+/// - pointers to sized types to pointers to other sized types
+/// - `*[]u8` to `*str`, and unsized pointers to the same pointee
+///
+/// Values aren't typed yet, the type of a cast's value is only known for literals and other
+/// casts. Casts of anything else are trusted to be legal.
+pub fn check_casts<'gc>(body: &ExprBlock, tsys: &'gc Module<'gc>, errs: &ErrorCollector) {
+    CastCheck { tsys, errs }.visit_block(body);
+}
+
+struct CastCheck<'a, 'gc> {
+    tsys: &'gc Module<'gc>,
+    errs: &'a ErrorCollector,
+}
+
+/// The types as far as casts are concerned
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum CastTy {
+    Int,
+    Float,
+    Ptr(Pointee),
+    /// Never converts, holds the description of the type
+    Other(&'static str),
+    Unknown,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Pointee {
+    Sized,
+    /// `[]u8`
+    Bytes,
+    Str,
+    /// Unsized arrays of anything but `u8`
+    Slice,
+}
+
+impl CastTy {
+    fn converts_to(self, to: CastTy) -> bool {
+        use CastTy::*;
+        match (self, to) {
+            (Unknown, _) | (_, Unknown) => true,
+            (Int | Float, Int | Float) => true,
+            (Ptr(Pointee::Sized), Ptr(Pointee::Sized)) => true,
+            (Ptr(Pointee::Bytes), Ptr(Pointee::Str)) => true,
+            (Ptr(from), Ptr(to)) => from == to,
+            _ => false,
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            CastTy::Int => "an integer",
+            CastTy::Float => "a float",
+            CastTy::Ptr(Pointee::Sized) => "a pointer",
+            CastTy::Ptr(Pointee::Bytes) => "`*[]u8`",
+            CastTy::Ptr(Pointee::Str) => "`*str`",
+            CastTy::Ptr(Pointee::Slice) => "a pointer to an unsized array",
+            CastTy::Other(desc) => desc,
+            CastTy::Unknown => "an unknown type",
+        }
+    }
+}
+
+impl<'gc> CastCheck<'_, 'gc> {
+    fn check(&self, ExprCast { expr, span_as, ty }: &ExprCast) {
+        let from = self.value(expr);
+        let to = self.ty(ty);
+
+        if !from.converts_to(to) {
+            self.errs.add_error(InvalidCastError {
+                loc: span_as.clone(),
+                from: Cow::Borrowed(from.describe()),
+                to: Cow::Borrowed(to.describe()),
+            });
+        }
+    }
+
+    /// The type of a value, literals without a suffix are classified by their form. Chars are
+    /// integers, strings are `*str` and byte strings `*[]u8`.
+    fn value(&self, expr: &Expr) -> CastTy {
+        match expr {
+            Expr::Number(_) | Expr::Byte(..) | Expr::Char(..) => CastTy::Int,
+            Expr::Float(_) => CastTy::Float,
+            Expr::String(..) => CastTy::Ptr(Pointee::Str),
+            Expr::ByteString(..) => CastTy::Ptr(Pointee::Bytes),
+            Expr::Cast(cast) => self.ty(&cast.ty),
+            Expr::Unary(ExprUnary {
+                op: UnOp::Neg(_) | UnOp::BitNot(_),
+                expr,
+            }) => self.value(expr),
+            Expr::Tuple(_) => CastTy::Other("a tuple"),
+            Expr::Array(_) => CastTy::Other("an array"),
+            Expr::Ctor(_) => CastTy::Other("a struct"),
+            _ => CastTy::Unknown,
+        }
+    }
+
+    fn ty(&self, ty: &ItemTy) -> CastTy {
+        match ty {
+            ItemTy::Referred(referred) => self.named(&referred.path),
+            ItemTy::Pointer(ptr) => CastTy::Ptr(self.pointee(&ptr.ty)),
+            ItemTy::Struct(_) => CastTy::Other("a struct"),
+            ItemTy::Enum(_) => CastTy::Other("an enum"),
+            ItemTy::Tuple(_) => CastTy::Other("a tuple"),
+            ItemTy::Func(_) => CastTy::Other("a function"),
+            ItemTy::Array(_) => CastTy::Other("an array"),
+            ItemTy::Never(_) => CastTy::Other("the never type"),
+        }
+    }
+
+    fn pointee(&self, ty: &ItemTy) -> Pointee {
+        match ty {
+            ItemTy::Array(TyArray { ty, size: None, .. }) => match &**ty {
+                ItemTy::Referred(referred) if primitive(&referred.path) == Some("u8") => {
+                    Pointee::Bytes
+                }
+                _ => Pointee::Slice,
+            },
+            ItemTy::Referred(referred) if primitive(&referred.path) == Some("str") => Pointee::Str,
+            _ => Pointee::Sized,
+        }
+    }
+
+    /// Primitives by name, other types through the type system. Unresolved types are
+    /// reported by pass 1.
+    fn named(&self, path: &ExprPath) -> CastTy {
+        match primitive(path) {
+            Some(
+                "u8" | "u16" | "u32" | "u64" | "usize" | "i8" | "i16" | "i32" | "i64" | "isize",
+            ) => return CastTy::Int,
+            Some("f32" | "f64") => return CastTy::Float,
+            Some("str") => return CastTy::Other("`str`"),
+            _ => {}
+        }
+
+        let md = if path.root.is_some() {
+            self.tsys.root()
+        } else {
+            self.tsys
+        };
+        match md
+            .access_type(&PathBuf::from(&path.path[..]))
+            .and_then(resolve_ref)
+        {
+            Some(TypeKind::Struct(_)) => CastTy::Other("a struct"),
+            Some(TypeKind::Enum(_)) => CastTy::Other("an enum"),
+            Some(TypeKind::Tuple(_)) => CastTy::Other("a tuple"),
+            Some(TypeKind::Func(_)) => CastTy::Other("a function"),
+            Some(TypeKind::Array(_)) => CastTy::Other("an array"),
+            Some(TypeKind::Never(_)) => CastTy::Other("the never type"),
+            _ => CastTy::Unknown,
+        }
+    }
+}
+
+impl Visitor for CastCheck<'_, '_> {
+    fn visit_expr(&mut self, expr: &Expr) {
+        if let Expr::Cast(cast) = expr {
+            self.check(cast);
+        }
+        walk_expr(self, expr);
+    }
+}
+
+/// The name of a path that may be a primitive type
+pub(crate) fn primitive(path: &ExprPath) -> Option<&str> {
+    match &path.path[..] {
+        [name] if path.root.is_none() => Some(&**name.span.fragment()),
+        _ => None,
+    }
+}
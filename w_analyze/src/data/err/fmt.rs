@@ -1,8 +1,15 @@
 use crate::data::err::fmt::builder::StepDesc;
 use crate::data::err::ErrKind;
-use w_tokenize::Span;
+use ariadne::{Label, Report, ReportKind, Source};
+use std::collections::HashMap;
+use std::fmt::{Debug, Display};
+use std::ops::Range;
+use w_tokenize::{FileId, SourceDb, Span};
 
-pub struct ErrorFormatter {}
+#[derive(Default)]
+pub struct ErrorFormatter {
+    errors: Vec<Error>,
+}
 
 pub struct Error {
     kind: ErrKind,
@@ -10,10 +17,93 @@ pub struct Error {
     description: String,
     location: Span,
 
+    elaborations: Vec<Error>,
     help: Vec<Error>,
     notes: Vec<String>,
 }
 
+impl Error {
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// The file the error points into, elaborations and help may point into other files
+    pub fn file(&self) -> FileId {
+        self.location.extra.file
+    }
+
+    pub fn report(&self) -> Report<(FileId, Range<usize>)> {
+        let kind = match self.kind {
+            ErrKind::Error => ReportKind::Error,
+            ErrKind::Warning => ReportKind::Warning,
+        };
+
+        let mut report = Report::build(kind, self.file(), self.location.location_offset())
+            .with_message(&self.description)
+            .with_label(Label::new(label_span(&self.location)));
+
+        // errors inside a macro's template also point to every invocation that led there
+        let mut expansion = self.location.extra.expansion.clone();
+        while let Some(expn) = expansion {
+            let call_site = expn.data().call_site.clone();
+            report = report.with_label(
+                Label::new(label_span(&call_site)).with_message("in this macro invocation"),
+            );
+            expansion = call_site.extra.expansion;
+        }
+
+        for sub in self.elaborations.iter().chain(&self.help) {
+            report = report
+                .with_label(Label::new(label_span(&sub.location)).with_message(&sub.description));
+        }
+
+        let notes = self
+            .notes
+            .iter()
+            .chain(self.help.iter().flat_map(|help| &help.notes));
+        let notes = notes.cloned().collect::<Vec<_>>();
+        if !notes.is_empty() {
+            report = report.with_note(notes.join("\n"));
+        }
+
+        report.finish()
+    }
+}
+
+fn label_span(span: &Span) -> (FileId, Range<usize>) {
+    let start = span.location_offset();
+    (span.extra.file, start..start + span.len())
+}
+
+/// Feeds the files of a [`SourceDb`] to ariadne, named by their path
+pub struct SourceCache<'a> {
+    db: &'a SourceDb,
+    sources: HashMap<FileId, Source>,
+}
+
+impl<'a> SourceCache<'a> {
+    pub fn new(db: &'a SourceDb) -> Self {
+        SourceCache {
+            db,
+            sources: HashMap::new(),
+        }
+    }
+}
+
+impl ariadne::Cache<FileId> for SourceCache<'_> {
+    fn fetch(&mut self, id: &FileId) -> Result<&Source, Box<dyn Debug + '_>> {
+        let db = self.db;
+        Ok(self
+            .sources
+            .entry(*id)
+            .or_insert_with(|| Source::from(db.get(*id).text())))
+    }
+
+    fn display<'a>(&self, id: &'a FileId) -> Option<Box<dyn Display + 'a>> {
+        Some(Box::new(self.db.get(*id).path().display().to_string()))
+    }
+}
+
 pub struct ErrorBuilder<'b> {
     fmt: Option<&'b mut ErrorFormatter>,
     kind: ErrKind,
@@ -66,7 +156,7 @@ macro_rules! define_builder_steps {
 }
 
 impl ErrorFormatter {
-    pub fn err(&mut self) -> StepDesc {
+    pub fn err(&mut self) -> StepDesc<'_> {
         StepDesc(ErrorBuilder {
             fmt: Some(self),
             kind: ErrKind::Error,
@@ -78,7 +168,7 @@ impl ErrorFormatter {
         })
     }
 
-    pub fn warn(&mut self) -> StepDesc {
+    pub fn warn(&mut self) -> StepDesc<'_> {
         StepDesc(ErrorBuilder {
             fmt: Some(self),
             kind: ErrKind::Warning,
@@ -90,8 +180,12 @@ impl ErrorFormatter {
         })
     }
 
-    pub fn submit(&mut self, _error: Error) {
-        todo!()
+    pub fn submit(&mut self, error: Error) {
+        self.errors.push(error);
+    }
+
+    pub fn finish(self) -> Vec<Error> {
+        self.errors
     }
 }
 
@@ -116,6 +210,7 @@ mod builder {
                     description: self.0.description.unwrap(),
                     location: self.0.location.unwrap(),
 
+                    elaborations: self.0.elaborations,
                     help: self.0.help,
                     notes: self.0.notes,
                 }
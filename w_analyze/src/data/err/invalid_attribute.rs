@@ -0,0 +1,24 @@
+use crate::data::err::fmt::ErrorFormatter;
+use crate::data::err::{AnalyzerError, ErrKind};
+use std::borrow::Cow;
+
+use w_parse::Ident;
+
+/// A known attribute used in the wrong place or with the wrong arguments
+pub struct InvalidAttributeError {
+    pub attr: Ident,
+    pub msg: Cow<'static, str>,
+}
+
+impl AnalyzerError for InvalidAttributeError {
+    fn kind(&self) -> ErrKind {
+        ErrKind::Error
+    }
+
+    fn fmt(&self, f: &mut ErrorFormatter) {
+        f.err()
+            .description(&*self.msg)
+            .location(self.attr.span.clone())
+            .submit();
+    }
+}
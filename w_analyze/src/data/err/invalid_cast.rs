@@ -0,0 +1,30 @@
+use crate::data::err::fmt::ErrorFormatter;
+use crate::data::err::{AnalyzerError, ErrKind};
+use std::borrow::Cow;
+
+use w_tokenize::Span;
+
+/// An `as` converting between types that have no conversion
+pub struct InvalidCastError {
+    /// The `as` of the cast
+    pub loc: Span,
+    pub from: Cow<'static, str>,
+    pub to: Cow<'static, str>,
+}
+
+impl AnalyzerError for InvalidCastError {
+    fn kind(&self) -> ErrKind {
+        ErrKind::Error
+    }
+
+    fn fmt(&self, f: &mut ErrorFormatter) {
+        f.err()
+            .description(format!("Can't cast {} to {}", self.from, self.to))
+            .location(self.loc.clone())
+            .add_note(
+                "`as` converts between integers and floats, between pointers to sized types, \
+                 and from `*[]u8` to `*str`",
+            )
+            .submit();
+    }
+}
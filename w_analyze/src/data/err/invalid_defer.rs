@@ -0,0 +1,61 @@
+use crate::data::err::fmt::ErrorFormatter;
+use crate::data::err::{AnalyzerError, ErrKind};
+use w_tokenize::Span;
+
+/// A `defer` outside of a block's statements, or deferring something that can't be
+pub struct InvalidDeferError {
+    /// The `defer` keyword
+    pub defer: Span,
+    pub loc: Span,
+    pub misuse: DeferMisuse,
+}
+
+#[derive(Copy, Clone)]
+pub enum DeferMisuse {
+    /// Used as a value instead of a statement
+    NotStatement,
+    /// Deferring a definition, whose name would never be in scope
+    Define,
+    /// `return`, `break` or `continue` leaving the deferred expression
+    Jump,
+}
+
+impl AnalyzerError for InvalidDeferError {
+    fn kind(&self) -> ErrKind {
+        ErrKind::Error
+    }
+
+    fn fmt(&self, f: &mut ErrorFormatter) {
+        match self.misuse {
+            DeferMisuse::NotStatement => {
+                f.err()
+                    .description("`defer` can only be used as a statement of a block")
+                    .location(self.loc.clone())
+                    .add_note("Deferred expressions run when the block is left, they have no value")
+                    .submit();
+            }
+            DeferMisuse::Define => {
+                f.err()
+                    .description("Definitions can not be deferred")
+                    .location(self.loc.clone())
+                    .add_elaboration()
+                    .description("Deferred here")
+                    .location(self.defer.clone())
+                    .build_help()
+                    .add_note("The name would go out of scope before it could be used")
+                    .submit();
+            }
+            DeferMisuse::Jump => {
+                f.err()
+                    .description("Deferred expressions may not jump out of the `defer`")
+                    .location(self.loc.clone())
+                    .add_elaboration()
+                    .description("Deferred here")
+                    .location(self.defer.clone())
+                    .build_help()
+                    .add_note("The block is already being left when deferred expressions run")
+                    .submit();
+            }
+        }
+    }
+}
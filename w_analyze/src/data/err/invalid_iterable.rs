@@ -0,0 +1,51 @@
+use crate::data::err::fmt::ErrorFormatter;
+use crate::data::err::{AnalyzerError, ErrKind};
+use std::borrow::Cow;
+
+use w_tokenize::Span;
+
+/// A `for` loop over a value that is neither an integer range nor has a method `next`
+pub struct InvalidIterableError {
+    /// The `in` of the loop
+    pub loc: Span,
+    pub ty: Cow<'static, str>,
+    /// The name of the type's `next` method, if it has one with the wrong signature
+    pub next: Option<Span>,
+}
+
+impl AnalyzerError for InvalidIterableError {
+    fn kind(&self) -> ErrKind {
+        ErrKind::Error
+    }
+
+    fn fmt(&self, f: &mut ErrorFormatter) {
+        match &self.next {
+            None => {
+                f.err()
+                    .description(format!("Can't iterate over {}", self.ty))
+                    .location(self.loc.clone())
+                    .add_note(
+                        "`for` iterates over integer ranges and values with a method \
+                         `next(self) option(T)`",
+                    )
+                    .submit();
+            }
+            Some(next) => {
+                f.err()
+                    .description(format!(
+                        "Can't iterate over {}, its method `next` isn't `next(self) option(T)`",
+                        self.ty
+                    ))
+                    .location(self.loc.clone())
+                    .add_elaboration()
+                    .description("Defined here")
+                    .location(next.clone())
+                    .build_help()
+                    .add_note(
+                        "`for` calls `next` before every iteration and stops at `option:none`",
+                    )
+                    .submit();
+            }
+        }
+    }
+}
@@ -0,0 +1,24 @@
+use crate::data::err::fmt::ErrorFormatter;
+use crate::data::err::{AnalyzerError, ErrKind};
+use std::borrow::Cow;
+
+use w_tokenize::Span;
+
+/// A pattern that doesn't fit the type it names or the value it's matched against
+pub struct InvalidPatternError {
+    pub loc: Span,
+    pub msg: Cow<'static, str>,
+}
+
+impl AnalyzerError for InvalidPatternError {
+    fn kind(&self) -> ErrKind {
+        ErrKind::Error
+    }
+
+    fn fmt(&self, f: &mut ErrorFormatter) {
+        f.err()
+            .description(&*self.msg)
+            .location(self.loc.clone())
+            .submit();
+    }
+}
@@ -1,17 +1,32 @@
 mod array_fix;
 mod duplicate_import;
 mod fmt;
+mod invalid_attribute;
+mod invalid_cast;
+mod invalid_defer;
+mod invalid_iterable;
+mod invalid_pattern;
 mod multiple_definitions;
 mod recursive_type;
+mod unknown_attribute;
 mod unresolved_type;
 
 use crate::data::err::fmt::ErrorFormatter;
 use std::cell::RefCell;
+use std::io;
+use w_tokenize::SourceDb;
 
 pub use array_fix::*;
 pub use duplicate_import::*;
+pub use fmt::{Error, SourceCache};
+pub use invalid_attribute::*;
+pub use invalid_cast::*;
+pub use invalid_defer::*;
+pub use invalid_iterable::*;
+pub use invalid_pattern::*;
 pub use multiple_definitions::*;
 pub use recursive_type::*;
+pub use unknown_attribute::*;
 pub use unresolved_type::*;
 
 #[derive(Default)]
@@ -45,4 +60,32 @@ impl ErrorCollector {
     pub fn has_errors(&self) -> bool {
         *self.has_errors.borrow()
     }
+
+    /// The description of every collected error, in the order they were added
+    pub fn descriptions(&self) -> Vec<String> {
+        let mut f = ErrorFormatter::default();
+        for error in self.errors.borrow().iter() {
+            error.fmt(&mut f);
+        }
+
+        f.finish()
+            .iter()
+            .map(|error| error.description().to_string())
+            .collect()
+    }
+
+    /// Prints every collected error, naming the file each of them points into
+    pub fn report(&self, db: &SourceDb) -> io::Result<()> {
+        let mut f = ErrorFormatter::default();
+        for error in self.errors.borrow().iter() {
+            error.fmt(&mut f);
+        }
+
+        let mut cache = SourceCache::new(db);
+        for error in f.finish() {
+            error.report().eprint(&mut cache)?;
+        }
+
+        Ok(())
+    }
 }
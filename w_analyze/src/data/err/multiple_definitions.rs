@@ -38,10 +38,10 @@ impl AnalyzerError for MultipleDefinitionsError {
 
         f.err()
             .description(msg1)
-            .location(self.loc.0.clone())
+            .location(self.loc.span.clone())
             .add_elaboration()
             .description(msg2)
-            .location(self.first.0.clone())
+            .location(self.first.span.clone())
             .build_help()
             .submit();
     }
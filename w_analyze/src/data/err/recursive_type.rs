@@ -15,10 +15,10 @@ impl AnalyzerError for RecursiveTypeError {
     fn fmt(&self, f: &mut ErrorFormatter) {
         f.err()
             .description("This type may not contain it self directly")
-            .location(self.usage.0.clone())
+            .location(self.usage.span.clone())
             .add_elaboration()
             .description("The type is defined here")
-            .location(self.og.0.clone())
+            .location(self.og.span.clone())
             .build_help()
             .add_note("Types may contain them self but only indirectly through pointers")
             .add_note("References to other types may never point to them self")
@@ -0,0 +1,20 @@
+use crate::data::err::fmt::ErrorFormatter;
+use crate::data::err::{AnalyzerError, ErrKind};
+
+use w_parse::Ident;
+
+pub struct UnknownAttributeError(pub Ident);
+
+impl AnalyzerError for UnknownAttributeError {
+    fn kind(&self) -> ErrKind {
+        ErrKind::Error
+    }
+
+    fn fmt(&self, f: &mut ErrorFormatter) {
+        f.err()
+            .description(format!("Unknown attribute `{}`", self.0.sym))
+            .location(self.0.span.clone())
+            .add_note("Check the spelling of the attribute")
+            .submit();
+    }
+}
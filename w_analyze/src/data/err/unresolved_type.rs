@@ -13,7 +13,7 @@ impl AnalyzerError for UnresolvedTypeError {
     fn fmt(&self, f: &mut ErrorFormatter) {
         f.err()
             .description("Unable to resolve type")
-            .location(self.0 .0.clone())
+            .location(self.0.span.clone())
             .add_note("Try defining the type")
             .add_note("Try importing the type")
             .submit();
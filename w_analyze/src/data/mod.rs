@@ -17,13 +17,14 @@ use std::ops::Deref;
 use typed_arena::Arena;
 
 use w_parse::Ident;
+use w_tokenize::Symbol;
 
 pub struct Module<'gc> {
     pub types_arena: &'gc Arena<TypeRef<'gc>>,
     pub modules_arena: &'gc Arena<Self>,
 
-    pub types: RefCell<HashMap<Ident, &'gc TypeRef<'gc>>>,
-    pub modules: RefCell<HashMap<Ident, &'gc Self>>,
+    pub types: RefCell<HashMap<Symbol, &'gc TypeRef<'gc>>>,
+    pub modules: RefCell<HashMap<Symbol, &'gc Self>>,
 
     // the module is where the item is from and not the item it self
     // pub imports: RefCell<HashMap<Ident<'a>, &'gc Self>>,
@@ -102,15 +103,30 @@ impl<'gc> Module<'gc> {
         let md_path = path.slice(..path.len() - 1);
         let md = self.access_or_create_module(md_path);
 
-        *md.types
-            .borrow_mut()
-            .entry(name.clone())
-            .or_insert_with(|| {
-                &*self.types_arena.alloc(TypeRef {
-                    loc: Location { name, home: md },
-                    definition: RefCell::new(None),
-                })
+        md.types.borrow_mut().entry(name.sym).or_insert_with(|| {
+            &*self.types_arena.alloc(TypeRef {
+                loc: Location { name, home: md },
+                definition: RefCell::new(None),
             })
+        })
+    }
+
+    /// The type at `path` if anything created it already
+    pub fn access_type(&'gc self, path: &Path) -> Option<&'gc TypeRef<'gc>> {
+        let name = path.last()?;
+        let md = self.access_module(path.slice(..path.len() - 1))?;
+        let ty = md.types.borrow().get(&name.sym).copied();
+        ty
+    }
+
+    /// The module at `path` if anything created it already
+    pub fn access_module(&'gc self, path: &Path) -> Option<&'gc Module<'gc>> {
+        let Some(next) = path.first() else {
+            return Some(self);
+        };
+
+        let next = self.modules.borrow().get(&next.sym).copied()?;
+        next.access_module(path.slice(1..))
     }
 
     pub fn access_or_create_module(&'gc self, path: &Path) -> &'gc Module<'gc> {
@@ -122,7 +138,7 @@ impl<'gc> Module<'gc> {
         let next = *self
             .modules
             .borrow_mut()
-            .entry(next.clone())
+            .entry(next.sym)
             .or_insert_with(|| {
                 Module::new(
                     self.path.join(next.clone()),
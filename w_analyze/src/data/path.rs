@@ -1,8 +1,13 @@
 use std::borrow::Borrow;
+use std::fmt::{Display, Formatter};
 use std::mem::transmute;
 use std::ops::{Bound, Deref, RangeBounds};
 use std::slice::Iter;
 use w_parse::Ident;
+use w_tokenize::Symbol;
+
+/// Identity of a path, made of the symbols of its segments without their spans
+pub type PathKey = Box<[Symbol]>;
 
 #[repr(transparent)]
 #[derive(Hash, PartialEq, Eq)]
@@ -44,6 +49,10 @@ impl Path {
         path
     }
 
+    pub fn key(&self) -> PathKey {
+        self.path.iter().map(|ident| ident.sym).collect()
+    }
+
     pub fn slice(&self, range: impl RangeBounds<usize>) -> &Path {
         let start = match range.start_bound() {
             Bound::Included(&n) => n,
@@ -62,25 +71,22 @@ impl Path {
     }
 }
 
-impl ToString for Path {
-    fn to_string(&self) -> String {
-        let mut buf = String::new();
-
+impl Display for Path {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let mut iter = self.path.iter();
 
         if let Some(ident) = iter.next() {
-            buf.push_str(&*ident.0);
+            f.write_str(&ident.span)?;
         }
         for ident in iter {
-            buf.push(':');
-            buf.push_str(&*ident.0);
+            write!(f, ":{}", &*ident.span)?;
         }
 
-        buf
+        Ok(())
     }
 }
 
-impl<'a, V: Into<Vec<Ident>>> From<V> for PathBuf {
+impl<V: Into<Vec<Ident>>> From<V> for PathBuf {
     fn from(vec: V) -> Self {
         Self { path: vec.into() }
     }
@@ -118,11 +124,11 @@ impl ToOwned for Path {
     }
 }
 
-impl<'a, 'b> IntoIterator for &'b Path {
+impl<'b> IntoIterator for &'b Path {
     type Item = &'b Ident;
     type IntoIter = Iter<'b, Ident>;
 
     fn into_iter(self) -> Self::IntoIter {
-        (&self.path).iter()
+        self.path.iter()
     }
 }
@@ -0,0 +1,423 @@
+// Checks where `defer` is used and lowers it into plain expressions on every exit of a block
+
+use crate::data::err::{DeferMisuse, InvalidDeferError};
+use crate::ErrorCollector;
+use std::mem;
+use w_parse::expr::block::{BlockKind, ExprBlock, Statement};
+use w_parse::expr::branch::ExprBranch;
+use w_parse::expr::defer::ExprDefer;
+use w_parse::expr::define::ExprDefine;
+use w_parse::expr::jump::{ExprBreak, ExprContinue, ExprReturn};
+use w_parse::expr::loops::{ExprFor, ExprLoop, ExprWhile, Label};
+use w_parse::expr::r#match::ExprMatch;
+use w_parse::expr::Expr;
+use w_parse::pattern::Pattern;
+use w_parse::visit::{walk_expr, Visitor};
+use w_parse::Ident;
+use w_tokenize::{Kind, Span, Symbol, Token};
+
+/// Reports every `defer` in a function body that isn't a statement of a block, defers a
+/// definition, or whose expression leaves it through `return`, `break` or `continue`
+pub fn check_defers(body: &ExprBlock, errs: &ErrorCollector) {
+    DeferCheck {
+        errs,
+        loops: vec![],
+        deferred: None,
+    }
+    .visit_block(body);
+}
+
+struct DeferCheck<'a> {
+    errs: &'a ErrorCollector,
+    /// Labels of the loops around the current expression, innermost last
+    loops: Vec<Option<Symbol>>,
+    /// The innermost `defer` around the current expression and how many loops were around it
+    deferred: Option<(Span, usize)>,
+}
+
+impl DeferCheck<'_> {
+    fn defer(&mut self, ExprDefer { span_defer, expr }: &ExprDefer) {
+        if let Expr::Define(ExprDefine { span_op, .. }) = &**expr {
+            self.error(span_defer, span_op, DeferMisuse::Define);
+        }
+
+        let outer = self
+            .deferred
+            .replace((span_defer.clone(), self.loops.len()));
+        self.visit_expr(expr);
+        self.deferred = outer;
+    }
+
+    /// Reports `span` if it jumps out of the innermost `defer`, `label` is `None` for returns
+    fn jump(&mut self, span: &Span, label: Option<&Option<Label>>) {
+        let Some((defer, depth)) = self.deferred.clone() else {
+            return;
+        };
+
+        // jumps to loops that don't exist are reported elsewhere
+        let target = match label {
+            None => None,
+            Some(None) => self.loops.len().checked_sub(1),
+            Some(Some(label)) => {
+                let target = self.loops.iter().rposition(|l| *l == Some(label.name.sym));
+                if target.is_none() {
+                    return;
+                }
+                target
+            }
+        };
+
+        if label.is_none() || target.is_some_and(|target| target < depth) {
+            self.error(&defer, span, DeferMisuse::Jump);
+        }
+    }
+
+    fn in_loop(&mut self, label: &Option<Label>, body: &ExprBlock) {
+        self.loops.push(label.as_ref().map(|l| l.name.sym));
+        self.visit_block(body);
+        self.loops.pop();
+    }
+
+    fn error(&self, defer: &Span, loc: &Span, misuse: DeferMisuse) {
+        self.errs.add_error(InvalidDeferError {
+            defer: defer.clone(),
+            loc: loc.clone(),
+            misuse,
+        });
+    }
+}
+
+impl Visitor for DeferCheck<'_> {
+    fn visit_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Defer(ExprDefer { span_defer, .. }) => {
+                self.error(span_defer, span_defer, DeferMisuse::NotStatement);
+                walk_expr(self, expr);
+            }
+            Expr::Break(ExprBreak {
+                span_break, label, ..
+            }) => {
+                self.jump(span_break, Some(label));
+                walk_expr(self, expr);
+            }
+            Expr::Continue(ExprContinue {
+                span_continue,
+                label,
+            }) => self.jump(span_continue, Some(label)),
+            Expr::Return(ExprReturn { span_return, .. }) => {
+                self.jump(span_return, None);
+                walk_expr(self, expr);
+            }
+
+            // the condition and the iterable belong to the surrounding loop
+            Expr::While(ExprWhile {
+                label, cond, body, ..
+            }) => {
+                self.visit_expr(cond);
+                self.in_loop(label, body);
+            }
+            Expr::For(ExprFor {
+                label,
+                iterable,
+                body,
+                ..
+            }) => {
+                self.visit_expr(iterable);
+                self.in_loop(label, body);
+            }
+            Expr::Loop(ExprLoop { label, body, .. }) => self.in_loop(label, body),
+
+            expr => walk_expr(self, expr),
+        }
+    }
+
+    fn visit_block(&mut self, block: &ExprBlock) {
+        match &block.kind {
+            BlockKind::Many { stmts, returning } => {
+                for stmt in stmts {
+                    match &stmt.expr {
+                        Expr::Defer(defer) => self.defer(defer),
+                        expr => self.visit_expr(expr),
+                    }
+                }
+
+                if let Some(expr) = returning {
+                    self.visit_expr(expr);
+                }
+            }
+            BlockKind::Inline(expr) => self.visit_expr(expr),
+        }
+    }
+}
+
+/// Removes every `defer` of a function body checked by [`check_defers`], copying the
+/// deferred expressions to every place their block is left.
+///
+/// At the end of a block they run in reverse order after its value was stored in a new
+/// name, which the block then evaluates to. Jumps leaving the block are wrapped into a block
+/// that stores their value, runs the deferred expressions of every block being left,
+/// innermost first, and jumps with the stored value.
+pub fn lower_defers(body: &mut ExprBlock) {
+    DeferLowering {
+        loops: vec![],
+        scopes: vec![],
+    }
+    .block(body);
+}
+
+struct DeferLowering {
+    /// Labels of the loops around the current expression, innermost last
+    loops: Vec<Option<Symbol>>,
+    scopes: Vec<Scope>,
+}
+
+/// A block that is being lowered
+struct Scope {
+    /// How many loops were around the block
+    loops: usize,
+    /// Deferred expressions reached so far with their `defer`s, in order
+    deferred: Vec<(Span, Expr)>,
+}
+
+impl DeferLowering {
+    fn block(&mut self, block: &mut ExprBlock) {
+        let (stmts, returning) = match &mut block.kind {
+            BlockKind::Many { stmts, returning } => (stmts, returning),
+            BlockKind::Inline(expr) => return self.expr(expr),
+        };
+
+        self.scopes.push(Scope {
+            loops: self.loops.len(),
+            deferred: vec![],
+        });
+
+        let mut acc = Vec::with_capacity(stmts.len());
+        for mut stmt in mem::take(stmts) {
+            if let Expr::Defer(ExprDefer {
+                span_defer,
+                mut expr,
+            }) = stmt.expr
+            {
+                self.expr(&mut expr);
+                let scope = self.scopes.last_mut().unwrap();
+                scope.deferred.push((span_defer, *expr));
+                continue;
+            }
+
+            self.expr(&mut stmt.expr);
+            acc.push(stmt);
+        }
+
+        if let Some(expr) = returning {
+            self.expr(expr);
+        }
+
+        let scope = self.scopes.pop().unwrap();
+        if !scope.deferred.is_empty() {
+            let (mut stmts_end, value) = store(returning.take().map(|expr| *expr), &block.span);
+            stmts_end.extend(scope.deferred.into_iter().rev().map(statement));
+            acc.extend(stmts_end);
+            *returning = value.map(Box::new);
+        }
+        *stmts = acc;
+    }
+
+    /// The deferred expressions to run before leaving the blocks inside loop `target`, all
+    /// of them for `None`
+    fn leaving(&self, target: Option<usize>) -> Vec<(Span, Expr)> {
+        self.scopes
+            .iter()
+            .rev()
+            .take_while(|scope| target.is_none_or(|target| scope.loops > target))
+            .flat_map(|scope| scope.deferred.iter().rev().cloned())
+            .collect()
+    }
+
+    fn target(&self, label: &Option<Label>) -> Option<usize> {
+        match label {
+            None => self.loops.len().checked_sub(1),
+            Some(label) => self.loops.iter().rposition(|l| *l == Some(label.name.sym)),
+        }
+    }
+
+    /// Runs `deferred` before the jump in `expr`
+    fn wrap_jump(expr: &mut Expr, deferred: Vec<(Span, Expr)>, span: Span) {
+        if deferred.is_empty() {
+            return;
+        }
+
+        let mut jump = mem::replace(expr, Expr::Error(span.clone()));
+        let mut stmts = vec![];
+        if let Expr::Break(ExprBreak { value, .. }) | Expr::Return(ExprReturn { value, .. }) =
+            &mut jump
+        {
+            let (define, stored) = store(value.take().map(|value| *value), &span);
+            stmts = define;
+            *value = stored.map(Box::new);
+        }
+        stmts.extend(deferred.into_iter().map(statement));
+
+        *expr = Expr::Block(ExprBlock {
+            span,
+            kind: BlockKind::Many {
+                stmts,
+                returning: Some(Box::new(jump)),
+            },
+        });
+    }
+
+    fn in_loop(&mut self, label: &Option<Label>, body: &mut ExprBlock) {
+        self.loops.push(label.as_ref().map(|l| l.name.sym));
+        self.block(body);
+        self.loops.pop();
+    }
+
+    fn expr(&mut self, expr: &mut Expr) {
+        match expr {
+            Expr::Break(ExprBreak {
+                span_break,
+                label,
+                value,
+            }) => {
+                if let Some(value) = value {
+                    self.expr(value);
+                }
+
+                // breaks without a loop are reported elsewhere, they don't leave anything
+                if let Some(target) = self.target(label) {
+                    let span = span_break.clone();
+                    Self::wrap_jump(expr, self.leaving(Some(target)), span);
+                }
+            }
+            Expr::Continue(ExprContinue {
+                span_continue,
+                label,
+            }) => {
+                if let Some(target) = self.target(label) {
+                    let span = span_continue.clone();
+                    Self::wrap_jump(expr, self.leaving(Some(target)), span);
+                }
+            }
+            Expr::Return(ExprReturn { span_return, value }) => {
+                if let Some(value) = value {
+                    self.expr(value);
+                }
+
+                let span = span_return.clone();
+                Self::wrap_jump(expr, self.leaving(None), span);
+            }
+            // only statements can be deferred, the analyzer reports the others
+            Expr::Defer(ExprDefer { expr, .. }) => self.expr(expr),
+
+            Expr::Block(block) => self.block(block),
+            Expr::Branch(ExprBranch {
+                cond,
+                body,
+                body_else,
+                ..
+            }) => {
+                self.expr(cond);
+                self.block(body);
+                if let Some(body_else) = body_else {
+                    self.block(body_else);
+                }
+            }
+            Expr::While(ExprWhile {
+                label, cond, body, ..
+            }) => {
+                self.expr(cond);
+                self.in_loop(label, body);
+            }
+            Expr::For(ExprFor {
+                label,
+                iterable,
+                body,
+                ..
+            }) => {
+                self.expr(iterable);
+                self.in_loop(label, body);
+            }
+            Expr::Loop(ExprLoop { label, body, .. }) => self.in_loop(label, body),
+            Expr::Match(ExprMatch { value, arms, .. }) => {
+                self.expr(value);
+                arms.iter_mut().for_each(|arm| self.block(&mut arm.body));
+            }
+
+            Expr::Tuple(many) => many.values.iter_mut().for_each(|expr| self.expr(expr)),
+            Expr::Array(many) => many.values.iter_mut().for_each(|expr| self.expr(expr)),
+            Expr::Ctor(ctor) => ctor.vals.values.iter_mut().for_each(|(_, e)| self.expr(e)),
+            Expr::Call(call) => {
+                self.expr(&mut call.base);
+                call.args.values.iter_mut().for_each(|expr| self.expr(expr));
+            }
+            Expr::Index(index) => {
+                self.expr(&mut index.base);
+                index
+                    .args
+                    .values
+                    .iter_mut()
+                    .for_each(|expr| self.expr(expr));
+            }
+            Expr::Binary(bin) => {
+                self.expr(&mut bin.left);
+                self.expr(&mut bin.right);
+            }
+            Expr::Define(def) => self.expr(&mut def.value),
+            Expr::Assign(asg) => {
+                self.expr(&mut asg.assignee);
+                self.expr(&mut asg.value);
+            }
+            Expr::Range(range) => {
+                range.start.iter_mut().for_each(|expr| self.expr(expr));
+                range.end.iter_mut().for_each(|expr| self.expr(expr));
+            }
+            Expr::Unary(unary) => self.expr(&mut unary.expr),
+            Expr::Cast(cast) => self.expr(&mut cast.expr),
+            Expr::Field(field) => self.expr(&mut field.base),
+
+            Expr::Path(_)
+            | Expr::Number(_)
+            | Expr::Float(_)
+            | Expr::String(..)
+            | Expr::Char(..)
+            | Expr::Byte(..)
+            | Expr::ByteString(..)
+            | Expr::Ident(_)
+            | Expr::Error(_) => {}
+        }
+    }
+}
+
+/// Defines a new name holding `value` so it can be used after other expressions ran
+fn store(value: Option<Expr>, span: &Span) -> (Vec<Statement>, Option<Expr>) {
+    let Some(value) = value else {
+        return (vec![], None);
+    };
+
+    let name = Ident {
+        sym: Symbol::intern("value").fresh(),
+        span: span.clone(),
+    };
+    let define = Expr::Define(ExprDefine {
+        span_op: span.clone(),
+        pattern: Pattern::Bind(name.clone()),
+        value: Box::new(value),
+    });
+
+    (
+        vec![statement((span.clone(), define))],
+        Some(Expr::Ident(name)),
+    )
+}
+
+/// `expr;`, the `;` is put at `span`
+pub(crate) fn statement((span, expr): (Span, Expr)) -> Statement {
+    Statement {
+        expr,
+        sim: Some(Token {
+            span,
+            kind: Kind::Semicolon,
+            trivia: None,
+        }),
+    }
+}
@@ -0,0 +1,512 @@
+// Checks what `for` loops iterate over and lowers them into loops calling `next`
+
+use crate::cast::primitive;
+use crate::data::err::InvalidIterableError;
+use crate::defer::statement;
+use crate::pattern::resolve_ref;
+use crate::{ErrorCollector, Module, PathBuf};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::mem;
+use w_parse::expr::assign::ExprAssignment;
+use w_parse::expr::block::{BlockKind, ExprBlock, Statement};
+use w_parse::expr::branch::ExprBranch;
+use w_parse::expr::call::ExprCall;
+use w_parse::expr::define::ExprDefine;
+use w_parse::expr::field::ExprField;
+use w_parse::expr::jump::ExprBreak;
+use w_parse::expr::loops::{ExprFor, ExprLoop};
+use w_parse::expr::many::ExprTuple;
+use w_parse::expr::ops::{BiOp, ExprBinary};
+use w_parse::expr::path::ExprPath;
+use w_parse::expr::r#match::{ExprMatch, MatchArm};
+use w_parse::expr::range::ExprRange;
+use w_parse::expr::unary::{ExprUnary, UnOp};
+use w_parse::expr::Expr;
+use w_parse::item::named::{ItemNamed, NamedKind};
+use w_parse::item::Item;
+use w_parse::pattern::{PatVariant, Pattern};
+use w_parse::types::func::TyNamedFunc;
+use w_parse::types::referred::TyReferred;
+use w_parse::types::ItemTy;
+use w_parse::visit::{walk_expr, walk_expr_mut, Visitor, VisitorMut};
+use w_parse::{Ident, ParsedModule};
+use w_rcstr::RcStr;
+use w_tokenize::{Number, Span, Symbol};
+
+/// The `next` methods of a module by the name of the type they are defined in
+pub struct Iterators<'a> {
+    methods: HashMap<Symbol, (&'a Ident, &'a TyNamedFunc)>,
+}
+
+impl<'a> Iterators<'a> {
+    /// Collects every function defined as `ty(T):next`
+    pub fn new(module: &'a ParsedModule) -> Self {
+        let methods = module
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Definer(ItemNamed {
+                    name,
+                    path,
+                    kind: NamedKind::Func(func),
+                    ..
+                }) => match &path[..] {
+                    [next] if next.sym.as_str() == "next" => Some((name.sym, (next, &func.func))),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect();
+
+        Iterators { methods }
+    }
+}
+
+/// Reports `for` loops over values that are neither integer ranges nor have a method
+/// `next(self) option(T)`.
+///
+/// Values aren't typed yet, only literals, constructors and casts have a known type. Loops
+/// over anything else are trusted to iterate over something that can be iterated.
+pub fn check_for_loops<'gc>(
+    body: &ExprBlock,
+    iterators: &Iterators,
+    tsys: &'gc Module<'gc>,
+    errs: &ErrorCollector,
+) {
+    ForCheck {
+        iterators,
+        tsys,
+        errs,
+    }
+    .visit_block(body);
+}
+
+struct ForCheck<'a, 'gc> {
+    iterators: &'a Iterators<'a>,
+    tsys: &'gc Module<'gc>,
+    errs: &'a ErrorCollector,
+}
+
+/// Why a value can't be iterated: its description and the `next` method with the wrong
+/// signature, if it has one
+type NotIterable = (Cow<'static, str>, Option<Span>);
+
+impl<'gc> ForCheck<'_, 'gc> {
+    fn check(
+        &self,
+        ExprFor {
+            span_in, iterable, ..
+        }: &ExprFor,
+    ) {
+        if let Err((ty, next)) = self.value(iterable) {
+            self.errs.add_error(InvalidIterableError {
+                loc: span_in.clone(),
+                ty,
+                next,
+            });
+        }
+    }
+
+    fn value(&self, expr: &Expr) -> Result<(), NotIterable> {
+        let desc = match expr {
+            Expr::Range(range) => return range_bounds(range),
+            Expr::Ctor(ctor) => return self.named(&ctor.ty_path),
+            Expr::Cast(cast) => return self.ty(&cast.ty),
+            Expr::Unary(ExprUnary {
+                op: UnOp::Neg(_) | UnOp::BitNot(_),
+                expr,
+            }) => return self.value(expr),
+
+            Expr::Number(_) | Expr::Byte(..) | Expr::Char(..) => "an integer",
+            Expr::Float(_) => "a float",
+            Expr::String(..) => "`*str`",
+            Expr::ByteString(..) => "`*[]u8`",
+            Expr::Tuple(_) => "a tuple",
+            Expr::Array(_) => "an array",
+            _ => return Ok(()),
+        };
+
+        Err((Cow::Borrowed(desc), None))
+    }
+
+    /// Pointers are trusted, a method taking `self` by pointer may be called through them
+    fn ty(&self, ty: &ItemTy) -> Result<(), NotIterable> {
+        let desc = match ty {
+            ItemTy::Referred(TyReferred { path, .. }) => return self.named(path),
+            ItemTy::Pointer(_) => return Ok(()),
+            ItemTy::Struct(_) => "a struct",
+            ItemTy::Enum(_) => "an enum",
+            ItemTy::Tuple(_) => "a tuple",
+            ItemTy::Func(_) => "a function",
+            ItemTy::Array(_) => "an array",
+            ItemTy::Never(_) => "the never type",
+        };
+
+        Err((Cow::Borrowed(desc), None))
+    }
+
+    /// Primitives by name, other types by their `next` method. Types that aren't defined
+    /// are reported by pass 1.
+    fn named(&self, path: &ExprPath) -> Result<(), NotIterable> {
+        let desc = match primitive(path) {
+            Some(
+                "u8" | "u16" | "u32" | "u64" | "usize" | "i8" | "i16" | "i32" | "i64" | "isize",
+            ) => "an integer",
+            Some("f32" | "f64") => "a float",
+            Some("str") => "`str`",
+            _ => return self.defined(path),
+        };
+
+        Err((Cow::Borrowed(desc), None))
+    }
+
+    fn defined(&self, path: &ExprPath) -> Result<(), NotIterable> {
+        let [name] = &path.path[..] else {
+            return Ok(());
+        };
+        let desc = || Cow::Owned(format!("`{}`", name.sym.as_str()));
+
+        if let Some((next, func)) = self.iterators.methods.get(&name.sym) {
+            return match is_next(func) {
+                true => Ok(()),
+                false => Err((desc(), Some(next.span.clone()))),
+            };
+        }
+
+        let md = if path.root.is_some() {
+            self.tsys.root()
+        } else {
+            self.tsys
+        };
+        match md
+            .access_type(&PathBuf::from(&path.path[..]))
+            .and_then(resolve_ref)
+        {
+            Some(_) => Err((desc(), None)),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Visitor for ForCheck<'_, '_> {
+    fn visit_expr(&mut self, expr: &Expr) {
+        if let Expr::For(f) = expr {
+            self.check(f);
+        }
+        walk_expr(self, expr);
+    }
+}
+
+/// Ranges need a start to count from and can't count floats
+fn range_bounds(ExprRange { start, end, .. }: &ExprRange) -> Result<(), NotIterable> {
+    fn is_float(expr: &Expr) -> bool {
+        match expr {
+            Expr::Float(_) => true,
+            Expr::Unary(ExprUnary {
+                op: UnOp::Neg(_),
+                expr,
+            }) => is_float(expr),
+            _ => false,
+        }
+    }
+
+    let desc = match start {
+        None => "a range without a start",
+        Some(start) if is_float(start) || end.as_deref().is_some_and(is_float) => {
+            "a range of floats"
+        }
+        Some(_) => return Ok(()),
+    };
+
+    Err((Cow::Borrowed(desc), None))
+}
+
+/// Whether a method takes `self` first and returns `option(T)`
+fn is_next(TyNamedFunc { args, ret_ty, .. }: &TyNamedFunc) -> bool {
+    let takes_self = args
+        .first()
+        .is_some_and(|arg| arg.name.sym.as_str() == "self");
+    let returns_option = match &**ret_ty {
+        ItemTy::Referred(TyReferred {
+            path,
+            args: Some(args),
+        }) => primitive(path) == Some("option") && args.args.len() == 1,
+        _ => false,
+    };
+
+    takes_self && returns_option
+}
+
+/// Replaces every `for` of a function body checked by [`check_for_loops`] with a `loop`.
+///
+/// Values with a `next` method are stored in a new name and the loop matches the result of
+/// `next` before every iteration:
+///
+/// ```w
+/// {
+///     iter := iterable;
+///     loop {
+///         match iter.next() {
+///             option:some(pattern) -> body,
+///             option:none -> break,
+///         }
+///     }
+/// }
+/// ```
+///
+/// Integer ranges count in a new name instead, which is moved on before the body runs so
+/// `continue` doesn't skip it. Inclusive ranges keep whether there is a next value in a
+/// flag, `end` itself may be the largest value of the type.
+pub fn lower_for_loops(body: &mut ExprBlock) {
+    ForLowering.visit_block_mut(body);
+}
+
+struct ForLowering;
+
+impl VisitorMut for ForLowering {
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        walk_expr_mut(self, expr);
+
+        let Expr::For(ExprFor { span_for, .. }) = expr else {
+            return;
+        };
+        let span = span_for.clone();
+        let Expr::For(f) = mem::replace(expr, Expr::Error(span)) else {
+            unreachable!()
+        };
+        *expr = lower(f);
+    }
+}
+
+fn lower(f: ExprFor) -> Expr {
+    let ExprFor {
+        span_for: span,
+        label,
+        pattern,
+        span_in,
+        iterable,
+        body,
+    } = f;
+
+    let (stmts, body) = match *iterable {
+        Expr::Range(ExprRange {
+            start: Some(start),
+            end,
+            inclusive,
+            ..
+        }) => lower_range(
+            &span,
+            *pattern,
+            *start,
+            end.map(|end| *end),
+            inclusive,
+            body,
+        ),
+        // the check reports ranges without a start
+        Expr::Range(range) => {
+            return Expr::For(ExprFor {
+                span_for: span,
+                label,
+                pattern,
+                span_in,
+                iterable: Box::new(Expr::Range(range)),
+                body,
+            })
+        }
+        iterable => {
+            let iter = fresh("iter", &span);
+            let next = Expr::Call(ExprCall {
+                base: Box::new(Expr::Field(ExprField {
+                    base: Box::new(Expr::Ident(iter.clone())),
+                    dot: span_in.clone(),
+                    field: named("next", &span_in),
+                })),
+                args: ExprTuple {
+                    span: span_in.clone(),
+                    values: vec![],
+                },
+            });
+            let arms = vec![
+                MatchArm {
+                    pattern: Pattern::Variant(PatVariant {
+                        path: option("some", &span_in),
+                        payload: Some((span_in.clone(), vec![*pattern])),
+                    }),
+                    body,
+                },
+                MatchArm {
+                    pattern: Pattern::Variant(PatVariant {
+                        path: option("none", &span_in),
+                        payload: None,
+                    }),
+                    body: block(&span, vec![], Some(leave(&span))),
+                },
+            ];
+            let matched = Expr::Match(ExprMatch {
+                span_match: span.clone(),
+                value: Box::new(next),
+                span: span.clone(),
+                arms,
+            });
+
+            (
+                vec![define(Pattern::Bind(iter), iterable, &span)],
+                block(&span, vec![statement((span.clone(), matched))], None),
+            )
+        }
+    };
+
+    let looped = Expr::Loop(ExprLoop {
+        span_loop: span.clone(),
+        label,
+        body,
+    });
+    Expr::Block(block(&span, stmts, Some(looped)))
+}
+
+/// The statements before the loop and its body for `start..end` and `start..=end`
+fn lower_range(
+    span: &Span,
+    pattern: Pattern,
+    start: Expr,
+    end: Option<Expr>,
+    inclusive: bool,
+    body: ExprBlock,
+) -> (Vec<Statement>, ExprBlock) {
+    let next = fresh("next", span);
+    let var = || Expr::Ident(next.clone());
+    let step = || assign(var(), binary(BiOp::Add, var(), one(span), span), span);
+
+    let mut before = vec![define(Pattern::Bind(next.clone()), start, span)];
+    let mut stmts = vec![];
+    match end {
+        // `start.._` counts on until the body leaves the loop
+        None => {
+            stmts.push(define(pattern, var(), span));
+            stmts.push(stmt_at(step(), span));
+        }
+        Some(end) if !inclusive => {
+            let stop = fresh("end", span);
+            before.push(define(Pattern::Bind(stop.clone()), end, span));
+
+            let done = binary(BiOp::Ge, var(), Expr::Ident(stop), span);
+            stmts.push(stmt_at(branch(done, leave(span), span), span));
+            stmts.push(define(pattern, var(), span));
+            stmts.push(stmt_at(step(), span));
+        }
+        Some(end) => {
+            let stop = fresh("end", span);
+            let more = fresh("more", span);
+            let flag = || Expr::Ident(more.clone());
+            before.push(define(Pattern::Bind(stop.clone()), end, span));
+            let first = binary(BiOp::Le, var(), Expr::Ident(stop.clone()), span);
+            before.push(define(Pattern::Bind(more.clone()), first, span));
+
+            let done = Expr::Unary(ExprUnary {
+                op: UnOp::Not(span.clone()),
+                expr: Box::new(flag()),
+            });
+            stmts.push(stmt_at(branch(done, leave(span), span), span));
+            stmts.push(define(pattern, var(), span));
+            let again = binary(BiOp::Lt, var(), Expr::Ident(stop), span);
+            stmts.push(stmt_at(assign(flag(), again, span), span));
+            stmts.push(stmt_at(branch(flag(), step(), span), span));
+        }
+    }
+
+    (before, block(span, stmts, Some(Expr::Block(body))))
+}
+
+/// A new name that can't clash with the names of the body
+fn fresh(name: &str, span: &Span) -> Ident {
+    Ident {
+        sym: Symbol::intern(name).fresh(),
+        span: span.clone(),
+    }
+}
+
+fn named(name: &str, span: &Span) -> Ident {
+    Ident {
+        sym: Symbol::intern(name),
+        span: span.clone(),
+    }
+}
+
+/// `option:variant`
+fn option(variant: &str, span: &Span) -> ExprPath {
+    ExprPath {
+        root: None,
+        path: vec![named("option", span), named(variant, span)],
+    }
+}
+
+/// The literal `1`. It doesn't appear in the source, its span holds only its own text.
+fn one(span: &Span) -> Expr {
+    let number = Span::new_extra(RcStr::new("1".to_string()), span.extra.clone());
+    Expr::Number(Box::new(Number {
+        number,
+        suffix: None,
+        base: None,
+    }))
+}
+
+fn block(span: &Span, stmts: Vec<Statement>, returning: Option<Expr>) -> ExprBlock {
+    ExprBlock {
+        span: span.clone(),
+        kind: BlockKind::Many {
+            stmts,
+            returning: returning.map(Box::new),
+        },
+    }
+}
+
+fn stmt_at(expr: Expr, span: &Span) -> Statement {
+    statement((span.clone(), expr))
+}
+
+fn define(pattern: Pattern, value: Expr, span: &Span) -> Statement {
+    let define = Expr::Define(ExprDefine {
+        span_op: span.clone(),
+        pattern,
+        value: Box::new(value),
+    });
+    stmt_at(define, span)
+}
+
+fn assign(assignee: Expr, value: Expr, span: &Span) -> Expr {
+    Expr::Assign(ExprAssignment {
+        span_op: span.clone(),
+        assignee: Box::new(assignee),
+        value: Box::new(value),
+    })
+}
+
+fn binary(op: BiOp, left: Expr, right: Expr, span: &Span) -> Expr {
+    Expr::Binary(ExprBinary {
+        op,
+        op_span: span.clone(),
+        left: Box::new(left),
+        right: Box::new(right),
+    })
+}
+
+/// `if cond { then }`
+fn branch(cond: Expr, then: Expr, span: &Span) -> Expr {
+    Expr::Branch(ExprBranch {
+        span_if: span.clone(),
+        cond: Box::new(cond),
+        body: block(span, vec![], Some(then)),
+        span_else: None,
+        body_else: None,
+    })
+}
+
+/// `break` out of the lowered loop, the innermost one wherever it is used
+fn leave(span: &Span) -> Expr {
+    Expr::Break(ExprBreak {
+        span_break: span.clone(),
+        label: None,
+        value: None,
+    })
+}
@@ -3,17 +3,22 @@
 extern crate core;
 
 use crate::data::err::ErrorCollector;
-use crate::data::md_raw::RawModuleInfo;
 use crate::data::path::PathBuf;
 use crate::data::Module;
 use crate::vmod::ModuleProvider;
-use std::collections::HashMap;
 use typed_arena::Arena;
 use w_parse::Ident;
 
+pub mod attrs;
+pub mod cast;
 pub mod data;
+pub mod defer;
 pub mod elided;
+pub mod for_loop;
 pub mod pass1_tsys;
+pub mod pattern;
+#[cfg(test)]
+mod tests;
 pub mod vmod;
 
 pub struct AnalyzerOptions {
@@ -21,7 +26,7 @@ pub struct AnalyzerOptions {
 }
 
 pub fn build_tsys(
-    vmd: &mut dyn ModuleProvider,
+    _vmd: &mut dyn ModuleProvider,
     opt: AnalyzerOptions,
 ) -> Result<(), ErrorCollector> {
     let collector = ErrorCollector::default();
@@ -29,9 +34,9 @@ pub fn build_tsys(
     let types_arena = Arena::new();
     let modules_arena = Arena::new();
 
-    let root_module = Module::new_root(&modules_arena, &types_arena);
+    let _root_module = Module::new_root(&modules_arena, &types_arena);
 
-    for dep in &opt.dependencies {
+    for _dep in &opt.dependencies {
         // vmd.
     }
 
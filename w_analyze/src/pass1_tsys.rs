@@ -1,20 +1,24 @@
+use crate::attrs::AttrRegistry;
+use crate::cast::check_casts;
 use crate::data::err::{
     ArrayNumberFix, DefinitionKind, MultipleDefinitionsError, UnresolvedTypeError,
 };
 use crate::data::types::{
-    TypeArray, TypeEnum, TypeFunc, TypeInfo, TypeKind, TypeNever, TypePtr, TypeRef, TypeStruct,
-    TypeTuple,
+    TypeArray, TypeEnum, TypeFunc, TypeInfo, TypeKind, TypeNever, TypePtr, TypeStruct, TypeTuple,
 };
+use crate::defer::{check_defers, lower_defers};
+use crate::for_loop::{check_for_loops, lower_for_loops, Iterators};
+use crate::pattern::check_patterns;
 use crate::{ErrorCollector, Module, PathBuf};
 use std::borrow::Cow;
 use w_parse::expr::path::ExprPath;
-use w_parse::item::named::NamedKind;
+use w_parse::item::named::{ItemNamed, NamedKind};
 use w_parse::item::Item;
 use w_parse::types::array::TyArray;
 use w_parse::types::func::TyFunc;
 use w_parse::types::never::TyNever;
 use w_parse::types::ptr::TyPtr;
-use w_parse::types::r#enum::TyEnum;
+use w_parse::types::r#enum::{EnumVariant, TyEnum};
 use w_parse::types::r#struct::TyStruct;
 use w_parse::types::tuple::TyTuple;
 use w_parse::types::ItemTy;
@@ -22,7 +26,9 @@ use w_parse::util::NameTyPair;
 use w_parse::ParsedModule;
 use w_tokenize::Number;
 
-pub fn run_pass1<'gc>(module: &ParsedModule, tsys: &'gc Module<'gc>, errs: &ErrorCollector) {
+/// Builds the types of `module` into `tsys` and checks its function bodies. Bodies that pass
+/// every check are lowered in place, `for` loops and `defer`s are gone from them afterwards.
+pub fn run_pass1<'gc>(module: &mut ParsedModule, tsys: &'gc Module<'gc>, errs: &ErrorCollector) {
     // Imports
     // for item in module.items.iter() {
     //     let def = match item {
@@ -36,11 +42,30 @@ pub fn run_pass1<'gc>(module: &ParsedModule, tsys: &'gc Module<'gc>, errs: &Erro
     //     resolve_imports(&def.imports, root, tsys, errs);
     // }
 
+    // Attributes
+    let attrs = AttrRegistry::default();
+    for item in module.items.iter() {
+        if let Item::Definer(def) = item {
+            attrs.check_item(def, errs);
+        }
+    }
+
+    // Function bodies
+    for item in module.items.iter() {
+        if let Item::Definer(ItemNamed {
+            kind: NamedKind::Func(func),
+            ..
+        }) = item
+        {
+            check_defers(&func.body, errs);
+        }
+    }
+
     // Type definitions
     for item in module.items.iter() {
         let def = match item {
             Item::Definer(def) => def,
-            Item::Import(_) => continue,
+            Item::Import(_) | Item::Error(_) => continue,
         };
 
         let ty = match &def.kind {
@@ -48,7 +73,11 @@ pub fn run_pass1<'gc>(module: &ParsedModule, tsys: &'gc Module<'gc>, errs: &Erro
             NamedKind::Func(_) => continue,
         };
 
-        let tref = tsys.access_or_create_type(&PathBuf::from([def.name.clone()]));
+        let path: Vec<_> = [def.name.clone()]
+            .into_iter()
+            .chain(def.path.iter().cloned())
+            .collect();
+        let tref = tsys.access_or_create_type(&PathBuf::from(path));
 
         if tref.definition.borrow().is_some() {
             errs.add_error(MultipleDefinitionsError {
@@ -70,6 +99,35 @@ pub fn run_pass1<'gc>(module: &ParsedModule, tsys: &'gc Module<'gc>, errs: &Erro
     }
 
     rrc::recursive_reference_check(tsys, errs);
+
+    // Patterns, casts and `for` loops, they need every type to be defined
+    let iterators = Iterators::new(module);
+    for item in module.items.iter() {
+        if let Item::Definer(ItemNamed {
+            kind: NamedKind::Func(func),
+            ..
+        }) = item
+        {
+            check_patterns(&func.body, tsys, errs);
+            check_casts(&func.body, tsys, errs);
+            check_for_loops(&func.body, &iterators, tsys, errs);
+        }
+    }
+    if errs.has_errors() {
+        return;
+    }
+
+    // Lowering, it relies on the bodies having passed the checks above
+    for item in module.items.iter_mut() {
+        if let Item::Definer(ItemNamed {
+            kind: NamedKind::Func(func),
+            ..
+        }) = item
+        {
+            lower_for_loops(&mut func.body);
+            lower_defers(&mut func.body);
+        }
+    }
 }
 
 // fn resolve_imports<'a, 'gc>(
@@ -102,7 +160,7 @@ pub fn run_pass1<'gc>(module: &ParsedModule, tsys: &'gc Module<'gc>, errs: &Erro
 fn build_type<'gc>(ty: &ItemTy, tsys: &'gc Module<'gc>, errs: &ErrorCollector) -> TypeKind<'gc> {
     match ty {
         ItemTy::Referred(reference) => {
-            let (root, path) = conv_path(tsys, reference);
+            let (root, path) = conv_path(tsys, &reference.path);
             TypeKind::Referred(root.access_or_create_type(&path), path)
         }
         ItemTy::Struct(TyStruct {
@@ -112,7 +170,7 @@ fn build_type<'gc>(ty: &ItemTy, tsys: &'gc Module<'gc>, errs: &ErrorCollector) -
             def: span_struct.clone(),
             fields: fields
                 .iter()
-                .map(|NameTyPair { name, ty }| (name.clone(), build_type(ty, tsys, errs)))
+                .map(|NameTyPair { name, ty, .. }| (name.clone(), build_type(ty, tsys, errs)))
                 .collect(),
         }),
         ItemTy::Enum(TyEnum {
@@ -122,10 +180,10 @@ fn build_type<'gc>(ty: &ItemTy, tsys: &'gc Module<'gc>, errs: &ErrorCollector) -
             def: span_enum.clone(),
             variants: variants
                 .iter()
-                .map(|(name, ty)| {
+                .map(|EnumVariant { name, fields, .. }| {
                     (
                         name.clone(),
-                        ty.as_ref().map(|tp| conv_tuple(tp, tsys, errs)),
+                        fields.as_ref().map(|tp| conv_tuple(tp, tsys, errs)),
                     )
                 })
                 .collect(),
@@ -144,7 +202,7 @@ fn build_type<'gc>(ty: &ItemTy, tsys: &'gc Module<'gc>, errs: &ErrorCollector) -
             def: span.clone(),
             ty: Box::new(build_type(ty, tsys, errs)),
             len: if let Some(num) = size {
-                match array_num_to_sized(&**num) {
+                match array_num_to_sized(num) {
                     Ok(n) => Some(n),
                     Err(err) => {
                         errs.add_error(ArrayNumberFix {
@@ -184,8 +242,8 @@ mod rrc {
     use w_parse::Ident;
 
     pub fn recursive_reference_check<'gc>(tsys: &'gc Module<'gc>, errs: &ErrorCollector) {
-        tsys.types.borrow().iter().for_each(|(id, ty)| {
-            rrc_investigate(ty, id.clone(), errs, &mut vec![]);
+        tsys.types.borrow().values().for_each(|ty| {
+            rrc_investigate(ty, ty.loc.name.clone(), errs, &mut vec![]);
         })
     }
 
@@ -217,9 +275,9 @@ mod rrc {
     ) {
         match ty {
             TypeKind::Referred(tr, path) => {
-                rrc_investigate(*tr, path.last().unwrap().clone(), errs, stack)
+                rrc_investigate(tr, path.last().unwrap().clone(), errs, stack)
             }
-            TypeKind::Array(TypeArray { ty, .. }) => rrc_investigate_tk(&*ty, errs, stack),
+            TypeKind::Array(TypeArray { ty, .. }) => rrc_investigate_tk(ty, errs, stack),
             TypeKind::Enum(TypeEnum { variants, .. }) => variants
                 .iter()
                 .filter_map(|(_, v)| v.as_ref())
@@ -265,11 +323,6 @@ fn conv_path<'gc>(tsys: &'gc Module<'gc>, path: &ExprPath) -> (&'gc Module<'gc>,
 }
 
 fn array_num_to_sized(num: &Number) -> Result<u64, Cow<'static, str>> {
-    if let Some(sign) = &num.sign {
-        if **sign != "+" {
-            return Err("Unsigned integers must be positive".into());
-        }
-    }
     if let Some(suffix) = &num.suffix {
         if **suffix != "usize" {
             return Err("Only usize numbers are allowed as array size".into());
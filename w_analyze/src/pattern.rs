@@ -0,0 +1,405 @@
+// Checks the patterns of function bodies against the enums and structs they name
+
+use crate::data::err::{InvalidPatternError, UnresolvedTypeError};
+use crate::data::types::{TypeEnum, TypeInfo, TypeKind, TypeRef, TypeStruct, TypeTuple};
+use crate::{ErrorCollector, Module, PathBuf};
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::mem::discriminant;
+use std::ptr;
+use w_parse::expr::block::ExprBlock;
+use w_parse::expr::branch::ExprBranch;
+use w_parse::expr::define::ExprDefine;
+use w_parse::expr::loops::{ExprFor, ExprWhile};
+use w_parse::expr::path::ExprPath;
+use w_parse::expr::unary::{ExprUnary, UnOp};
+use w_parse::expr::Expr;
+use w_parse::pattern::{PatRange, PatStruct, PatVariant, Pattern};
+use w_parse::visit::{walk_expr, Visitor};
+use w_tokenize::Span;
+
+/// Reports patterns naming types that aren't enums or structs, variants and fields those
+/// don't have, payloads of the wrong length, and refutable patterns outside of conditions.
+///
+/// Nested patterns are checked against the types of the fields they match. The value a
+/// pattern is matched against isn't typed yet, a pattern naming an enum or struct is
+/// trusted to match it.
+pub fn check_patterns<'gc>(body: &ExprBlock, tsys: &'gc Module<'gc>, errs: &ErrorCollector) {
+    PatternCheck { tsys, errs }.visit_block(body);
+}
+
+struct PatternCheck<'a, 'gc> {
+    tsys: &'gc Module<'gc>,
+    errs: &'a ErrorCollector,
+}
+
+impl<'gc> PatternCheck<'_, 'gc> {
+    /// `expected` is the type of the matched value, if it is known
+    fn check(&self, pattern: &Pattern, expected: Option<&TypeKind<'gc>>) {
+        let resolved = expected.and_then(resolve);
+
+        match pattern {
+            Pattern::Bind(_) | Pattern::Fill(_) => {}
+            Pattern::Tuple(span, fields) => match &resolved {
+                Some(TypeKind::Tuple(TypeTuple { fields: types, .. })) => {
+                    if types.len() != fields.len() {
+                        self.error(
+                            span,
+                            format!(
+                                "The tuple has {} fields but the pattern has {}",
+                                types.len(),
+                                fields.len()
+                            ),
+                        );
+                        return;
+                    }
+                    for (field, ty) in fields.iter().zip(types) {
+                        self.check(field, Some(ty));
+                    }
+                }
+                Some(other) => {
+                    let msg = format!("A tuple pattern can't match {}", describe(other));
+                    self.error(span, msg);
+                }
+                None => fields.iter().for_each(|field| self.check(field, None)),
+            },
+            Pattern::Variant(variant) => self.check_variant(variant, expected, resolved),
+            Pattern::Struct(strct) => self.check_struct(strct, expected),
+            Pattern::Literal(lit) => {
+                if let Some(ty @ (TypeKind::Enum(_) | TypeKind::Struct(_) | TypeKind::Tuple(_))) =
+                    &resolved
+                {
+                    let msg = format!("A literal can't match {}", describe(ty));
+                    self.error(&literal_span(lit), msg);
+                }
+            }
+            Pattern::Range(PatRange {
+                span_op,
+                start,
+                end,
+                ..
+            }) => {
+                let ends = start.iter().chain(end.iter());
+                if ends
+                    .clone()
+                    .any(|lit| matches!(**lit, Expr::String(..) | Expr::ByteString(..)))
+                {
+                    self.error(span_op, "Only numbers, chars and bytes form ranges");
+                } else if let (Some(start), Some(end)) = (start, end) {
+                    if discriminant(unsigned(start)) != discriminant(unsigned(end)) {
+                        self.error(span_op, "The ends of the range have different types");
+                    }
+                }
+
+                if let Some(ty @ (TypeKind::Enum(_) | TypeKind::Struct(_) | TypeKind::Tuple(_))) =
+                    &resolved
+                {
+                    let msg = format!("A range can't match {}", describe(ty));
+                    self.error(span_op, msg);
+                }
+            }
+        }
+    }
+
+    fn check_variant(
+        &self,
+        PatVariant { path, payload }: &PatVariant,
+        expected: Option<&TypeKind<'gc>>,
+        resolved: Option<TypeKind<'gc>>,
+    ) {
+        let variant = path.path.last().unwrap();
+
+        // a lone variant takes its enum from the matched value
+        let (name, kind) = if path.path.len() >= 2 {
+            let Some(ty) = self.lookup(path, path.path.len() - 1) else {
+                return;
+            };
+            if !self.expect_named(expected, ty, &path.path[0].span) {
+                return;
+            }
+            (
+                Cow::Owned(format!("`{}`", ty.loc.name.span.fragment())),
+                resolve_ref(ty),
+            )
+        } else {
+            match expected {
+                Some(ty) => (describe(ty), resolved),
+                None => (Cow::Borrowed("the value"), None),
+            }
+        };
+
+        let fields = match &kind {
+            Some(TypeKind::Enum(TypeEnum { variants, .. })) => {
+                match variants.iter().find(|(name, _)| name.sym == variant.sym) {
+                    Some((_, fields)) => fields,
+                    None => {
+                        let msg = format!("{name} has no variant `{}`", variant.span.fragment());
+                        self.error(&variant.span, msg);
+                        return;
+                    }
+                }
+            }
+            Some(_) => {
+                self.error(&path.path[0].span, format!("{name} is not an enum"));
+                return;
+            }
+            None => {
+                if let Some((_, pats)) = payload {
+                    pats.iter().for_each(|pat| self.check(pat, None));
+                }
+                return;
+            }
+        };
+
+        let variant = variant.span.fragment();
+        match (payload, fields) {
+            (Some((span, pats)), Some(TypeTuple { fields, .. })) => {
+                if pats.len() != fields.len() {
+                    let msg = format!(
+                        "The variant `{variant}` has {} fields but the pattern has {}",
+                        fields.len(),
+                        pats.len()
+                    );
+                    self.error(span, msg);
+                    return;
+                }
+                for (pat, ty) in pats.iter().zip(fields) {
+                    self.check(pat, Some(ty));
+                }
+            }
+            (Some((span, _)), None) => {
+                self.error(span, format!("The variant `{variant}` has no fields"));
+            }
+            (None, Some(TypeTuple { fields, .. })) => {
+                let msg = format!(
+                    "The variant `{variant}` has {} fields, the pattern has to match them",
+                    fields.len()
+                );
+                self.error(&path.path.last().unwrap().span, msg);
+            }
+            (None, None) => {}
+        }
+    }
+
+    fn check_struct(
+        &self,
+        PatStruct { path, fields, .. }: &PatStruct,
+        expected: Option<&TypeKind<'gc>>,
+    ) {
+        let Some(ty) = self.lookup(path, path.path.len()) else {
+            return;
+        };
+        if !self.expect_named(expected, ty, &path.path[0].span) {
+            return;
+        }
+
+        let name = ty.loc.name.span.fragment();
+        let types = match resolve_ref(ty) {
+            Some(TypeKind::Struct(TypeStruct { fields, .. })) => fields,
+            Some(_) => {
+                self.error(&path.path[0].span, format!("`{name}` is not a struct"));
+                return;
+            }
+            None => return,
+        };
+
+        let mut seen = HashSet::new();
+        for field in fields {
+            let field_name = field.name.span.fragment();
+            if !seen.insert(field.name.sym) {
+                let msg = format!("The field `{field_name}` is matched more than once");
+                self.error(&field.name.span, msg);
+                continue;
+            }
+
+            match types.iter().find(|(name, _)| name.sym == field.name.sym) {
+                Some((_, ty)) => {
+                    if let Some(pattern) = &field.pattern {
+                        self.check(pattern, Some(ty));
+                    }
+                }
+                None => {
+                    let msg = format!("`{name}` has no field `{field_name}`");
+                    self.error(&field.name.span, msg);
+                }
+            }
+        }
+    }
+
+    /// The type named by the first `len` segments of `path`
+    fn lookup(&self, path: &ExprPath, len: usize) -> Option<&'gc TypeRef<'gc>> {
+        let md = if path.root.is_some() {
+            self.tsys.root()
+        } else {
+            self.tsys
+        };
+
+        let ty = md.access_type(&PathBuf::from(&path.path[..len]));
+        match ty {
+            Some(ty) if ty.definition.borrow().is_some() => Some(ty),
+            _ => {
+                self.errs
+                    .add_error(UnresolvedTypeError(path.path[len - 1].clone()));
+                None
+            }
+        }
+    }
+
+    /// Whether a pattern naming `ty` can match a value of the `expected` type
+    fn expect_named(
+        &self,
+        expected: Option<&TypeKind<'gc>>,
+        ty: &'gc TypeRef<'gc>,
+        loc: &Span,
+    ) -> bool {
+        let matches = match expected {
+            None => true,
+            Some(TypeKind::Referred(expected, _)) => ptr::eq(*expected, ty),
+            Some(_) => false,
+        };
+
+        if !matches {
+            let msg = format!(
+                "The pattern matches `{}` but the value is {}",
+                ty.loc.name.span.fragment(),
+                describe(expected.unwrap())
+            );
+            self.error(loc, msg);
+        }
+        matches
+    }
+
+    fn error(&self, loc: &Span, msg: impl Into<Cow<'static, str>>) {
+        self.errs.add_error(InvalidPatternError {
+            loc: loc.clone(),
+            msg: msg.into(),
+        });
+    }
+
+    fn irrefutable(&self, pattern: &Pattern) {
+        if pattern.is_refutable() {
+            self.error(
+                &pattern_span(pattern),
+                "This pattern doesn't match every value, only `if` and `while` can test it",
+            );
+        }
+    }
+}
+
+impl Visitor for PatternCheck<'_, '_> {
+    fn visit_expr(&mut self, expr: &Expr) {
+        match expr {
+            // conditions are the only place refutable patterns may define names
+            Expr::Branch(ExprBranch {
+                cond,
+                body,
+                body_else,
+                ..
+            }) => {
+                walk_expr(self, cond);
+                self.visit_block(body);
+                if let Some(body_else) = body_else {
+                    self.visit_block(body_else);
+                }
+            }
+            Expr::While(ExprWhile { cond, body, .. }) => {
+                walk_expr(self, cond);
+                self.visit_block(body);
+            }
+            Expr::Define(ExprDefine { pattern, .. }) => {
+                self.irrefutable(pattern);
+                walk_expr(self, expr);
+            }
+            Expr::For(ExprFor { pattern, .. }) => {
+                self.irrefutable(pattern);
+                walk_expr(self, expr);
+            }
+            expr => walk_expr(self, expr),
+        }
+    }
+
+    fn visit_pattern(&mut self, pattern: &Pattern) {
+        self.check(pattern, None);
+    }
+}
+
+/// The definition behind the references of `ty`, `None` if it isn't defined
+fn resolve<'gc>(ty: &TypeKind<'gc>) -> Option<TypeKind<'gc>> {
+    match ty {
+        TypeKind::Referred(ty, _) => resolve_ref(ty),
+        ty => Some(ty.clone()),
+    }
+}
+
+pub(crate) fn resolve_ref<'gc>(mut ty: &'gc TypeRef<'gc>) -> Option<TypeKind<'gc>> {
+    // recursive references are reported by pass 1, give up on them
+    for _ in 0..64 {
+        let kind = match ty.definition.borrow().as_ref()? {
+            TypeInfo::Owned { kind } => kind.clone(),
+            TypeInfo::Proxy(next) => {
+                ty = next;
+                continue;
+            }
+        };
+
+        match kind {
+            TypeKind::Referred(next, _) => ty = next,
+            kind => return Some(kind),
+        }
+    }
+    None
+}
+
+fn describe(ty: &TypeKind) -> Cow<'static, str> {
+    Cow::Borrowed(match ty {
+        TypeKind::Referred(ty, _) => {
+            return Cow::Owned(format!("`{}`", ty.loc.name.span.fragment()))
+        }
+        TypeKind::Array(_) => "an array",
+        TypeKind::Enum(_) => "an enum",
+        TypeKind::Func(_) => "a function",
+        TypeKind::Never(_) => "the never type",
+        TypeKind::Ptr(_) => "a pointer",
+        TypeKind::Struct(_) => "a struct",
+        TypeKind::Tuple(_) => "a tuple",
+    })
+}
+
+fn literal_span(lit: &Expr) -> Span {
+    match lit {
+        Expr::Number(num) => num.number.clone(),
+        Expr::Float(num) => num.integer.clone(),
+        Expr::String(span, _)
+        | Expr::Char(span, _)
+        | Expr::Byte(span, _)
+        | Expr::ByteString(span, _) => span.clone(),
+        Expr::Unary(ExprUnary {
+            op: UnOp::Neg(span),
+            ..
+        }) => span.clone(),
+        _ => unreachable!("patterns only hold literals"),
+    }
+}
+
+/// The literal without its sign
+fn unsigned(lit: &Expr) -> &Expr {
+    match lit {
+        Expr::Unary(ExprUnary { expr, .. }) => expr,
+        lit => lit,
+    }
+}
+
+/// Where the pattern starts
+fn pattern_span(pattern: &Pattern) -> Span {
+    match pattern {
+        Pattern::Bind(ident) => ident.span.clone(),
+        Pattern::Fill(span) | Pattern::Tuple(span, _) => span.clone(),
+        Pattern::Variant(PatVariant { path, .. }) | Pattern::Struct(PatStruct { path, .. }) => {
+            path.path[0].span.clone()
+        }
+        Pattern::Literal(lit) => literal_span(lit),
+        Pattern::Range(PatRange { start, span_op, .. }) => start
+            .as_ref()
+            .map_or_else(|| span_op.clone(), |start| literal_span(start)),
+    }
+}
@@ -0,0 +1,477 @@
+use crate::data::err::ErrorCollector;
+use crate::data::Module;
+use crate::defer::lower_defers;
+use crate::for_loop::lower_for_loops;
+use crate::pass1_tsys::run_pass1;
+use typed_arena::Arena;
+use w_parse::expr::block::{BlockKind, ExprBlock, Statement};
+use w_parse::expr::define::ExprDefine;
+use w_parse::expr::Expr;
+use w_parse::item::named::{ItemNamed, NamedKind};
+use w_parse::item::Item;
+use w_parse::pattern::Pattern;
+use w_parse::{parse_module, Ident, ParsedModule, TokenSpan};
+use w_rcstr::RcStr;
+use w_tokenize::{tokenize, FileId, Span};
+
+fn source(src: impl Into<String>) -> Span {
+    Span::new_extra(RcStr::new(src.into()), FileId::DETACHED.into())
+}
+
+fn parse(src: &str) -> ParsedModule {
+    let span = source(src);
+    let (_, tokens) = tokenize(span.clone()).unwrap();
+    let (_, module) =
+        parse_module(TokenSpan::new(span, tokens), Ident::new(source("test"))).unwrap();
+    module
+}
+
+/// The descriptions of every error pass 1 reports for `src`
+fn analyze(src: &str) -> Vec<String> {
+    let mut module = parse(src);
+
+    let types = Arena::new();
+    let modules = Arena::new();
+    let root = Module::new_root(&modules, &types);
+
+    let errs = ErrorCollector::default();
+    run_pass1(&mut module, root, &errs);
+    errs.descriptions()
+}
+
+#[test]
+fn attributes() {
+    // pass 1 doesn't know the primitives, type definitions stick to types defined here
+    assert!(analyze(
+        "rc :: @(no_copy) struct {\n    inner *rc,\n}\n\
+         trap :: @extern(\"__buildin__\") func() !;\n"
+    )
+    .is_empty());
+
+    assert_eq!(
+        analyze("a :: @copy struct {}\n"),
+        ["Unknown attribute `copy`"]
+    );
+    assert_eq!(
+        analyze("a :: struct {\n    @no_copy x *a,\n}\n"),
+        ["`no_copy` can't be used on struct fields"]
+    );
+    assert_eq!(
+        analyze("f :: @no_copy func() u8 { 1 }\n"),
+        ["`no_copy` can't be used on functions"]
+    );
+    assert_eq!(
+        analyze("f :: func(@extern(\"c\") a u8) u8 { a }\n"),
+        ["`extern` can't be used on function parameters"]
+    );
+    assert_eq!(
+        analyze("f :: @extern func() !;\ng :: @(no_copy(1)) struct {}\n"),
+        [
+            "`extern` takes 1 argument(s) but 0 were given",
+            "`no_copy` takes 0 argument(s) but 1 were given"
+        ]
+    );
+}
+
+#[test]
+fn defers() {
+    assert!(analyze(
+        "f :: func() () {\n    defer a();\n    loop { defer loop { break; }; }\n    \
+         loop @outer { defer loop @inner { loop { break @inner; } }; }\n}\n"
+    )
+    .is_empty());
+
+    assert_eq!(
+        analyze("f :: func() () { defer x := 1; }\n"),
+        ["Definitions can not be deferred"]
+    );
+    assert_eq!(
+        analyze("f :: func() () { a := defer b(); }\n"),
+        ["`defer` can only be used as a statement of a block"]
+    );
+    assert_eq!(
+        analyze("f :: func() () { defer return; }\n"),
+        ["Deferred expressions may not jump out of the `defer`"]
+    );
+    assert_eq!(
+        analyze("f :: func() () { loop { defer if c { break }; } }\n"),
+        ["Deferred expressions may not jump out of the `defer`"]
+    );
+    // the label names a loop outside of the `defer`
+    assert_eq!(
+        analyze("f :: func() () { loop @outer { defer loop { break @outer; }; } }\n"),
+        ["Deferred expressions may not jump out of the `defer`"]
+    );
+}
+
+/// The body of the first item, a function, after lowering it with `lower`
+fn lowered(src: &str, lower: fn(&mut ExprBlock)) -> ExprBlock {
+    let module = parse(src);
+    let Some(Item::Definer(ItemNamed {
+        kind: NamedKind::Func(func),
+        ..
+    })) = module.items.into_iter().next()
+    else {
+        panic!("expected a function");
+    };
+
+    let mut body = func.body;
+    lower(&mut body);
+    body
+}
+
+/// Name of the function called by `expr`, or of the name `expr` is
+fn name(expr: &Expr) -> String {
+    match expr {
+        Expr::Call(call) => name(&call.base),
+        Expr::Ident(ident) => ident.sym.as_str().to_string(),
+        Expr::Define(def) => {
+            let Pattern::Bind(ident) = &def.pattern else {
+                panic!("expected a name to be defined");
+            };
+            format!("{} := {}", ident.sym.as_str(), name(&def.value))
+        }
+        expr => panic!("unexpected {expr:?}"),
+    }
+}
+
+fn statements(block: &ExprBlock) -> (&[Statement], Option<&Expr>) {
+    let BlockKind::Many { stmts, returning } = &block.kind else {
+        panic!("expected a block with statements");
+    };
+    (stmts, returning.as_deref())
+}
+
+#[test]
+fn lowered_defers() {
+    // the value is stored, then the deferred expressions run in reverse
+    let body = lowered(
+        "f :: func() () { defer a(); b(); defer c(); d() }\n",
+        lower_defers,
+    );
+    let (stmts, returning) = statements(&body);
+    let names: Vec<_> = stmts.iter().map(|stmt| name(&stmt.expr)).collect();
+    let value = name(returning.unwrap());
+    assert_eq!(names, ["b", &format!("{value} := d"), "c", "a"]);
+
+    // leaving the loop runs the defers of the loop body only
+    let body = lowered(
+        "f :: func() () { defer a(); loop { defer b(); if c() { break } } }\n",
+        lower_defers,
+    );
+    let (stmts, _) = statements(&body);
+    let Expr::Loop(l) = &stmts[0].expr else {
+        panic!("expected the loop");
+    };
+    let (inner, _) = statements(&l.body);
+    let Expr::Branch(branch) = &inner[0].expr else {
+        panic!("expected the branch");
+    };
+    let (_, Some(Expr::Block(wrapped))) = statements(&branch.body) else {
+        panic!("expected the break to be wrapped");
+    };
+    let (run, jump) = statements(wrapped);
+    assert_eq!(run.len(), 1);
+    assert_eq!(name(&run[0].expr), "b");
+    assert!(matches!(jump, Some(Expr::Break(_))));
+    assert_eq!(name(&stmts[1].expr), "a");
+
+    // returns leave every block, innermost first, after storing their value
+    let body = lowered(
+        "f :: func() () { defer a(); loop { defer b(); return c(); } }\n",
+        lower_defers,
+    );
+    let (stmts, _) = statements(&body);
+    let Expr::Loop(l) = &stmts[0].expr else {
+        panic!("expected the loop");
+    };
+    let Expr::Block(wrapped) = &statements(&l.body).0[0].expr else {
+        panic!("expected the return to be wrapped");
+    };
+    let (run, jump) = statements(wrapped);
+    let names: Vec<_> = run.iter().map(|stmt| name(&stmt.expr)).collect();
+    let Some(Expr::Return(ret)) = jump else {
+        panic!("expected the return");
+    };
+    let value = name(ret.value.as_deref().unwrap());
+    assert_eq!(names, [&format!("{value} := c"), "b", "a"]);
+}
+
+#[test]
+fn patterns() {
+    let types = "shape :: enum {\n    circle(*shape),\n    pair(*shape, *shape),\n    none,\n}\n\
+                 point :: struct {\n    x *point,\n    s shape,\n}\n";
+    let check = |body: &str| analyze(&format!("{types}f :: func() () {{ {body} }}\n"));
+
+    assert!(check(
+        "(a, b) := t;\n\
+         if shape:pair(a, _) := s { a }\n\
+         if point { x, s = shape:circle(_) } := p { x }\n\
+         if point { s = none } := p { }\n\
+         while circle(c) := s { c }"
+    )
+    .is_empty());
+
+    assert_eq!(
+        check("if shape:square := s { }"),
+        ["`shape` has no variant `square`"]
+    );
+    assert_eq!(
+        check("if point { z } := p { }"),
+        ["`point` has no field `z`"]
+    );
+    assert_eq!(
+        check("if point { x, x } := p { }"),
+        ["The field `x` is matched more than once"]
+    );
+
+    // payloads have to match the fields of the variant
+    assert_eq!(
+        check("if shape:pair(a) := s { }"),
+        ["The variant `pair` has 2 fields but the pattern has 1"]
+    );
+    assert_eq!(
+        check("if shape:none(a) := s { }"),
+        ["The variant `none` has no fields"]
+    );
+    assert_eq!(
+        check("if shape:circle := s { }"),
+        ["The variant `circle` has 1 fields, the pattern has to match them"]
+    );
+
+    // nested patterns are checked against the type of the field
+    assert_eq!(
+        check("if point { s = square(a) } := p { }"),
+        ["`shape` has no variant `square`"]
+    );
+    assert_eq!(
+        check("if point { s = point { x } } := p { }"),
+        ["The pattern matches `point` but the value is `shape`"]
+    );
+    assert_eq!(
+        check("if point { s = (a, b) } := p { }"),
+        ["A tuple pattern can't match an enum"]
+    );
+    assert_eq!(
+        check("if point { s = 0 } := p { }"),
+        ["A literal can't match an enum"]
+    );
+
+    // only conditions may test a pattern
+    let refutable = "This pattern doesn't match every value, only `if` and `while` can test it";
+    assert_eq!(check("shape:none := s;"), [refutable]);
+    assert_eq!(check("for (0, a) in items { a }"), [refutable]);
+}
+
+#[test]
+fn casts() {
+    let check = |value: &str, ty: &str| {
+        analyze(&format!(
+            "point :: struct {{\n    x *point,\n}}\nf :: func() () {{ {value} as {ty}; }}\n"
+        ))
+    };
+
+    // values of every kind the check tells apart, and types to cast them to
+    let int = ["1", "-1", "b'a'", "'a'", "1 as u16"];
+    let float = ["1.5", "-1.5", "1 as f64"];
+    let sized = "p as *point";
+    let bytes = ["b\"abc\"", "p as *[]u8"];
+    let str = ["\"abc\"", "p as *str"];
+    let slice = "p as *[]u16";
+
+    let legal = int
+        .iter()
+        .chain(&float)
+        .flat_map(|value| ["u8", "i64", "usize", "f32", "f64"].map(|ty| (*value, ty)))
+        .chain([(sized, "*point"), (sized, "*u8"), (slice, "*[]u16")])
+        .chain(
+            bytes
+                .iter()
+                .flat_map(|value| ["*[]u8", "*str"].map(|ty| (*value, ty))),
+        )
+        .chain(str.iter().map(|value| (*value, "*str")));
+    for (value, ty) in legal {
+        assert!(check(value, ty).is_empty(), "{value} as {ty}");
+    }
+
+    let illegal = [
+        ("1", "*point", "Can't cast an integer to a pointer"),
+        ("'a'", "*str", "Can't cast an integer to `*str`"),
+        ("1.5", "*[]u8", "Can't cast a float to `*[]u8`"),
+        ("\"abc\"", "u8", "Can't cast `*str` to an integer"),
+        ("\"abc\"", "*[]u8", "Can't cast `*str` to `*[]u8`"),
+        ("b\"abc\"", "f32", "Can't cast `*[]u8` to a float"),
+        (
+            "b\"abc\"",
+            "*[]u16",
+            "Can't cast `*[]u8` to a pointer to an unsized array",
+        ),
+        (sized, "usize", "Can't cast a pointer to an integer"),
+        (sized, "*str", "Can't cast a pointer to `*str`"),
+        (
+            sized,
+            "*[]u16",
+            "Can't cast a pointer to a pointer to an unsized array",
+        ),
+        (
+            slice,
+            "*point",
+            "Can't cast a pointer to an unsized array to a pointer",
+        ),
+        (
+            slice,
+            "*[]u8",
+            "Can't cast a pointer to an unsized array to `*[]u8`",
+        ),
+        ("p as *str", "*point", "Can't cast `*str` to a pointer"),
+        ("1", "point", "Can't cast an integer to a struct"),
+        ("1", "str", "Can't cast an integer to `str`"),
+        ("1", "(u8, u8)", "Can't cast an integer to a tuple"),
+        ("1", "[4]u8", "Can't cast an integer to an array"),
+        ("(1, 2)", "u8", "Can't cast a tuple to an integer"),
+        ("[1, 2]", "*[]u8", "Can't cast an array to `*[]u8`"),
+        (
+            "point { x = p }",
+            "*point",
+            "Can't cast a struct to a pointer",
+        ),
+    ];
+    for (value, ty, msg) in illegal {
+        assert_eq!(check(value, ty), [msg], "{value} as {ty}");
+    }
+}
+
+#[test]
+fn for_loops() {
+    let types = "point :: struct {\n    x *point,\n}\n\
+                 iter(T) :: struct {\n    at *point,\n}\n\
+                 iter(T):next :: func(self *iter(T)) option(*point) { none }\n\
+                 bad(T) :: struct {\n    at *point,\n}\n\
+                 bad(T):next :: func(self *bad(T)) *point { at }\n";
+    let check = |body: &str| analyze(&format!("{types}f :: func() () {{ {body} }}\n"));
+
+    assert!(check(
+        "for i in 0..10 { i }\n\
+         for i in 0..=n { i }\n\
+         for i in 1.._ { break }\n\
+         for x in p as iter(point) { x }\n\
+         for x in p as *bad(point) { x }\n\
+         for x in items { x }"
+    )
+    .is_empty());
+
+    let illegal = [
+        ("5", "Can't iterate over an integer"),
+        ("'a'", "Can't iterate over an integer"),
+        ("\"abc\"", "Can't iterate over `*str`"),
+        ("(1, 2)", "Can't iterate over a tuple"),
+        ("p as u8", "Can't iterate over an integer"),
+        ("p as point", "Can't iterate over `point`"),
+        ("1.5..3.0", "Can't iterate over a range of floats"),
+        ("0..-2.5", "Can't iterate over a range of floats"),
+        ("..10", "Can't iterate over a range without a start"),
+        (
+            "p as bad(point)",
+            "Can't iterate over `bad`, its method `next` isn't `next(self) option(T)`",
+        ),
+    ];
+    for (iterable, msg) in illegal {
+        assert_eq!(
+            check(&format!("for x in {iterable} {{ }}")),
+            [msg],
+            "{iterable}"
+        );
+    }
+}
+
+/// The statements before the loop a `for` was lowered into and the statements of the loop
+fn lowered_for(body: &ExprBlock) -> (&[Statement], &[Statement], Option<&Expr>) {
+    let (stmts, _) = statements(body);
+    let Expr::Block(block) = &stmts[0].expr else {
+        panic!("expected the lowered loop");
+    };
+    let (before, Some(Expr::Loop(l))) = statements(block) else {
+        panic!("expected the loop");
+    };
+    let (inner, returning) = statements(&l.body);
+    (before, inner, returning)
+}
+
+#[test]
+fn lowered_for_loops() {
+    let src = |iterable: &str| format!("f :: func() () {{ for i in {iterable} {{ a(); }}; }}\n");
+
+    // values with a `next` method are matched against `option:some`
+    let body = lowered(&src("items"), lower_for_loops);
+    let (before, inner, _) = lowered_for(&body);
+    let iter = name(&before[0].expr);
+    assert!(iter.ends_with(":= items"));
+    let Expr::Match(matched) = &inner[0].expr else {
+        panic!("expected the match");
+    };
+    let Pattern::Variant(some) = &matched.arms[0].pattern else {
+        panic!("expected `option:some`");
+    };
+    let path: Vec<_> = some.path.path.iter().map(|i| i.sym.as_str()).collect();
+    assert_eq!(path, ["option", "some"]);
+    assert_eq!(name(&statements(&matched.arms[0].body).0[0].expr), "a");
+    assert!(matches!(
+        statements(&matched.arms[1].body).1,
+        Some(Expr::Break(_))
+    ));
+
+    // ranges count in a new name, which moves on before the body runs
+    let body = lowered(&src("0..n"), lower_for_loops);
+    let (before, inner, returning) = lowered_for(&body);
+    assert_eq!(before.len(), 2);
+    let Expr::Define(ExprDefine {
+        pattern: Pattern::Bind(next),
+        ..
+    }) = &before[0].expr
+    else {
+        panic!("expected the counter");
+    };
+    assert!(matches!(inner[0].expr, Expr::Branch(_)));
+    assert_eq!(name(&inner[1].expr), format!("i := {}", next.sym.as_str()));
+    assert!(matches!(inner[2].expr, Expr::Assign(_)));
+    assert!(matches!(returning, Some(Expr::Block(_))));
+
+    // inclusive ranges check a flag instead of the end, which may be the largest value
+    let body = lowered(&src("0..=n"), lower_for_loops);
+    let (before, inner, _) = lowered_for(&body);
+    assert_eq!(before.len(), 3);
+    assert_eq!(inner.len(), 4);
+
+    // ranges without an end never stop on their own
+    let body = lowered(&src("0.._"), lower_for_loops);
+    let (before, inner, _) = lowered_for(&body);
+    assert_eq!(before.len(), 1);
+    assert_eq!(inner.len(), 2);
+}
+
+#[test]
+fn pass1_lowers_bodies() {
+    let mut module = parse("f :: func() () { defer a(); for i in 0..n { b(); } }\n");
+
+    let types = Arena::new();
+    let modules = Arena::new();
+    let root = Module::new_root(&modules, &types);
+    let errs = ErrorCollector::default();
+    run_pass1(&mut module, root, &errs);
+    assert!(!errs.has_errors());
+
+    let Some(Item::Definer(ItemNamed {
+        kind: NamedKind::Func(func),
+        ..
+    })) = module.items.first()
+    else {
+        panic!("expected a function");
+    };
+    let (stmts, _) = statements(&func.body);
+    let Expr::Block(lowered) = &stmts[0].expr else {
+        panic!("expected the lowered loop");
+    };
+    assert!(matches!(statements(lowered).1, Some(Expr::Loop(_))));
+    // the deferred call runs at the end of the body
+    assert_eq!(name(&stmts[1].expr), "a");
+}
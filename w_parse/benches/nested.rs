@@ -0,0 +1,69 @@
+//! Parses deeply nested expressions. Doubling the depth should double the time, backtracking
+//! that reparses nested groups shows up as a growing ratio between depths.
+//!
+//! Run with `cargo bench -p w_parse`.
+
+use std::hint::black_box;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use w_parse::{parse_module, Ident, TokenSpan};
+use w_rcstr::RcStr;
+use w_tokenize::{tokenize, FileId, Span};
+
+/// Shapes of nesting, the opening and closing text of one level around the innermost `x`
+const SHAPES: [(&str, &str, &str); 6] = [
+    ("parens", "(", ")"),
+    ("calls", "f(", ")"),
+    ("tuples of defines", "(a := ", ")"),
+    ("conditions", "if (", ") { x }"),
+    ("assigning bodies", "if a { b = ", " }"),
+    ("match arms", "match v { a -> ", " }"),
+];
+
+/// Shapes whose levels hold two expressions, like `if (`, reach `MAX_NESTING` at 64 levels
+const DEPTHS: [usize; 4] = [8, 16, 32, 63];
+
+fn source(src: String) -> Span {
+    Span::new_extra(RcStr::new(src), FileId::DETACHED.into())
+}
+
+fn bench(open: &str, close: &str, depth: usize) -> Duration {
+    let src = format!(
+        "f :: func() u8 {{ {}x{}; }}",
+        open.repeat(depth),
+        close.repeat(depth)
+    );
+    let span = source(src);
+    let (_, tokens) = tokenize(span.clone()).expect("benchmark input tokenizes");
+    let tokens: Arc<[_]> = tokens.into();
+
+    let runs = 5;
+    let mut best = Duration::MAX;
+    for _ in 0..runs {
+        let start = Instant::now();
+        let res = parse_module(
+            TokenSpan::new(span.clone(), tokens.clone()),
+            Ident::new(source("bench".to_string())),
+        );
+        let elapsed = start.elapsed();
+        assert!(res.is_ok(), "benchmark input parses");
+        black_box(res.ok());
+        best = best.min(elapsed);
+    }
+    best
+}
+
+fn main() {
+    println!("parsing nested expressions, best of 5");
+    for (name, open, close) in SHAPES {
+        let mut last: Option<Duration> = None;
+        for depth in DEPTHS {
+            let time = bench(open, close, depth);
+            let ratio = last.map_or(String::new(), |last| {
+                format!(" ({:.1}x)", time.as_secs_f64() / last.as_secs_f64())
+            });
+            println!("{name:>18} {depth:>4}: {time:>10.2?}{ratio}");
+            last = Some(time);
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use crate::expr::many::{parse_tuple, ExprTuple};
+use crate::expr::parse_many0;
+use crate::{parse_identifier, tag, Ident, ParResult, TokenSpan, Weak};
+use nom::combinator::{all_consuming, map, opt};
+use nom::multi::many0;
+use nom::Parser;
+use w_tokenize::{Kind, Span};
+
+#[derive(Debug, Clone)]
+pub struct Attribute {
+    /// The `@` this attribute was written behind
+    pub span_at: Span,
+    pub name: Ident,
+    pub args: Option<ExprTuple>,
+}
+
+/// Any amount of `@name(ARGS)` or `@(name(ARGS), ...)`
+pub fn parse_attributes(i: TokenSpan) -> ParResult<Vec<Attribute>> {
+    map(many0(parse_attribute_group), |groups| {
+        groups.into_iter().flatten().collect()
+    })(i)
+}
+
+fn parse_attribute_group(i: TokenSpan) -> ParResult<Vec<Attribute>> {
+    let (i, at) = Weak(Kind::At).parse(i)?;
+    let span_at = at.span;
+
+    let (i, list) = opt(tag!(Kind::Tuple(_), Token { kind: Kind::Tuple(vals), .. } => vals))(i)?;
+    match list {
+        Some(list) => {
+            let list = TokenSpan::new(i.file.clone(), list);
+            let (_, attrs) = all_consuming(parse_many0(|i| parse_attribute(i, &span_at)))(list)?;
+            Ok((i, attrs))
+        }
+        None => map(|i| parse_attribute(i, &span_at), |attr| vec![attr])(i),
+    }
+}
+
+fn parse_attribute(i: TokenSpan, span_at: &Span) -> ParResult<Attribute> {
+    let (i, name) = parse_identifier(i)?;
+    let (i, args) = opt(parse_tuple)(i)?;
+
+    Ok((
+        i,
+        Attribute {
+            span_at: span_at.clone(),
+            name,
+            args,
+        },
+    ))
+}
@@ -1,8 +1,12 @@
 use crate::parser::TokenSpan;
+use ariadne::{Label, Report, ReportKind};
 use nom::error::{ErrorKind, ParseError};
-use nom::Offset;
 use std::borrow::Cow;
+use std::cell::RefCell;
 use std::fmt::{Debug, Formatter};
+use std::ops::Range;
+use std::sync::Arc;
+use w_tokenize::{FileId, Kind, SpanCtx};
 
 pub struct ErrorChain {
     err_acc: Vec<Error>,
@@ -11,6 +15,8 @@ pub struct ErrorChain {
 pub struct Error {
     pub location: TokenSpan,
     pub reason: Cow<'static, str>,
+    /// What would have been accepted instead of the token at `location`, in words
+    pub expected: Vec<&'static str>,
 }
 
 impl Error {
@@ -18,8 +24,215 @@ impl Error {
         Self {
             location,
             reason: reason.into(),
+            expected: vec![],
         }
     }
+
+    /// "expected `;`, `}` or an operator, found `:=`", or the reason if nothing is expected
+    pub fn message(&self) -> Cow<'static, str> {
+        // tokens first, then what's described in words
+        let mut expected = self.expected.clone();
+        expected.sort_by_key(|what| !what.starts_with('`'));
+
+        let Some((last, rest)) = expected.split_last() else {
+            return self.reason.clone();
+        };
+
+        let expected = match rest {
+            [] => last.to_string(),
+            rest => format!("{} or {last}", rest.join(", ")),
+        };
+        let found = match self.location.first() {
+            Some(token)
+                if matches!(token.kind, Kind::Tuple(_) | Kind::Block(_) | Kind::Array(_)) =>
+            {
+                describe(&token.kind).to_string()
+            }
+            Some(token) => format!("`{}`", token.span.fragment()),
+            None => match self.closing_delimiter() {
+                Some((_, delim)) => format!("`{delim}`"),
+                None => "the end of the input".to_string(),
+            },
+        };
+
+        format!("expected {expected}, found {found}").into()
+    }
+
+    /// Bytes of the offending token, the closing delimiter if the tokens of a group ran
+    /// out, or empty at the end of the input
+    pub fn range(&self) -> Range<usize> {
+        let start = self.location.file_offset();
+        match (self.location.first(), self.closing_delimiter()) {
+            (Some(token), _) => start..start + token.span.len(),
+            (None, Some((offset, _))) => offset..offset + 1,
+            (None, None) => start..start,
+        }
+    }
+
+    /// Offset of the delimiter closing the group whose tokens ran out
+    fn closing_delimiter(&self) -> Option<(usize, char)> {
+        if !self.location.is_empty() {
+            return None;
+        }
+
+        let offset = self.location.file_offset();
+        let rest = &self.location.file.fragment()[offset..];
+        let skipped = rest.len() - rest.trim_start().len();
+        let delim = rest[skipped..].chars().next()?;
+        matches!(delim, ')' | '}' | ']').then_some((offset + skipped, delim))
+    }
+}
+
+thread_local! {
+    /// The failure furthest into the file since the last [`take_furthest`] on this thread
+    static FURTHEST: RefCell<Option<Error>> = const { RefCell::new(None) };
+}
+
+/// Records that `what` would have been accepted at `at`. Only the expectations at the
+/// furthest position reached are kept, that's usually where the actual mistake is.
+///
+/// Tokens of a macro expansion lie in the macro's template, their offsets say nothing about
+/// how far the parser got in the file it parses. A failure at tokens of another file or
+/// expansion than the furthest one starts over instead of being compared to it.
+pub(crate) fn expect(at: &TokenSpan, what: &'static str) {
+    FURTHEST.with_borrow_mut(|furthest| {
+        let offset = at.file_offset();
+        match furthest {
+            Some(err) if err.location.origin() != at.origin() => {
+                let mut err = Error::new(at.clone(), "unexpected token");
+                err.expected.push(what);
+                *furthest = Some(err);
+            }
+            Some(err) if err.location.file_offset() > offset => {}
+            Some(err) if err.location.file_offset() == offset => {
+                if !err.expected.contains(&what) {
+                    err.expected.push(what);
+                }
+            }
+            _ => {
+                let mut err = Error::new(at.clone(), "unexpected token");
+                err.expected.push(what);
+                *furthest = Some(err);
+            }
+        }
+    })
+}
+
+/// State of the furthest failure, see [`summarize`]
+#[derive(Clone)]
+pub(crate) struct Mark {
+    position: Option<(SpanCtx, usize)>,
+    expected: usize,
+}
+
+pub(crate) fn mark() -> Mark {
+    FURTHEST.with_borrow(|furthest| Mark {
+        position: furthest
+            .as_ref()
+            .map(|err| (err.location.origin().clone(), err.location.file_offset())),
+        expected: furthest.as_ref().map_or(0, |err| err.expected.len()),
+    })
+}
+
+/// Replaces what was expected at `at` since `mark` by `what`, like "an expression" instead
+/// of every token an expression can start with
+pub(crate) fn summarize(mark: Mark, at: &TokenSpan, what: &'static str) {
+    let offset = at.file_offset();
+    let unchanged = FURTHEST.with_borrow_mut(|furthest| match furthest {
+        Some(err)
+            if err.location.origin() == at.origin() && err.location.file_offset() == offset =>
+        {
+            let reached = mark
+                .position
+                .as_ref()
+                .map(|(origin, offset)| (origin, *offset));
+            let keep = if reached == Some((at.origin(), offset)) {
+                mark.expected
+            } else {
+                0
+            };
+            err.expected.truncate(keep);
+            false
+        }
+        _ => true,
+    });
+
+    if !unchanged {
+        expect(at, what);
+    }
+}
+
+/// Takes the furthest failure, starting over with the next one
+pub(crate) fn take_furthest() -> Option<Error> {
+    FURTHEST.take()
+}
+
+/// The way a token of this kind is referred to in messages
+pub fn describe(kind: &Kind) -> &'static str {
+    describe_variant(kind.name())
+}
+
+/// See [`describe`], takes the name of the variant of a [`Kind`]
+pub fn describe_variant(name: &str) -> &'static str {
+    match name {
+        "Ident" => "a name",
+        "Fill" => "`_`",
+        "InlineBlk" => "`->`",
+        "Colon" => "`:`",
+        "DoubleCol" => "`::`",
+        "Define" => "`:=`",
+        "Assign" => "`=`",
+        "Comma" => "`,`",
+        "Semicolon" => "`;`",
+        "Dot" => "`.`",
+        "At" => "`@`",
+        "Dollar" => "`$`",
+        "Range" => "`..`",
+        "RangeInclusive" => "`..=`",
+        "Add" => "`+`",
+        "Sub" => "`-`",
+        "Mul" => "`*`",
+        "Div" => "`/`",
+        "Mod" => "`%`",
+        "And" => "`&`",
+        "Or" => "`|`",
+        "Xor" => "`^`",
+        "Shl" => "`<<`",
+        "Shr" => "`>>`",
+        "Eq" => "`==`",
+        "Neq" => "`!=`",
+        "Lt" => "`<`",
+        "Le" => "`<=`",
+        "Gt" => "`>`",
+        "Ge" => "`>=`",
+        "AndL" => "`&&`",
+        "OrL" => "`||`",
+        "Not" => "`!`",
+        "BitNot" => "`~`",
+        "AddAssign" => "`+=`",
+        "SubAssign" => "`-=`",
+        "MulAssign" => "`*=`",
+        "DivAssign" => "`/=`",
+        "ModAssign" => "`%=`",
+        "AndAssign" => "`&=`",
+        "OrAssign" => "`|=`",
+        "XorAssign" => "`^=`",
+        "ShlAssign" => "`<<=`",
+        "ShrAssign" => "`>>=`",
+        "Tuple" => "`(`",
+        "Block" => "`{`",
+        "Array" => "`[`",
+        "String" => "a string",
+        "Number" => "a number",
+        "Float" => "a float",
+        "Char" => "a character",
+        "Byte" => "a byte",
+        "ByteString" => "a byte string",
+        "DocComment" => "a doc comment",
+        "InnerDocComment" => "a module doc comment",
+        "Error" => "an invalid token",
+        _ => "a token",
+    }
 }
 
 impl ErrorChain {
@@ -30,6 +243,74 @@ impl ErrorChain {
     pub fn put_errs(&mut self, mut other: ErrorChain) {
         self.err_acc.append(&mut other.err_acc);
     }
+
+    /// Innermost error first, followed by the parsers that gave up because of it
+    pub fn errors(&self) -> &[Error] {
+        &self.err_acc
+    }
+
+    /// Where in the file the innermost error is
+    pub fn offset(&self) -> usize {
+        self.err_acc
+            .first()
+            .map_or(0, |err| err.location.file_offset())
+    }
+
+    /// Puts the furthest failure recorded since the last call in front, if the parser got
+    /// at least as far there. Only for errors the parser backtracked from, a
+    /// [`nom::Err::Failure`] already is where the mistake is.
+    pub(crate) fn with_furthest(mut self) -> Self {
+        if let Some(furthest) = take_furthest() {
+            let reached = self.err_acc.first().is_none_or(|err| {
+                err.location.origin() == furthest.location.origin()
+                    && err.location.file_offset() <= furthest.location.file_offset()
+            });
+            if reached {
+                self.err_acc.insert(0, furthest);
+            }
+        }
+        self
+    }
+
+    /// The message of the innermost error
+    pub fn message(&self) -> Cow<'static, str> {
+        self.err_acc
+            .first()
+            .map_or("invalid syntax".into(), Error::message)
+    }
+
+    /// Renders the innermost error with a label on the offending token
+    pub fn report(&self) -> Report<(FileId, Range<usize>)> {
+        let Some(err) = self.err_acc.first() else {
+            return Report::build(ReportKind::Error, FileId::DETACHED, 0)
+                .with_message(self.message())
+                .finish();
+        };
+
+        let file = err.location.file_id();
+        let label = match err.location.first() {
+            Some(token) => format!("unexpected {}", describe(&token.kind)),
+            None if err.closing_delimiter().is_some() => "group ends here".to_string(),
+            None => "input ends here".to_string(),
+        };
+
+        Report::build(ReportKind::Error, file, err.range().start)
+            .with_message(err.message())
+            .with_label(Label::new((file, err.range())).with_message(label))
+            .finish()
+    }
+
+    /// Whether both chains start with the same error at the same token
+    pub(crate) fn same_origin(&self, other: &ErrorChain) -> bool {
+        match (self.err_acc.first(), other.err_acc.first()) {
+            (Some(a), Some(b)) => {
+                Arc::ptr_eq(&a.location.tokens, &b.location.tokens)
+                    && a.location.local.start == b.location.local.start
+                    && a.reason == b.reason
+            }
+            _ => false,
+        }
+    }
 }
 
 impl From<Error> for ErrorChain {
@@ -62,45 +343,15 @@ impl Debug for ErrorChain {
 
 impl Debug for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "Error: {}", self.reason)?;
-        if self.location.is_empty() {
-            writeln!(f, "At EOF")?;
-            return Ok(());
-        }
-
-        let last_t = &self.location[0].span;
-        let top_lines = (last_t.location_line() - 1).saturating_sub(3);
-        let top_offset = (last_t.location_line() - 1) - top_lines;
-        let mut lines = self.location.file.lines().skip(top_lines as usize);
-        let mut tmp = vec![];
-
-        for _ in 0..top_offset {
-            tmp.push(lines.next().unwrap());
+        match self.location.first() {
+            Some(token) => write!(
+                f,
+                "{}:{}: {}",
+                token.span.location_line(),
+                token.span.get_utf8_column(),
+                self.message()
+            ),
+            None => write!(f, "at the end: {}", self.message()),
         }
-
-        let main = lines.next().unwrap();
-        let line_offset = (*self.location.file).offset(main);
-        let offset = last_t.location_offset() - line_offset;
-
-        writeln!(
-            f,
-            "At {top_lines}:{offset}\n--------------------------------"
-        )?;
-
-        for line in tmp {
-            writeln!(f, "{line}")?;
-        }
-        writeln!(f, "{main}")?;
-        write!(f, "{}", String::from_iter((0..offset).map(|_| ' ')))?;
-        let len = last_t.len().max(1);
-        writeln!(f, "{}", String::from_iter((0..len).map(|_| '^')))?;
-
-        for line in lines.take(3) {
-            writeln!(f, "{line}")?;
-        }
-
-        write!(f, "--------------------------------")?;
-
-        Ok(())
     }
 }
@@ -1,13 +1,14 @@
+use crate::error::expect;
 use crate::expr::{parse_expression, Expr};
+use crate::recovery::{is_recovering, recover, skip_statement};
 use crate::{tag, ParResult, TokenSpan, Weak};
 use assert_matches::assert_matches;
 use either::Either;
 use nom::branch::alt;
 use nom::combinator::{cond, eof, map, opt};
 use nom::sequence::pair;
-use nom::{Offset, Parser, Slice};
-use std::rc::Rc;
-use w_tokenize::{Kind, Span, Token};
+use nom::{Err, Offset, Parser, Slice};
+use w_tokenize::{Group, Kind, Span, Token};
 
 #[derive(Debug, Clone)]
 pub struct Statement {
@@ -34,35 +35,68 @@ pub fn parse_block(i: TokenSpan) -> ParResult<ExprBlock> {
     alt((parse_block_many, parse_block_inline))(i)
 }
 
-fn parse_block_many(i: TokenSpan) -> ParResult<ExprBlock> {
-    let (oi, block) = Weak(Kind::Block(Rc::from([]))).parse(i)?;
+/// `{ stmts; value }`, the only form of a block that is an expression on its own
+pub(crate) fn parse_block_many(i: TokenSpan) -> ParResult<ExprBlock> {
+    let (oi, block) = Weak(Kind::Block(Group::default())).parse(i)?;
     let span = block.span;
     let mut i =
         assert_matches!(block.kind, Kind::Block(vals) => TokenSpan::new(oi.file.clone(), vals));
 
     let mut acc = vec![];
-    let last;
+    let mut last = None;
 
     loop {
-        let (ni, expr) = parse_expression(i)?;
-        let (ni, sim) = alt((
-            map(
-                pair(
-                    alt((
-                        map(Weak(Kind::Semicolon), Some),
-                        cond(expr.needs_termination(), Weak(Kind::Semicolon)),
-                    )),
-                    opt(eof),
+        i = skip_docs(i);
+        if i.is_empty() {
+            break;
+        }
+
+        let stmt = parse_expression(i.clone()).and_then(|(ni, expr)| {
+            let ni = skip_docs(ni);
+            let sim = alt((
+                map(
+                    pair(
+                        alt((
+                            map(Weak(Kind::Semicolon), Some),
+                            cond(expr.needs_termination(), Weak(Kind::Semicolon)),
+                        )),
+                        opt(eof),
+                    ),
+                    Either::Left,
                 ),
-                Either::Left,
-            ),
-            map(eof, Either::Right),
-        ))(ni)?;
+                map(eof, Either::Right),
+            ))(ni.clone());
+
+            if sim.is_err() {
+                expect(&ni, "`}`");
+            }
+            let (ni, sim) = sim?;
+            Ok((ni, expr, sim))
+        });
+
+        let (ni, expr, sim) = match stmt {
+            Ok(stmt) => stmt,
+            Err(err @ (Err::Error(_) | Err::Failure(_))) if is_recovering() => {
+                recover(err);
+                let skipped = skip_statement(&i);
+                let sim = i[..skipped]
+                    .last()
+                    .filter(|tk| tk.kind == Kind::Semicolon)
+                    .cloned();
+
+                acc.push(Statement {
+                    expr: Expr::Error((&i.slice(..skipped)).into()),
+                    sim,
+                });
+                i = i.slice(skipped..);
+                continue;
+            }
+            Err(err) => return Err(err),
+        };
 
         match sim {
             Either::Left((sim, Some(_))) => {
                 acc.push(Statement { expr, sim });
-                last = None;
                 break;
             }
             Either::Left((sim, None)) => acc.push(Statement { expr, sim }),
@@ -88,6 +122,15 @@ fn parse_block_many(i: TokenSpan) -> ParResult<ExprBlock> {
     ))
 }
 
+/// Doc comments only document items, fields and variants, in a block they're comments
+fn skip_docs(i: TokenSpan) -> TokenSpan {
+    let docs = i
+        .iter()
+        .take_while(|tk| matches!(tk.kind, Kind::DocComment(_)))
+        .count();
+    i.slice(docs..)
+}
+
 fn parse_block_inline(oi: TokenSpan) -> ParResult<ExprBlock> {
     let (i, _arrow) = tag!(Kind::InlineBlk)(oi.clone())?;
     let (i, expr) = parse_expression(i)?;
@@ -1,5 +1,5 @@
 use crate::expr::block::{parse_block, ExprBlock};
-use crate::expr::{parse_expression, Expr};
+use crate::expr::{parse_expression_no_ctor, Expr};
 use crate::{parse_keyword, ParResult, TokenSpan};
 use nom::combinator::opt;
 use nom::sequence::pair;
@@ -18,7 +18,7 @@ pub struct ExprBranch {
 pub fn parse_branch(i: TokenSpan) -> ParResult<ExprBranch> {
     let (i, span_if) = parse_keyword("if")(i)?;
 
-    let (i, cond) = parse_expression(i)?;
+    let (i, cond) = parse_expression_no_ctor(i)?;
     let (i, body) = parse_block(i)?;
 
     let (i, opt_else) = opt(parse_else)(i)?;
@@ -0,0 +1,15 @@
+use crate::expr::Expr;
+use crate::types::ItemTy;
+use w_tokenize::Span;
+
+/// `expr as ty`, converts a number or a pointer into another type.
+///
+/// Casts bind tighter than any binary operator and looser than unary ones, `-x as u8 * y`
+/// is `((-x) as u8) * y`. Chained casts apply from left to right, see
+/// [`CAST_PRIORITY`](crate::expr::ops::CAST_PRIORITY).
+#[derive(Debug, Clone)]
+pub struct ExprCast {
+    pub expr: Box<Expr>,
+    pub span_as: Span,
+    pub ty: ItemTy,
+}
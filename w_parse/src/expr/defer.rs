@@ -0,0 +1,23 @@
+use crate::expr::{parse_expression, Expr};
+use crate::{parse_keyword, ParResult, TokenSpan};
+use nom::combinator::map;
+use w_tokenize::Span;
+
+/// `defer expr`, a statement that postpones the expression until its block is left.
+///
+/// Deferred expressions run in reverse order of their `defer`s, on every way out of the
+/// enclosing block: reaching its end, `return`, and `break` or `continue` of a loop outside
+/// of the block. Only the `defer`s reached before leaving run. The value of a block ending
+/// in an expression is evaluated before any of them.
+#[derive(Debug, Clone)]
+pub struct ExprDefer {
+    pub span_defer: Span,
+    pub expr: Box<Expr>,
+}
+
+pub fn parse_defer(i: TokenSpan) -> ParResult<ExprDefer> {
+    let (i, span_defer) = parse_keyword("defer")(i)?;
+    let (i, expr) = map(parse_expression, Box::new)(i)?;
+
+    Ok((i, ExprDefer { span_defer, expr }))
+}
@@ -1,26 +1,69 @@
-use crate::expr::{parse_expression, Expr};
-use crate::{tag, ParResult, TokenSpan};
-use nom::combinator::map;
-use w_tokenize::Span;
+use crate::expr::{parse_expression, parse_expression_no_ctor, Expr};
+use crate::pattern::{parse_pattern, Pattern};
+use crate::{is_keyword, tag, ParResult, TokenSpan};
+use w_tokenize::{Kind, Span};
 
+/// `pattern := value`, binds the names of the pattern.
+///
+/// As the condition of `if` or `while` the pattern may be refutable, `if option:some(a) := x`
+/// runs its body only if `x` matches. Everywhere else it has to match every value.
 #[derive(Debug, Clone)]
 pub struct ExprDefine {
     pub span_op: Span,
-    pub assignee: Box<Expr>,
+    pub pattern: Pattern,
     pub value: Box<Expr>,
 }
 
-pub fn parse_define(i: TokenSpan) -> ParResult<ExprDefine> {
-    let (i, assignee) = map(parse_expression, Box::new)(i)?;
+/// `ctors` is passed on to the value, see [`parse_expression_no_ctor`]
+pub fn parse_define(i: TokenSpan, ctors: bool) -> ParResult<ExprDefine> {
+    let (i, pattern) = parse_pattern(i)?;
     let (i, span_op) = tag!(Kind::Define)(i)?;
-    let (i, value) = map(parse_expression, Box::new)(i)?;
+    let (i, value) = if ctors {
+        parse_expression(i)?
+    } else {
+        parse_expression_no_ctor(i)?
+    };
 
     Ok((
         i,
         ExprDefine {
             span_op,
-            assignee,
-            value,
+            pattern,
+            value: Box::new(value),
         },
     ))
 }
+
+/// Whether the tokens could be a pattern followed by `:=`. Patterns are made of names,
+/// paths, literals and range operators, and end at the first group, so this only looks at a
+/// few tokens. Parsing a pattern in front of every expression would parse nested groups
+/// once for every level they are nested in.
+pub(crate) fn starts_define(i: &TokenSpan) -> bool {
+    for (idx, tk) in i.iter().enumerate() {
+        match &tk.kind {
+            Kind::Define => return true,
+            Kind::Tuple(_) | Kind::Block(_) => {
+                return i.get(idx + 1).is_some_and(|tk| tk.kind == Kind::Define)
+            }
+            Kind::Ident => {
+                if is_keyword(&tk.span) {
+                    return false;
+                }
+            }
+            Kind::DoubleCol
+            | Kind::Colon
+            | Kind::Fill
+            | Kind::Sub
+            | Kind::Range
+            | Kind::RangeInclusive
+            | Kind::Number(_)
+            | Kind::Float(_)
+            | Kind::String(_)
+            | Kind::Char(_)
+            | Kind::Byte(_)
+            | Kind::ByteString(_) => {}
+            _ => return false,
+        }
+    }
+    false
+}
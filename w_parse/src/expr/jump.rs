@@ -0,0 +1,80 @@
+use crate::expr::loops::{parse_label, Label};
+use crate::expr::{can_begin_expr, parse_expression, Expr};
+use crate::{parse_keyword, ParResult, TokenSpan};
+use nom::combinator::{map, opt};
+use w_tokenize::{Kind, Span};
+
+/// `break`, `break value`, `break @label` or `break @label value`, leaves the innermost or
+/// the labeled loop. Only `loop` takes a value, `while` and `for` always evaluate to `()`.
+#[derive(Debug, Clone)]
+pub struct ExprBreak {
+    pub span_break: Span,
+    pub label: Option<Label>,
+    pub value: Option<Box<Expr>>,
+}
+
+/// `continue` or `continue @label`, starts the next iteration of the innermost or the
+/// labeled loop
+#[derive(Debug, Clone)]
+pub struct ExprContinue {
+    pub span_continue: Span,
+    pub label: Option<Label>,
+}
+
+/// `return` or `return value`, leaves the enclosing function
+#[derive(Debug, Clone)]
+pub struct ExprReturn {
+    pub span_return: Span,
+    pub value: Option<Box<Expr>>,
+}
+
+pub fn parse_break(i: TokenSpan) -> ParResult<ExprBreak> {
+    let (i, span_break) = parse_keyword("break")(i)?;
+    let (i, label) = opt(parse_label)(i)?;
+    let (i, value) = parse_value(i)?;
+
+    Ok((
+        i,
+        ExprBreak {
+            span_break,
+            label,
+            value,
+        },
+    ))
+}
+
+pub fn parse_continue(i: TokenSpan) -> ParResult<ExprContinue> {
+    let (i, span_continue) = parse_keyword("continue")(i)?;
+    let (i, label) = opt(parse_label)(i)?;
+
+    Ok((
+        i,
+        ExprContinue {
+            span_continue,
+            label,
+        },
+    ))
+}
+
+pub fn parse_return(i: TokenSpan) -> ParResult<ExprReturn> {
+    let (i, span_return) = parse_keyword("return")(i)?;
+    let (i, value) = parse_value(i)?;
+
+    Ok((i, ExprReturn { span_return, value }))
+}
+
+/// A jump carries no value when it ends its block or statement, or an inline branch body
+/// in front of `else`. A block behind it is never its value, in a condition like
+/// `if done || return { .. }` it is the body.
+fn parse_value(i: TokenSpan) -> ParResult<Option<Box<Expr>>> {
+    match i.first() {
+        Some(tok)
+            if can_begin_expr(&tok.kind)
+                && !matches!(tok.kind, Kind::Block(_))
+                && !matches!(&**tok.span, "else") =>
+        {
+            map(parse_expression, |expr| Some(Box::new(expr)))(i)
+        }
+        _ => Ok((i, None)),
+    }
+}
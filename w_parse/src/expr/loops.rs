@@ -1,27 +1,135 @@
 use crate::expr::block::{parse_block, ExprBlock};
-use crate::expr::{parse_expression, Expr};
-use crate::{parse_keyword, ParResult, TokenSpan};
-use nom::combinator::map;
-use w_tokenize::Span;
+use crate::expr::{parse_expression_no_ctor, Expr};
+use crate::pattern::{parse_pattern, Pattern};
+use crate::{parse_keyword, parse_name, Ident, ParResult, TokenSpan, Weak};
+use nom::combinator::{map, opt};
+use nom::Parser;
+use w_tokenize::{Kind, Span};
+
+/// `@name` right behind the keyword of a loop, lets `break` and `continue` leave or restart
+/// a loop other than the innermost one
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub span_at: Span,
+    pub name: Ident,
+}
+
+pub fn parse_label(i: TokenSpan) -> ParResult<Label> {
+    let (i, at) = Weak(Kind::At).parse(i)?;
+    let (i, name) = parse_name(i)?;
+
+    Ok((
+        i,
+        Label {
+            span_at: at.span,
+            name,
+        },
+    ))
+}
+
+/// `loop body`, runs the body until a `break` leaves it.
+///
+/// The loop evaluates to the values its `break`s carry, all of which have to agree on a
+/// type, or to `()` if they carry none. A loop that is never left has the never type.
+#[derive(Debug, Clone)]
+pub struct ExprLoop {
+    pub span_loop: Span,
+    pub label: Option<Label>,
+    pub body: ExprBlock,
+}
+
+pub fn parse_loop(i: TokenSpan) -> ParResult<ExprLoop> {
+    let (i, span_loop) = parse_keyword("loop")(i)?;
+    let (i, label) = opt(parse_label)(i)?;
+    let (i, body) = parse_block(i)?;
+
+    Ok((
+        i,
+        ExprLoop {
+            span_loop,
+            label,
+            body,
+        },
+    ))
+}
 
 #[derive(Debug, Clone)]
 pub struct ExprWhile {
     pub span_while: Span,
+    pub label: Option<Label>,
     pub cond: Box<Expr>,
     pub body: ExprBlock,
 }
 
 pub fn parse_while(i: TokenSpan) -> ParResult<ExprWhile> {
     let (i, span_while) = parse_keyword("while")(i)?;
-    let (i, cond) = map(parse_expression, Box::new)(i)?;
+    let (i, label) = opt(parse_label)(i)?;
+    let (i, cond) = map(parse_expression_no_ctor, Box::new)(i)?;
     let (i, body) = parse_block(i)?;
 
     Ok((
         i,
         ExprWhile {
             span_while,
+            label,
             cond,
             body,
         },
     ))
 }
+
+/// `for pattern in iterable body`, runs the body once for every item of the iterable.
+///
+/// The iterable is evaluated once, before the first iteration. What it yields depends on
+/// its type:
+///
+/// - Integer ranges `start..end` and `start..=end` yield every integer from `start` up to
+///   and excluding, or including, `end`, in the integer type of the range. Nothing is
+///   yielded if the range is empty. A range without an end, `start.._`, never stops on its
+///   own.
+/// - Any other value has to provide a method `next(self) option(T)`, which is called before
+///   every iteration. The loop ends at the first `option:none`, the value of every
+///   `option:some` is matched against the pattern. Methods taking `self` by pointer get a
+///   pointer to the value held by the loop.
+///
+/// Either way the loop as a whole is equivalent to
+///
+/// ```w
+/// {
+///     iter := iterable;
+///     while option:some(pattern) := iter.next() body
+/// }
+/// ```
+///
+/// where ranges behave as if they had such a `next` method. The loop itself evaluates to
+/// `()`.
+#[derive(Debug, Clone)]
+pub struct ExprFor {
+    pub span_for: Span,
+    pub label: Option<Label>,
+    pub pattern: Box<Pattern>,
+    pub span_in: Span,
+    pub iterable: Box<Expr>,
+    pub body: ExprBlock,
+}
+
+pub fn parse_for(i: TokenSpan) -> ParResult<ExprFor> {
+    let (i, span_for) = parse_keyword("for")(i)?;
+    let (i, label) = opt(parse_label)(i)?;
+    let (i, pattern) = map(parse_pattern, Box::new)(i)?;
+    let (i, span_in) = parse_keyword("in")(i)?;
+    let (i, iterable) = map(parse_expression_no_ctor, Box::new)(i)?;
+    let (i, body) = parse_block(i)?;
+
+    Ok((
+        i,
+        ExprFor {
+            span_for,
+            label,
+            pattern,
+            span_in,
+            iterable,
+            body,
+        },
+    ))
+}
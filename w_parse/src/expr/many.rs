@@ -5,8 +5,7 @@ use nom::combinator::{all_consuming, map, opt};
 use nom::multi::separated_list0;
 use nom::sequence::{terminated, tuple};
 use nom::Parser;
-use std::rc::Rc;
-use w_tokenize::{Kind, Span};
+use w_tokenize::{Group, Kind, Span};
 
 #[derive(Debug, Clone)]
 pub struct ExprTuple {
@@ -27,7 +26,7 @@ pub struct ExprObject {
 }
 
 pub fn parse_tuple(i: TokenSpan) -> ParResult<ExprTuple> {
-    let (i, tuple) = Weak(Kind::Tuple(Rc::from([]))).parse(i)?;
+    let (i, tuple) = Weak(Kind::Tuple(Group::default())).parse(i)?;
     let span = tuple.span;
     let tuple =
         assert_matches!(tuple.kind, Kind::Tuple(vals) => TokenSpan::new(i.file.clone(), vals));
@@ -37,7 +36,7 @@ pub fn parse_tuple(i: TokenSpan) -> ParResult<ExprTuple> {
 }
 
 pub fn parse_array(i: TokenSpan) -> ParResult<ExprArray> {
-    let (i, array) = Weak(Kind::Array(Rc::from([]))).parse(i)?;
+    let (i, array) = Weak(Kind::Array(Group::default())).parse(i)?;
     let span = array.span;
     let array =
         assert_matches!(array.kind, Kind::Array(vals) => TokenSpan::new(i.file.clone(), vals));
@@ -47,7 +46,7 @@ pub fn parse_array(i: TokenSpan) -> ParResult<ExprArray> {
 }
 
 pub fn parse_object(i: TokenSpan) -> ParResult<ExprObject> {
-    let (i, block) = Weak(Kind::Block(Rc::from([]))).parse(i)?;
+    let (i, block) = Weak(Kind::Block(Group::default())).parse(i)?;
     let span = block.span;
     let block =
         assert_matches!(block.kind, Kind::Block(vals) => TokenSpan::new(i.file.clone(), vals));
@@ -0,0 +1,61 @@
+use crate::expr::block::{parse_block, ExprBlock};
+use crate::expr::many::parse_many0;
+use crate::expr::{parse_expression_no_ctor, Expr};
+use crate::pattern::{parse_pattern, parse_pattern_no_struct, Pattern};
+use crate::{parse_keyword, ParResult, TokenSpan, Weak};
+use assert_matches::assert_matches;
+use nom::branch::alt;
+use nom::combinator::{all_consuming, map};
+use nom::sequence::pair;
+use nom::Parser;
+use w_tokenize::{Group, Kind, Span};
+
+/// `match value { pattern -> expr, pattern { ... } }`, evaluates the body of the first arm
+/// whose pattern matches the value
+#[derive(Debug, Clone)]
+pub struct ExprMatch {
+    pub span_match: Span,
+    pub value: Box<Expr>,
+    /// The block holding the arms
+    pub span: Span,
+    pub arms: Vec<MatchArm>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub body: ExprBlock,
+}
+
+pub fn parse_match(i: TokenSpan) -> ParResult<ExprMatch> {
+    let (i, span_match) = parse_keyword("match")(i)?;
+    let (i, value) = map(parse_expression_no_ctor, Box::new)(i)?;
+
+    let (i, block) = Weak(Kind::Block(Group::default())).parse(i)?;
+    let span = block.span;
+    let block =
+        assert_matches!(block.kind, Kind::Block(vals) => TokenSpan::new(i.file.clone(), vals));
+    let (_, arms) = all_consuming(parse_many0(parse_arm))(block)?;
+
+    Ok((
+        i,
+        ExprMatch {
+            span_match,
+            value,
+            span,
+            arms,
+        },
+    ))
+}
+
+/// `option:none { a }` reads as a struct pattern at first, the braces can only be the body
+/// if nothing follows them
+fn parse_arm(i: TokenSpan) -> ParResult<MatchArm> {
+    map(
+        alt((
+            pair(parse_pattern, parse_block),
+            pair(parse_pattern_no_struct, parse_block),
+        )),
+        |(pattern, body)| MatchArm { pattern, body },
+    )(i)
+}
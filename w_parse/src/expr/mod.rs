@@ -1,4 +1,6 @@
+use crate::error::{expect, mark, summarize, Error};
 use crate::expr::call::{parse_call_wrapper, ExprCall};
+use crate::expr::cast::ExprCast;
 use crate::expr::field::{parse_field_wrapper, ExprField};
 use crate::expr::index::{parse_index_wrapper, ExprIndex};
 use crate::expr::many::{parse_array, parse_tuple, ExprArray, ExprTuple};
@@ -8,58 +10,67 @@ use crate::{parse_name, ErrorChain, Ident, ParResult, TokenSpan};
 
 use nom::branch::alt;
 
-use crate::expr::block::{BlockKind, ExprBlock};
+use crate::expr::block::{parse_block_many, BlockKind, ExprBlock};
 use crate::expr::branch::{parse_branch, ExprBranch};
 use crate::expr::ctor::{parse_ctor, ExprCtor};
-use crate::expr::loops::{parse_while, ExprWhile};
-use crate::expr::ops::{parse_binary_ops, ExprBinary};
+use crate::expr::defer::{parse_defer, ExprDefer};
+use crate::expr::jump::{
+    parse_break, parse_continue, parse_return, ExprBreak, ExprContinue, ExprReturn,
+};
+use crate::expr::loops::{parse_for, parse_loop, parse_while, ExprFor, ExprLoop, ExprWhile};
+use crate::expr::ops::{parse_operators, ExprBinary, ASSIGN_PRIORITY};
+use crate::expr::r#match::{parse_match, ExprMatch};
+use crate::expr::range::ExprRange;
+use crate::types::never::TyNever;
 use nom::combinator::{cond, map, map_opt, opt, verify};
 use nom::error::{ErrorKind, ParseError};
 use nom::multi::many0;
-use nom::{Err, InputTake};
-use w_tokenize::{Number, Span, Token};
+use nom::{Err, InputTake, Slice};
+use std::cell::Cell;
+use w_tokenize::{Float, Kind, Number, Span, Token};
 
-use crate::expr::assign::{parse_assignment, ExprAssignment};
-use crate::expr::define::{parse_define, ExprDefine};
+use crate::expr::assign::ExprAssignment;
+use crate::expr::define::{parse_define, starts_define, ExprDefine};
 pub use many::parse_many0;
 
 pub mod assign;
 pub mod block;
 pub mod branch;
 pub mod call;
+pub mod cast;
 pub mod ctor;
+pub mod defer;
 pub mod define;
 pub mod field;
 pub mod index;
+pub mod jump;
 pub mod loops;
 pub mod many;
+pub mod r#match;
 pub mod ops;
 pub mod path;
+pub mod range;
 pub mod unary;
 
 #[macro_export]
 macro_rules! tag {
-    ($pt:pat) => {{
+    (Kind::$kind:ident $(($($payload:tt)*))?) => {{
         use w_tokenize::Kind;
         $crate::expr::tag(
-            |tk| match &tk.kind {
-                $pt => true,
-                _ => false,
-            },
+            |tk| matches!(&tk.kind, Kind::$kind $(($($payload)*))?),
             |tk| tk.span,
+            $crate::error::describe_variant(stringify!($kind)),
         )
     }};
-    ($pt:pat, $spt:pat => $res:expr) => {{
+    (Kind::$kind:ident $(($($payload:tt)*))?, $spt:pat => $res:expr) => {{
         use w_tokenize::{Kind, Token};
         $crate::expr::tag(
-            |tk| match &tk.kind {
-                $pt => true,
-                _ => false,
-            },
+            |tk| matches!(&tk.kind, Kind::$kind $(($($payload)*))?),
             |tk| match tk {
                 $spt => $res,
                 _ => unreachable!(),
             },
+            $crate::error::describe_variant(stringify!($kind)),
         )
     }};
 }
@@ -76,29 +87,115 @@ pub enum Expr {
     Binary(ExprBinary),
 
     Branch(ExprBranch),
+    Match(ExprMatch),
     While(ExprWhile),
+    For(ExprFor),
+    Loop(ExprLoop),
+
+    Break(ExprBreak),
+    Continue(ExprContinue),
+    Return(ExprReturn),
+    Defer(ExprDefer),
 
     Define(ExprDefine),
     Assign(ExprAssignment),
 
     Number(Box<Number>),
+    Float(Box<Float>),
     String(Span, String),
+    Char(Span, char),
+    Byte(Span, u8),
+    ByteString(Span, Vec<u8>),
     Ident(Ident),
 
     Unary(ExprUnary),
+    Cast(ExprCast),
     Field(ExprField),
     Call(ExprCall),
     Index(ExprIndex),
+
+    Range(ExprRange),
+
+    /// Tokens skipped after a syntax error, only produced when recovering
+    Error(Span),
 }
 
 pub fn parse_expression(i: TokenSpan) -> ParResult<Expr> {
-    parse_expr_pre_pass(i, false)
+    parse_expression_restricted(i, true)
+}
+
+/// An expression without constructors outside of groups, for the heads of `if`, `while`,
+/// `for` and `match`. Their block would be read as the fields of a constructor otherwise,
+/// `if a { b = c }` compares nothing with a constructor of `a`. Constructors within
+/// parentheses are fine, `if (a { b = c }).d {}`.
+pub fn parse_expression_no_ctor(i: TokenSpan) -> ParResult<Expr> {
+    parse_expression_restricted(i, false)
+}
+
+fn parse_expression_restricted(i: TokenSpan, ctors: bool) -> ParResult<Expr> {
+    if i.is_empty() {
+        return Err(Err::Error(ErrorChain::from_error_kind(i, ErrorKind::Eof)));
+    }
+    let _nested = Nested::enter(&i)?;
+
+    let mark = mark();
+    let (i, expr) = if starts_define(&i) {
+        alt((
+            // a pattern followed by `:=`, in front of everything the pattern could be read as
+            map(|i| parse_define(i, ctors), Expr::Define),
+            |i| parse_operators(i, ASSIGN_PRIORITY, ctors),
+        ))(i)?
+    } else {
+        parse_operators(i, ASSIGN_PRIORITY, ctors)?
+    };
+
+    // fields, calls, indices and operators could have continued the expression as well
+    summarize(mark, &i, "an operator");
+    Ok((i, expr))
+}
+
+/// How many expressions may be nested in each other, parsing anything deeper fails.
+///
+/// Every level takes a few frames of the recursive descent. At the limit the parser needs
+/// about 4 MB of stack in release builds and 12 MB in debug builds, more than the 2 MB that
+/// spawned threads get by default. Parse on a thread with a larger stack if the input may
+/// nest that deeply.
+pub const MAX_NESTING: usize = 128;
+
+thread_local! {
+    /// How many expressions the parser is inside of on this thread
+    static NESTING: Cell<usize> = const { Cell::new(0) };
+}
+
+/// An expression being parsed, counted until it is dropped
+struct Nested;
+
+impl Nested {
+    fn enter(i: &TokenSpan) -> Result<Nested, Err<ErrorChain>> {
+        let depth = NESTING.get();
+        if depth >= MAX_NESTING {
+            return Err(Err::Failure(ErrorChain::from(Error::new(
+                i.slice(..1),
+                "Expressions are nested too deeply",
+            ))));
+        }
+
+        NESTING.set(depth + 1);
+        Ok(Nested)
+    }
 }
 
-pub fn parse_expr_pre_pass(i: TokenSpan, deep: bool) -> ParResult<Expr> {
+impl Drop for Nested {
+    fn drop(&mut self) {
+        NESTING.set(NESTING.get() - 1);
+    }
+}
+
+/// An operand, the unary operators in front of it bind tighter than any binary operator
+pub fn parse_expr_pre_pass(i: TokenSpan, ctors: bool) -> ParResult<Expr> {
     let (i, unaries) = many0(parse_unary)(i)?;
 
-    let (i, mut expr) = parse_expr_mid_pass(i, deep)?;
+    let (i, mut expr) = parse_expr_mid_pass(i, ctors)?;
 
     for op in unaries.into_iter().rev() {
         expr = Expr::Unary(ExprUnary {
@@ -109,8 +206,8 @@ pub fn parse_expr_pre_pass(i: TokenSpan, deep: bool) -> ParResult<Expr> {
     Ok((i, expr))
 }
 
-fn parse_expr_mid_pass(i: TokenSpan, deep: bool) -> ParResult<Expr> {
-    let (mut i, mut expr) = parse_expr_post_pass(i, deep)?;
+fn parse_expr_mid_pass(i: TokenSpan, ctors: bool) -> ParResult<Expr> {
+    let (mut i, mut expr) = parse_expr_post_pass(i, ctors)?;
 
     loop {
         let (ni, (nexpr, cont)) = parse_succeeding(i, expr)?;
@@ -141,44 +238,98 @@ fn parse_succeeding(i: TokenSpan, expr: Expr) -> ParResult<(Expr, bool)> {
     Ok((i, ret))
 }
 
-fn parse_expr_post_pass(i: TokenSpan, deep: bool) -> ParResult<Expr> {
+/// Dispatches on the first token, keywords and groups each start one kind of expression
+fn parse_expr_post_pass(i: TokenSpan, ctors: bool) -> ParResult<Expr> {
+    let mark = mark();
+    let res = match i.first().map(|tk| (&tk.kind, &**tk.span)) {
+        Some((Kind::Ident, "if")) => map(parse_branch, Expr::Branch)(i.clone()),
+        Some((Kind::Ident, "match")) => map(parse_match, Expr::Match)(i.clone()),
+        Some((Kind::Ident, "while")) => map(parse_while, Expr::While)(i.clone()),
+        Some((Kind::Ident, "for")) => map(parse_for, Expr::For)(i.clone()),
+        Some((Kind::Ident, "loop")) => map(parse_loop, Expr::Loop)(i.clone()),
+        Some((Kind::Ident, "break")) => map(parse_break, Expr::Break)(i.clone()),
+        Some((Kind::Ident, "continue")) => map(parse_continue, Expr::Continue)(i.clone()),
+        Some((Kind::Ident, "return")) => map(parse_return, Expr::Return)(i.clone()),
+        Some((Kind::Ident, "defer")) => map(parse_defer, Expr::Defer)(i.clone()),
+        Some((Kind::Ident | Kind::DoubleCol | Kind::Colon, _)) => alt((
+            map_opt(cond(ctors, map(parse_ctor, Expr::Ctor)), |x| x),
+            map(verify(parse_path, |pt| pt.path.len() >= 2), Expr::Path),
+            map(parse_name, Expr::Ident),
+        ))(i.clone()),
+        Some((Kind::Tuple(_), _)) => map(parse_tuple, Expr::Tuple)(i.clone()),
+        Some((Kind::Array(_), _)) => map(parse_array, Expr::Array)(i.clone()),
+        Some((Kind::Block(_), _)) => map(parse_block_many, Expr::Block)(i.clone()),
+        _ => parse_literal(i.clone()),
+    };
+
+    if res.is_err() {
+        summarize(mark, &i, "an expression");
+    }
+    res
+}
+
+/// A number, float, string, char, byte or byte string literal
+pub(crate) fn parse_literal(i: TokenSpan) -> ParResult<Expr> {
     alt((
-        map_opt(cond(!deep, map(parse_binary_ops, Expr::Binary)), |x| x),
-        map(parse_ctor, Expr::Ctor),
-        map(verify(parse_path, |pt| pt.path.len() >= 2), Expr::Path),
-        map(parse_name, Expr::Ident),
-        map(parse_tuple, Expr::Tuple),
-        map(parse_array, Expr::Array),
-        map(parse_branch, Expr::Branch),
-        map(parse_while, Expr::While),
-        map(parse_assignment, Expr::Assign),
-        map(parse_define, Expr::Define),
-        tag!(Kind::String(_), Token { kind: Kind::String(num), span } => Expr::String(span, num)),
+        tag!(Kind::String(_), Token { kind: Kind::String(num), span, .. } => Expr::String(span, num)),
+        tag!(Kind::Char(_), Token { kind: Kind::Char(c), span, .. } => Expr::Char(span, c)),
+        tag!(Kind::Byte(_), Token { kind: Kind::Byte(b), span, .. } => Expr::Byte(span, b)),
+        tag!(Kind::ByteString(_), Token { kind: Kind::ByteString(bytes), span, .. } => Expr::ByteString(span, bytes)),
         tag!(Kind::Number(_), Token { kind: Kind::Number(num), .. } => Expr::Number(num)),
+        tag!(Kind::Float(_), Token { kind: Kind::Float(num), .. } => Expr::Float(num)),
     ))(i)
 }
 
+/// Takes a single token `parser` accepts, `expected` describes such tokens in messages
 pub fn tag<O>(
     parser: fn(&Token) -> bool,
     map: fn(Token) -> O,
+    expected: &'static str,
 ) -> impl FnMut(TokenSpan) -> ParResult<O> {
     move |i| {
         if i.is_empty() {
+            expect(&i, expected);
             return Err(Err::Error(ErrorChain::from_error_kind(i, ErrorKind::Eof)));
         }
 
-        let (i, took) = TokenSpan::take_split(&i, 1);
+        let (ni, took) = TokenSpan::take_split(&i, 1);
         if !parser(&took[0]) {
+            expect(&i, expected);
             return Err(Err::Error(ErrorChain::from_error_kind(
                 took.clone(),
                 ErrorKind::Tag,
             )));
         }
 
-        Ok((i, map(took[0].clone())))
+        Ok((ni, map(took[0].clone())))
     }
 }
 
+/// Whether an expression can start with a token of this kind, parsing an expression from
+/// any other token is bound to fail
+pub(crate) fn can_begin_expr(kind: &Kind) -> bool {
+    matches!(
+        kind,
+        Kind::Ident
+            | Kind::Tuple(_)
+            | Kind::Block(_)
+            | Kind::Array(_)
+            | Kind::String(_)
+            | Kind::Number(_)
+            | Kind::Float(_)
+            | Kind::Char(_)
+            | Kind::Byte(_)
+            | Kind::ByteString(_)
+            | Kind::Range
+            | Kind::RangeInclusive
+            | Kind::And
+            | Kind::Mul
+            | Kind::Not
+            | Kind::Sub
+            | Kind::BitNot
+    )
+}
+
 impl Expr {
     pub fn needs_termination(&self) -> bool {
         match self {
@@ -187,19 +338,50 @@ impl Expr {
             | Expr::Path(_)
             | Expr::Ctor(_)
             | Expr::Number(_)
+            | Expr::Float(_)
             | Expr::String(_, _)
+            | Expr::Char(_, _)
+            | Expr::Byte(_, _)
+            | Expr::ByteString(_, _)
             | Expr::Ident(_)
             | Expr::Unary(_)
+            | Expr::Cast(_)
             | Expr::Field(_)
             | Expr::Call(_)
             | Expr::Index(_)
             | Expr::Binary(_)
+            | Expr::Range(_)
             | Expr::Assign(_)
-            | Expr::Define(_) => true,
-            Expr::Branch(_) => false,
-            Expr::Block(body) | Expr::While(ExprWhile { body, .. }) => {
+            | Expr::Define(_)
+            | Expr::Break(_)
+            | Expr::Continue(_)
+            | Expr::Return(_)
+            | Expr::Defer(_) => true,
+            Expr::Branch(_) | Expr::Match(_) | Expr::Error(_) => false,
+            Expr::Block(body)
+            | Expr::While(ExprWhile { body, .. })
+            | Expr::For(ExprFor { body, .. })
+            | Expr::Loop(ExprLoop { body, .. }) => {
                 matches!(body.kind, BlockKind::Inline(_))
             }
         }
     }
+
+    /// The never type of jumps, which don't evaluate to a value since control never reaches
+    /// their end. Loops aren't included, their type depends on the `break`s inside them.
+    pub fn never(&self) -> Option<TyNever> {
+        match self {
+            Expr::Break(ExprBreak {
+                span_break: span, ..
+            })
+            | Expr::Continue(ExprContinue {
+                span_continue: span,
+                ..
+            })
+            | Expr::Return(ExprReturn {
+                span_return: span, ..
+            }) => Some(TyNever(span.clone())),
+            _ => None,
+        }
+    }
 }
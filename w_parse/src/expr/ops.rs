@@ -1,10 +1,14 @@
+use crate::error::{expect, Error};
+use crate::expr::assign::ExprAssignment;
+use crate::expr::cast::ExprCast;
+use crate::expr::range::{parse_range, parse_range_op};
 use crate::expr::{parse_expr_pre_pass, Expr};
-use crate::{ErrorChain, ParResult, TokenSpan};
-use assert_matches::assert_matches;
-use nom::combinator::opt;
+use crate::types::parse_type;
+use crate::{parse_keyword, ErrorChain, ParResult, TokenSpan};
+use nom::combinator::map;
 use nom::error::{ErrorKind, ParseError};
 use nom::Err;
-use nom::InputTake;
+use nom::{InputTake, Slice};
 use w_tokenize::{Kind, Span};
 
 #[derive(Debug, Clone)]
@@ -45,68 +49,143 @@ pub enum BiOp {
     OrL,
 }
 
-pub fn parse_binary_ops(mut i: TokenSpan) -> ParResult<ExprBinary> {
-    let mut exprs = vec![];
-    let mut ops = vec![];
-
-    loop {
-        let (ni, expr) = parse_expr_pre_pass(i, true)?;
-        let (ni, op) = opt(parse_bi_op)(ni)?;
-        i = ni;
+/// How operators of the same priority group. `-` is left associative, `a - b - c` is
+/// `(a - b) - c`, and `=` is right associative, `a = b = c` is `a = (b = c)`. Non associative
+/// operators like `==` can't follow each other without parentheses.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Assoc {
+    Left,
+    Right,
+    None,
+}
 
-        exprs.push(expr);
+/// Priority of `as`, casts bind tighter than every binary operator
+pub const CAST_PRIORITY: u32 = 0;
+/// Priority of `..` and `..=`, only assignments bind weaker than ranges
+pub const RANGE_PRIORITY: u32 = 10;
+/// Priority of `=`, the weakest of all operators
+pub const ASSIGN_PRIORITY: u32 = 11;
+
+/// An operator following an operand
+enum Infix {
+    Binary(Span, BiOp),
+    Cast(Span),
+    /// Not consumed, [`parse_range`] reads the operator itself
+    Range,
+    Assign(Span),
+}
 
-        if let Some(op) = op {
-            ops.push(op);
-        } else {
-            break;
+impl Infix {
+    fn priority(&self) -> u32 {
+        match self {
+            Infix::Binary(_, op) => op.priority(),
+            Infix::Cast(_) => CAST_PRIORITY,
+            Infix::Range => RANGE_PRIORITY,
+            Infix::Assign(_) => ASSIGN_PRIORITY,
         }
     }
 
-    debug_assert_eq!(exprs.len() - 1, ops.len());
-
-    if exprs.len() == 1 {
-        return Err(Err::Error(ErrorChain::from_error_kind(i, ErrorKind::Count)));
+    fn assoc(&self) -> Assoc {
+        match self {
+            Infix::Binary(_, op) => op.assoc(),
+            Infix::Cast(_) => Assoc::Left,
+            Infix::Range => Assoc::None,
+            Infix::Assign(_) => Assoc::Right,
+        }
     }
-
-    eval_order(&mut exprs, &mut ops);
-
-    Ok((
-        i,
-        assert_matches!(exprs.pop().unwrap(), Expr::Binary(bin) => bin),
-    ))
 }
 
-fn eval_order(exprs: &mut Vec<Expr>, ops: &mut Vec<(Span, BiOp)>) {
-    while !ops.is_empty() {
-        let lowest = ops.iter().map(|op| op.1.priority()).min().unwrap();
-
-        while let Some(idx) = ops
-            .iter()
-            .enumerate()
-            .find_map(|(id, op)| (op.1.priority() == lowest).then_some(id))
-        {
-            let (op_span, op) = ops.remove(idx);
-            let left = exprs.remove(idx);
-            let right = exprs.remove(idx);
-
-            exprs.insert(
-                idx,
-                Expr::Binary(ExprBinary {
+/// Parses operands joined by operators with a priority of at most `max`, lower priorities
+/// bind tighter. Every operator is read once, which keeps this linear in the length of the
+/// expression. Constructors are only parsed if `ctors` is set, see
+/// [`parse_expression_no_ctor`](crate::expr::parse_expression_no_ctor).
+pub fn parse_operators(i: TokenSpan, max: u32, ctors: bool) -> ParResult<Expr> {
+    let (mut i, mut expr) = if max >= RANGE_PRIORITY && parse_range_op(i.clone()).is_ok() {
+        let (i, range) = parse_range(i, None, ctors)?;
+        (i, Expr::Range(range))
+    } else {
+        parse_expr_pre_pass(i, ctors)?
+    };
+
+    // priority of the last operator if it is non associative
+    let mut last = None;
+    while let Ok((ni, op)) = parse_infix(i.clone()) {
+        let priority = op.priority();
+        if priority > max {
+            break;
+        }
+        if last == Some(priority) {
+            return Err(Err::Failure(ErrorChain::from(Error::new(
+                i.slice(..1),
+                "Comparisons and ranges can't be chained, add parentheses",
+            ))));
+        }
+        last = (op.assoc() == Assoc::None).then_some(priority);
+
+        // the right operand only takes operators binding tighter, unless this one is right
+        // associative
+        let right_max = match op.assoc() {
+            Assoc::Right => priority,
+            Assoc::Left | Assoc::None => priority.saturating_sub(1),
+        };
+
+        (i, expr) = match op {
+            Infix::Binary(op_span, op) => {
+                let (ni, right) = parse_operators(ni, right_max, ctors)?;
+                let bin = ExprBinary {
                     op,
                     op_span,
-                    left: Box::new(left),
+                    left: Box::new(expr),
                     right: Box::new(right),
-                }),
-            );
-        }
+                };
+                (ni, Expr::Binary(bin))
+            }
+            Infix::Cast(span_as) => {
+                let (ni, ty) = parse_type(ni)?;
+                let cast = ExprCast {
+                    expr: Box::new(expr),
+                    span_as,
+                    ty,
+                };
+                (ni, Expr::Cast(cast))
+            }
+            Infix::Range => {
+                let (ni, range) = parse_range(i, Some(expr), ctors)?;
+                (ni, Expr::Range(range))
+            }
+            Infix::Assign(span_op) => {
+                let (ni, value) = parse_operators(ni, right_max, ctors)?;
+                let asg = ExprAssignment {
+                    span_op,
+                    assignee: Box::new(expr),
+                    value: Box::new(value),
+                };
+                (ni, Expr::Assign(asg))
+            }
+        };
     }
 
-    debug_assert_eq!(exprs.len(), 1);
+    Ok((i, expr))
+}
+
+fn parse_infix(i: TokenSpan) -> ParResult<Infix> {
+    if let Ok((i, span_as)) = parse_keyword("as")(i.clone()) {
+        return Ok((i, Infix::Cast(span_as)));
+    }
+
+    match i.first().map(|tk| &tk.kind) {
+        Some(Kind::Range | Kind::RangeInclusive) => Ok((i, Infix::Range)),
+        Some(Kind::Assign) => {
+            let (i, took) = TokenSpan::take_split(&i, 1);
+            Ok((i, Infix::Assign(took[0].span.clone())))
+        }
+        _ => map(parse_bi_op, |(span, op)| Infix::Binary(span, op))(i),
+    }
 }
 
 pub fn parse_bi_op(i: TokenSpan) -> ParResult<(Span, BiOp)> {
     if i.is_empty() {
+        expect(&i, "an operator");
         return Err(Err::Error(ErrorChain::from_error_kind(i, ErrorKind::Eof)));
     }
 
@@ -131,10 +210,11 @@ pub fn parse_bi_op(i: TokenSpan) -> ParResult<(Span, BiOp)> {
         Kind::AndL => BiOp::AndL,
         Kind::OrL => BiOp::OrL,
         _ => {
+            expect(&took, "an operator");
             return Err(Err::Error(ErrorChain::from_error_kind(
                 took.clone(),
                 ErrorKind::Tag,
-            )))
+            )));
         }
     };
 
@@ -142,29 +222,26 @@ pub fn parse_bi_op(i: TokenSpan) -> ParResult<(Span, BiOp)> {
 }
 
 impl BiOp {
+    /// Lower priorities bind tighter, the table follows Rust. Bitwise operators bind tighter
+    /// than comparisons, `a & b == c` is `(a & b) == c`, and `&&` tighter than `||`.
     pub fn priority(&self) -> u32 {
         match self {
-            BiOp::Add => 1,
-            BiOp::Sub => 1,
-            BiOp::Mul => 0,
-            BiOp::Div => 0,
-            BiOp::Mod => 0,
-
-            BiOp::And => 2,
-            BiOp::Or => 2,
-            BiOp::Xor => 2,
-            BiOp::Shl => 3,
-            BiOp::Shr => 3,
-
-            BiOp::Eq => 4,
-            BiOp::Neq => 4,
-            BiOp::Lt => 4,
-            BiOp::Gt => 4,
-            BiOp::Le => 4,
-            BiOp::Ge => 4,
-
-            BiOp::AndL => 6,
-            BiOp::OrL => 5,
+            BiOp::Mul | BiOp::Div | BiOp::Mod => 1,
+            BiOp::Add | BiOp::Sub => 2,
+            BiOp::Shl | BiOp::Shr => 3,
+            BiOp::And => 4,
+            BiOp::Xor => 5,
+            BiOp::Or => 6,
+            BiOp::Eq | BiOp::Neq | BiOp::Lt | BiOp::Gt | BiOp::Le | BiOp::Ge => 7,
+            BiOp::AndL => 8,
+            BiOp::OrL => 9,
+        }
+    }
+
+    pub fn assoc(&self) -> Assoc {
+        match self {
+            BiOp::Eq | BiOp::Neq | BiOp::Lt | BiOp::Gt | BiOp::Le | BiOp::Ge => Assoc::None,
+            _ => Assoc::Left,
         }
     }
 }
@@ -1,4 +1,5 @@
 use crate::{parse_name, tag, Ident, ParResult, TokenSpan, Weak};
+use nom::branch::alt;
 use nom::combinator::{map, opt};
 use nom::multi::separated_list1;
 use nom::sequence::pair;
@@ -11,11 +12,14 @@ pub struct ExprPath {
     pub path: Vec<Ident>,
 }
 
+/// `a:b:c`, a leading `:` starts at the root module. The tokenizer reads a single `:` as
+/// `::`, either separates the segments. `::` is tried first so a separator that is there
+/// isn't reported as missing.
 pub fn parse_path(i: TokenSpan) -> ParResult<ExprPath> {
     map(
         pair(
-            opt(tag!(Kind::Colon)),
-            separated_list1(Weak(Kind::Colon), parse_name),
+            opt(alt((tag!(Kind::DoubleCol), tag!(Kind::Colon)))),
+            separated_list1(alt((Weak(Kind::DoubleCol), Weak(Kind::Colon))), parse_name),
         ),
         |(root, path)| ExprPath { root, path },
     )(i)
@@ -25,7 +29,7 @@ impl Hash for ExprPath {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.root.is_some().hash(state);
         for ident in &self.path {
-            (*ident.0).hash(state);
+            ident.sym.hash(state);
         }
     }
 }
@@ -40,7 +44,7 @@ impl PartialEq for ExprPath {
                 .path
                 .iter()
                 .zip(other.path.iter())
-                .all(|(a, b)| *a.0 == *b.0)
+                .all(|(a, b)| a.sym == b.sym)
     }
 }
 
@@ -0,0 +1,56 @@
+use crate::error::{Error, ErrorChain};
+use crate::expr::ops::{parse_operators, RANGE_PRIORITY};
+use crate::expr::Expr;
+use crate::{ParResult, TokenSpan, Weak};
+use nom::branch::alt;
+use nom::combinator::{map, opt};
+use nom::{Err, Slice};
+use w_tokenize::{Kind, Span};
+
+#[derive(Debug, Clone)]
+pub struct ExprRange {
+    pub span_op: Span,
+    pub start: Option<Box<Expr>>,
+    /// `None` for both `a..` and `a.._`
+    pub end: Option<Box<Expr>>,
+    pub inclusive: bool,
+}
+
+pub fn parse_range_op(i: TokenSpan) -> ParResult<(Span, bool)> {
+    map(alt((Weak(Kind::Range), Weak(Kind::RangeInclusive))), |tk| {
+        (tk.span, tk.kind == Kind::RangeInclusive)
+    })(i)
+}
+
+/// Parses `..END` or `..=END`, the start has to be parsed beforehand
+pub fn parse_range(i: TokenSpan, start: Option<Expr>, ctors: bool) -> ParResult<ExprRange> {
+    let (ni, (span_op, inclusive)) = parse_range_op(i.clone())?;
+    let (ni, end) = if ni.is_empty() {
+        (ni, None)
+    } else {
+        alt((
+            map(Weak(Kind::Fill), |_| None),
+            opt(map(
+                |i| parse_operators(i, RANGE_PRIORITY - 1, ctors),
+                Box::new,
+            )),
+        ))(ni)?
+    };
+
+    if inclusive && end.is_none() {
+        return Err(Err::Failure(ErrorChain::from(Error::new(
+            i.slice(..1),
+            "Inclusive ranges must have an end",
+        ))));
+    }
+
+    Ok((
+        ni,
+        ExprRange {
+            span_op,
+            start: start.map(Box::new),
+            end,
+            inclusive,
+        },
+    ))
+}
@@ -14,17 +14,30 @@ pub struct ExprUnary {
 pub enum UnOp {
     Ref(Span),
     Deref(Span),
+    /// `!`, logical not
     Not(Span),
+    /// `-`, arithmetic negation
+    Neg(Span),
+    /// `~`, bitwise complement
+    BitNot(Span),
 }
 
 pub fn parse_unary(i: TokenSpan) -> ParResult<UnOp> {
     map(
-        alt((Weak(Kind::And), Weak(Kind::Mul), Weak(Kind::Not))),
+        alt((
+            Weak(Kind::And),
+            Weak(Kind::Mul),
+            Weak(Kind::Not),
+            Weak(Kind::Sub),
+            Weak(Kind::BitNot),
+        )),
         |tk| {
             (match tk.kind {
                 Kind::And => UnOp::Ref,
                 Kind::Mul => UnOp::Deref,
                 Kind::Not => UnOp::Not,
+                Kind::Sub => UnOp::Neg,
+                Kind::BitNot => UnOp::BitNot,
                 _ => unreachable!(),
             })(tk.span)
         },
@@ -0,0 +1,80 @@
+use crate::expr::parse_many0;
+use crate::{parse_name, parse_type, tag, Ident, ItemTy, ParResult, TokenSpan, Weak};
+use nom::branch::alt;
+use nom::combinator::{all_consuming, map, opt};
+use nom::sequence::preceded;
+use w_tokenize::{Kind, Span};
+
+/// `(T, U(bound, ...), V=default)` behind the name of an item
+#[derive(Debug, Clone)]
+pub struct Generics {
+    pub span: Span,
+    pub params: Vec<GenericParam>,
+}
+
+#[derive(Debug, Clone)]
+pub struct GenericParam {
+    pub name: Ident,
+    pub bounds: Vec<ItemTy>,
+    pub default: Option<ItemTy>,
+}
+
+/// `(ty, _, ...)` applied to a type reference
+#[derive(Debug, Clone)]
+pub struct GenericArgs {
+    pub span: Span,
+    pub args: Vec<GenericArg>,
+}
+
+#[derive(Debug, Clone)]
+pub enum GenericArg {
+    Type(ItemTy),
+    /// `_`, left to be inferred
+    Fill(Span),
+}
+
+pub fn parse_generics(i: TokenSpan) -> ParResult<Generics> {
+    let (i, (span, tuple)) =
+        tag!(Kind::Tuple(_), Token { kind: Kind::Tuple(vals), span, .. } => (span, vals))(i)?;
+    let tuple = TokenSpan::new(i.file.clone(), tuple);
+
+    let (_, params) = all_consuming(parse_many0(parse_generic_param))(tuple)?;
+
+    Ok((i, Generics { span, params }))
+}
+
+fn parse_generic_param(i: TokenSpan) -> ParResult<GenericParam> {
+    let (i, name) = parse_name(i)?;
+    let (i, bounds) = opt(parse_bounds)(i)?;
+    let (i, default) = opt(preceded(Weak(Kind::Assign), parse_type))(i)?;
+
+    Ok((
+        i,
+        GenericParam {
+            name,
+            bounds: bounds.unwrap_or_default(),
+            default,
+        },
+    ))
+}
+
+fn parse_bounds(i: TokenSpan) -> ParResult<Vec<ItemTy>> {
+    let (i, tuple) = tag!(Kind::Tuple(_), Token { kind: Kind::Tuple(vals), .. } => vals)(i)?;
+    let tuple = TokenSpan::new(i.file.clone(), tuple);
+    let (_, bounds) = all_consuming(parse_many0(parse_type))(tuple)?;
+
+    Ok((i, bounds))
+}
+
+pub fn parse_generic_args(i: TokenSpan) -> ParResult<GenericArgs> {
+    let (i, (span, tuple)) =
+        tag!(Kind::Tuple(_), Token { kind: Kind::Tuple(vals), span, .. } => (span, vals))(i)?;
+    let tuple = TokenSpan::new(i.file.clone(), tuple);
+
+    let (_, args) = all_consuming(parse_many0(alt((
+        map(tag!(Kind::Fill), GenericArg::Fill),
+        map(parse_type, GenericArg::Type),
+    ))))(tuple)?;
+
+    Ok((i, GenericArgs { span, args }))
+}
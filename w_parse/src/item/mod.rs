@@ -3,6 +3,7 @@ use crate::item::named::ItemNamed;
 use crate::{ParResult, TokenSpan};
 use nom::branch::alt;
 use nom::combinator::map;
+use w_tokenize::Span;
 
 pub mod func;
 pub mod import;
@@ -13,6 +14,8 @@ pub mod named;
 pub enum Item {
     Definer(ItemNamed),
     Import(ItemImports),
+    /// Tokens skipped after a syntax error, only produced when recovering
+    Error(Span),
 }
 
 pub fn parse_item(i: TokenSpan) -> ParResult<Item> {
@@ -1,13 +1,23 @@
+use crate::attr::{parse_attributes, Attribute};
+use crate::generics::{parse_generics, Generics};
 use crate::item::func::{parse_item_func, ItemFunc};
+use crate::util::{parse_docs, Docs};
 use crate::{parse_name, parse_type, Ident, ItemTy, ParResult, TokenSpan, Weak};
 use nom::branch::alt;
-use nom::combinator::{cond, map};
+use nom::combinator::{cond, map, opt, peek, verify};
+use nom::multi::many1;
+use nom::sequence::{preceded, terminated};
 use nom::Parser;
 use w_tokenize::{Kind, Token};
 
 #[derive(Debug, Clone)]
 pub struct ItemNamed {
+    pub docs: Docs,
     pub name: Ident,
+    pub generics: Option<Generics>,
+    /// Names behind the generics, `iter(T):next` defines `next` inside of `iter`
+    pub path: Vec<Ident>,
+    pub attrs: Vec<Attribute>,
     pub kind: NamedKind,
 }
 
@@ -24,15 +34,42 @@ pub struct ItemNamedType {
 }
 
 pub fn parse_named(i: TokenSpan) -> ParResult<ItemNamed> {
+    let (i, docs) = parse_docs(i)?;
     let (i, name) = parse_name(i)?;
+    let (i, generics) = opt(parse_generics)(i)?;
+    let (i, path) = match generics {
+        Some(_) => opt(parse_item_path)(i)?,
+        None => (i, None),
+    };
     let (i, _) = Weak(Kind::DoubleCol).parse(i)?;
+    let (i, attrs) = parse_attributes(i)?;
 
     let (i, kind) = alt((
         map(parse_item_func, NamedKind::Func),
         map(parse_type_definer, NamedKind::Type),
     ))(i)?;
 
-    Ok((i, ItemNamed { name, kind }))
+    Ok((
+        i,
+        ItemNamed {
+            docs,
+            name,
+            generics,
+            path: path.unwrap_or_default(),
+            attrs,
+            kind,
+        },
+    ))
+}
+
+/// `:next` in `iter(T):next ::`. The tokenizer reads `:` as `::` as well, only a `::` written
+/// out ends the path, `iter(T) :: a:b;` still defines `iter` as `a:b`.
+fn parse_item_path(i: TokenSpan) -> ParResult<Vec<Ident>> {
+    let double_col = verify(Weak(Kind::DoubleCol), |tk: &Token| &**tk.span == "::");
+    terminated(
+        many1(preceded(Weak(Kind::DoubleCol), parse_name)),
+        peek(double_col),
+    )(i)
 }
 
 pub fn parse_type_definer(i: TokenSpan) -> ParResult<ItemNamedType> {
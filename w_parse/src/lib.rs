@@ -1,12 +1,20 @@
 #![cfg_attr(debug_assertions, allow(dead_code))]
 
+pub mod attr;
 pub mod error;
 pub mod expr;
+pub mod generics;
 pub mod item;
+pub mod macros;
 pub mod module;
 pub mod parser;
+pub mod pattern;
+mod recovery;
+#[cfg(test)]
+mod tests;
 pub mod types;
 pub mod util;
+pub mod visit;
 
 use crate::error::{Error, ErrorChain};
 use crate::parser::Weak;
@@ -15,16 +23,34 @@ use nom::combinator::{map, verify};
 use nom::{Err, Parser};
 use std::borrow::Cow;
 use std::hash::{Hash, Hasher};
-use std::rc::Rc;
-use w_tokenize::{Kind, Span};
+use std::sync::Arc;
+use w_tokenize::{Kind, Span, Symbol};
 
 pub use crate::parser::{ParResult, TokenSpan};
-pub use module::{parse_module, ParsedModule};
+pub use macros::expand_macros;
+pub use module::{parse_module, parse_module_recovering, ParsedModule};
 
-pub type SVec<T> = Rc<[T]>;
+pub type SVec<T> = Arc<[T]>;
 
+/// Identifier with its text interned, comparing and hashing only looks at the [`Symbol`]
 #[derive(Debug, Clone)]
-pub struct Ident(pub Span);
+pub struct Ident {
+    pub sym: Symbol,
+    pub span: Span,
+}
+
+impl Ident {
+    /// Names bound by a macro template get a symbol of their own for every expansion
+    pub fn new(span: Span) -> Self {
+        let sym = Symbol::intern(&span);
+        let sym = match &span.extra.expansion {
+            Some(expn) if expn.binds(sym) => sym.hygienic(expn),
+            _ => sym,
+        };
+
+        Ident { sym, span }
+    }
+}
 
 pub fn parse(i: TokenSpan) -> ParResult<()> {
     Ok((i, ()))
@@ -32,7 +58,7 @@ pub fn parse(i: TokenSpan) -> ParResult<()> {
 
 fn parse_keyword(specific: &str) -> impl FnMut(TokenSpan) -> ParResult<Span> + '_ {
     move |i| {
-        verify(map(parse_identifier, |id| id.0), |ident| {
+        verify(map(Weak(Kind::Ident), |tok| tok.span), |ident| {
             **ident == specific
         })(i)
     }
@@ -40,7 +66,7 @@ fn parse_keyword(specific: &str) -> impl FnMut(TokenSpan) -> ParResult<Span> + '
 
 fn parse_identifier(i: TokenSpan) -> ParResult<Ident> {
     let (i, tok) = Weak(Kind::Ident).parse(i)?;
-    Ok((i, Ident(tok.span)))
+    Ok((i, Ident::new(tok.span)))
 }
 
 fn quick_err<T>(span: TokenSpan, reason: impl Into<Cow<'static, str>>) -> ParResult<T> {
@@ -52,31 +78,42 @@ fn parse_name(i: TokenSpan) -> ParResult<Ident> {
 }
 
 fn keyword_check(ident: &Ident) -> bool {
-    !matches!(
-        &**ident.0,
+    !is_keyword(&ident.span)
+}
+
+fn is_keyword(word: &str) -> bool {
+    matches!(
+        word,
         "struct"
             | "enum"
             | "func"
             | "for"
             | "while"
+            | "in"
             | "loop"
+            | "break"
+            | "continue"
+            | "return"
             | "if"
+            | "match"
             | "else"
             | "mut"
+            | "as"
             | "defer"
             | "mod"
+            | "macro"
     )
 }
 
 impl Hash for Ident {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        (*self.0).hash(state)
+        self.sym.hash(state)
     }
 }
 
 impl PartialEq for Ident {
     fn eq(&self, other: &Self) -> bool {
-        (*self.0) == (*other.0)
+        self.sym == other.sym
     }
 }
 impl Eq for Ident {}
@@ -0,0 +1,718 @@
+//! Macros working on token trees, expanded before the module is parsed.
+//!
+//! ```w
+//! square :: macro {
+//!     ($x:expr) -> { $x * $x },
+//!     ($($x:expr),+) -> { ($(square!($x)),+) },
+//! }
+//! ```
+//!
+//! Each rule pairs a pattern with a template. The first rule whose pattern matches the
+//! tokens of an invocation `square!(..)` is transcribed in its place, the result is
+//! expanded again. Names the template defines with `:=` are hygienic, they neither see nor
+//! shadow the names at the call site. Captured expressions keep their precedence, `square!(a
+//! + 1)` is `(a + 1) * (a + 1)`.
+
+use crate::error::{Error, ErrorChain};
+use crate::expr::{can_begin_expr, parse_expression};
+use crate::types::parse_type;
+use crate::{keyword_check, Ident, TokenSpan};
+use nom::Slice;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::Arc;
+use w_tokenize::{ExpnData, ExpnId, Group, Kind, Span, Symbol, Token};
+
+/// Invocations can expand to further invocations at most this deep
+const RECURSION_LIMIT: usize = 64;
+
+pub struct MacroDef {
+    pub name: Ident,
+    rules: Vec<Rule>,
+}
+
+struct Rule {
+    pattern: Vec<Matcher>,
+    template: Arc<[Token]>,
+    /// Identifiers the template binds with `:=`
+    locals: Vec<Symbol>,
+}
+
+enum Matcher {
+    /// Has to appear as is
+    Token(Token),
+    /// A group with the same delimiters, whose content matches
+    Group(Token, Vec<Matcher>),
+    /// `$name:fragment`
+    Fragment(Ident, Fragment),
+    /// `$( ... ) sep? *` or `$( ... ) sep? +`
+    Repeat {
+        inner: Vec<Matcher>,
+        sep: Option<Token>,
+        at_least_one: bool,
+    },
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Fragment {
+    Expr,
+    Type,
+    Ident,
+}
+
+/// Tokens captured by a metavariable, once per repetition it's nested in
+#[derive(Clone)]
+enum Capture {
+    Tokens(Vec<Token>),
+    Repeated(Vec<Capture>),
+}
+
+type Bindings = HashMap<Symbol, Capture>;
+
+/// Removes the macro definitions of a module and expands every invocation of them.
+///
+/// The tokens of an expansion keep the spans of the template they come from, the
+/// [`w_tokenize::SpanCtx`] of those spans leads to the invocation. Tokens passed to the
+/// macro keep their spans from the call site.
+pub fn expand_macros(i: TokenSpan) -> Result<TokenSpan, ErrorChain> {
+    let expander = Expander::collect(&i)?;
+    let tokens = expander.expand(&i.tokens, i.local.clone(), true, 0)?;
+    Ok(TokenSpan::new(i.file, tokens))
+}
+
+struct Expander {
+    file: Span,
+    macros: HashMap<Symbol, MacroDef>,
+    /// Ranges of the top-level tokens taken by definitions
+    definitions: Vec<Range<usize>>,
+}
+
+impl Expander {
+    fn collect(i: &TokenSpan) -> Result<Expander, ErrorChain> {
+        let mut expander = Expander {
+            file: i.file.clone(),
+            macros: HashMap::new(),
+            definitions: vec![],
+        };
+
+        let tokens = &i.tokens;
+        let mut idx = i.local.start;
+        while idx < i.local.end {
+            let is_def = matches!(
+                tokens[idx..i.local.end],
+                [Token {
+                    kind: Kind::Ident,
+                    ..
+                }, Token {
+                    kind: Kind::DoubleCol,
+                    ..
+                }, Token {
+                    kind: Kind::Ident,
+                    span: ref kw,
+                    ..
+                }, Token {
+                    kind: Kind::Block(_),
+                    ..
+                }, ..] if **kw == "macro"
+            );
+
+            if !is_def {
+                idx += 1;
+                continue;
+            }
+
+            let name = Ident::new(tokens[idx].span.clone());
+            if !keyword_check(&name) {
+                return Err(expander.error(tokens, idx, "expected a name for the macro"));
+            }
+
+            let Kind::Block(rules) = &tokens[idx + 3].kind else {
+                unreachable!()
+            };
+            let rules = expander.rules(&rules.tokens())?;
+
+            // documentation of the macro goes away with it
+            let mut start = idx;
+            while start > i.local.start && matches!(tokens[start - 1].kind, Kind::DocComment(_)) {
+                start -= 1;
+            }
+            expander.definitions.push(start..idx + 4);
+
+            if expander.macros.contains_key(&name.sym) {
+                return Err(expander.error(tokens, idx, "macro is defined multiple times"));
+            }
+            expander.macros.insert(name.sym, MacroDef { name, rules });
+
+            idx += 4;
+        }
+
+        Ok(expander)
+    }
+
+    /// `(pattern) -> { template }` separated by optional `,` or `;`
+    fn rules(&self, tokens: &Arc<[Token]>) -> Result<Vec<Rule>, ErrorChain> {
+        let mut rules = vec![];
+        let mut idx = 0;
+
+        while idx < tokens.len() {
+            let (Some(Kind::Tuple(pattern)), Some(Kind::InlineBlk), Some(Kind::Block(template))) = (
+                tokens.get(idx).map(|tk| &tk.kind),
+                tokens.get(idx + 1).map(|tk| &tk.kind),
+                tokens.get(idx + 2).map(|tk| &tk.kind),
+            ) else {
+                return Err(self.error(tokens, idx, "expected a rule `(pattern) -> { template }`"));
+            };
+
+            rules.push(Rule {
+                pattern: self.pattern(&pattern.tokens())?,
+                template: template.tokens(),
+                locals: locals(&template.tokens()),
+            });
+
+            idx += 3;
+            if tokens
+                .get(idx)
+                .is_some_and(|tk| matches!(tk.kind, Kind::Comma | Kind::Semicolon))
+            {
+                idx += 1;
+            }
+        }
+
+        if rules.is_empty() {
+            return Err(ErrorChain::from(Error::new(
+                TokenSpan::new(self.file.clone(), tokens.clone()),
+                "macro needs at least one rule",
+            )));
+        }
+
+        Ok(rules)
+    }
+
+    fn pattern(&self, tokens: &Arc<[Token]>) -> Result<Vec<Matcher>, ErrorChain> {
+        let mut matchers = vec![];
+        let mut idx = 0;
+
+        while idx < tokens.len() {
+            let token = &tokens[idx];
+
+            if !matches!(token.kind, Kind::Dollar) {
+                matchers.push(match &token.kind {
+                    Kind::Tuple(inner) | Kind::Block(inner) | Kind::Array(inner) => {
+                        Matcher::Group(token.clone(), self.pattern(&inner.tokens())?)
+                    }
+                    _ => Matcher::Token(token.clone()),
+                });
+                idx += 1;
+                continue;
+            }
+
+            match tokens.get(idx + 1).map(|tk| &tk.kind) {
+                Some(Kind::Ident) => {
+                    let name = Ident::new(tokens[idx + 1].span.clone());
+                    let fragment = match tokens.get(idx + 2..idx + 4) {
+                        Some([colon, frag])
+                            if colon.kind == Kind::DoubleCol && frag.kind == Kind::Ident =>
+                        {
+                            match &**frag.span {
+                                "expr" => Fragment::Expr,
+                                "type" => Fragment::Type,
+                                "ident" => Fragment::Ident,
+                                _ => {
+                                    return Err(self.error(
+                                        tokens,
+                                        idx + 3,
+                                        "unknown fragment, expected `expr`, `type` or `ident`",
+                                    ))
+                                }
+                            }
+                        }
+                        _ => {
+                            return Err(self.error(
+                                tokens,
+                                idx + 1,
+                                "metavariable needs a fragment like `$name:expr`",
+                            ))
+                        }
+                    };
+
+                    matchers.push(Matcher::Fragment(name, fragment));
+                    idx += 4;
+                }
+                Some(Kind::Tuple(inner)) => {
+                    let (sep, at_least_one, len) = self.repetition(tokens, idx + 2)?;
+                    matchers.push(Matcher::Repeat {
+                        inner: self.pattern(&inner.tokens())?,
+                        sep,
+                        at_least_one,
+                    });
+                    idx += 2 + len;
+                }
+                _ => {
+                    return Err(self.error(
+                        tokens,
+                        idx,
+                        "expected a metavariable or a repetition after `$`",
+                    ))
+                }
+            }
+        }
+
+        Ok(matchers)
+    }
+
+    /// The optional separator and operator following the group of a repetition at `idx`,
+    /// returns whether it repeats at least once and how many tokens it took
+    fn repetition(
+        &self,
+        tokens: &Arc<[Token]>,
+        idx: usize,
+    ) -> Result<(Option<Token>, bool, usize), ErrorChain> {
+        let op = |tk: Option<&Token>| match tk.map(|tk| &tk.kind) {
+            Some(Kind::Mul) => Some(false),
+            Some(Kind::Add) => Some(true),
+            _ => None,
+        };
+
+        if let Some(at_least_one) = op(tokens.get(idx)) {
+            return Ok((None, at_least_one, 1));
+        }
+
+        match (tokens.get(idx), op(tokens.get(idx + 1))) {
+            (Some(sep), Some(at_least_one)) if group_tokens(&sep.kind).is_none() => {
+                Ok((Some(sep.clone()), at_least_one, 2))
+            }
+            _ => Err(self.error(
+                tokens,
+                idx.min(tokens.len() - 1),
+                "expected `*` or `+` to end the repetition",
+            )),
+        }
+    }
+
+    /// Expands the invocations in `range` of `tokens`, skipping the definitions if they are
+    /// the `top` level of the module. `depth` counts the expansions the tokens came from,
+    /// groups don't add to it.
+    fn expand(
+        &self,
+        tokens: &Arc<[Token]>,
+        range: Range<usize>,
+        top: bool,
+        depth: usize,
+    ) -> Result<Arc<[Token]>, ErrorChain> {
+        let mut acc = Vec::with_capacity(range.len());
+        let mut changed = range.len() != tokens.len();
+        let mut idx = range.start;
+
+        while idx < range.end {
+            if top {
+                if let Some(def) = self.definitions.iter().find(|def| def.start == idx) {
+                    idx = def.end;
+                    changed = true;
+                    continue;
+                }
+            }
+
+            let token = &tokens[idx];
+
+            if let Some(def) = self.invoked(tokens, idx) {
+                if depth >= RECURSION_LIMIT {
+                    return Err(self.error(
+                        tokens,
+                        idx,
+                        "recursion limit reached while expanding the macro",
+                    ));
+                }
+
+                let expanded: Arc<[Token]> = self.invoke(def, tokens, idx)?.into();
+                let expanded = self.expand(&expanded, 0..expanded.len(), false, depth + 1)?;
+                acc.extend(expanded.iter().cloned());
+                changed = true;
+                idx += 3;
+                continue;
+            }
+
+            acc.push(match group_tokens(&token.kind) {
+                Some(inner) => {
+                    let expanded = self.expand(&inner, 0..inner.len(), false, depth)?;
+                    if Arc::ptr_eq(&expanded, &inner) {
+                        token.clone()
+                    } else {
+                        changed = true;
+                        Token {
+                            span: token.span.clone(),
+                            kind: regroup(&token.kind, expanded),
+                            trivia: token.trivia.clone(),
+                        }
+                    }
+                }
+                None => token.clone(),
+            });
+            idx += 1;
+        }
+
+        Ok(if changed { acc.into() } else { tokens.clone() })
+    }
+
+    /// The macro invoked by `name!(..)` at `idx`
+    fn invoked(&self, tokens: &[Token], idx: usize) -> Option<&MacroDef> {
+        match tokens.get(idx..idx + 3)? {
+            [name, bang, group] if name.kind == Kind::Ident && bang.kind == Kind::Not => {
+                group_tokens(&group.kind)?;
+                self.macros.get(&Symbol::intern(&name.span))
+            }
+            _ => None,
+        }
+    }
+
+    fn invoke(
+        &self,
+        def: &MacroDef,
+        tokens: &Arc<[Token]>,
+        idx: usize,
+    ) -> Result<Vec<Token>, ErrorChain> {
+        let (name, group) = (&tokens[idx].span, &tokens[idx + 2]);
+        let args = group_tokens(&group.kind).unwrap();
+
+        for rule in &def.rules {
+            let mut bindings = Bindings::new();
+            if self.matches(&rule.pattern, &args, 0, &mut bindings) != Some(args.len()) {
+                continue;
+            }
+
+            let end = group.span.location_offset() + group.span.len();
+            let mut call_site = self.file.slice(name.location_offset()..end);
+            call_site.extra = name.extra.clone();
+
+            let expn = ExpnId::new(ExpnData {
+                name: def.name.span.clone(),
+                call_site,
+                locals: rule.locals.clone(),
+            });
+
+            let mut acc = vec![];
+            self.transcribe(&rule.template, &bindings, &expn, &mut acc)?;
+            return Ok(acc);
+        }
+
+        Err(self.error(tokens, idx, "no rule of the macro matches these tokens"))
+    }
+
+    /// Matches `matchers` against the tokens from `pos` on, returns where the match ends
+    fn matches(
+        &self,
+        matchers: &[Matcher],
+        tokens: &Arc<[Token]>,
+        mut pos: usize,
+        bindings: &mut Bindings,
+    ) -> Option<usize> {
+        for matcher in matchers {
+            match matcher {
+                Matcher::Token(expected) => {
+                    if !same_token(tokens.get(pos)?, expected) {
+                        return None;
+                    }
+                    pos += 1;
+                }
+                Matcher::Group(expected, inner_matchers) => {
+                    let token = tokens.get(pos)?;
+                    if token.kind.cmp_id() != expected.kind.cmp_id() {
+                        return None;
+                    }
+                    let inner = group_tokens(&token.kind)?;
+                    if self.matches(inner_matchers, &inner, 0, bindings)? != inner.len() {
+                        return None;
+                    }
+                    pos += 1;
+                }
+                Matcher::Fragment(name, fragment) => {
+                    let len = self.fragment_len(*fragment, tokens, pos)?;
+                    let captured = match fragment {
+                        Fragment::Expr if len > 1 => {
+                            vec![self.parenthesized(&tokens[pos..pos + len])]
+                        }
+                        _ => tokens[pos..pos + len].to_vec(),
+                    };
+                    bindings.insert(name.sym, Capture::Tokens(captured));
+                    pos += len;
+                }
+                Matcher::Repeat {
+                    inner,
+                    sep,
+                    at_least_one,
+                } => {
+                    let mut iterations: Vec<Bindings> = vec![];
+
+                    loop {
+                        let start = match (&sep, iterations.is_empty()) {
+                            (Some(sep), false) => match tokens.get(pos) {
+                                Some(token) if same_token(token, sep) => pos + 1,
+                                _ => break,
+                            },
+                            _ => pos,
+                        };
+
+                        let mut frame = Bindings::new();
+                        match self.matches(inner, tokens, start, &mut frame) {
+                            // an empty match would repeat forever
+                            Some(end) if end > start => {
+                                pos = end;
+                                iterations.push(frame);
+                            }
+                            _ => break,
+                        }
+                    }
+
+                    if *at_least_one && iterations.is_empty() {
+                        return None;
+                    }
+
+                    for name in metavariables(inner) {
+                        let captures = iterations
+                            .iter_mut()
+                            .map(|frame| frame.remove(&name).unwrap())
+                            .collect();
+                        bindings.insert(name, Capture::Repeated(captures));
+                    }
+                }
+            }
+        }
+
+        Some(pos)
+    }
+
+    /// Number of tokens from `pos` on that make up the fragment
+    fn fragment_len(&self, fragment: Fragment, tokens: &Arc<[Token]>, pos: usize) -> Option<usize> {
+        let first = tokens.get(pos)?;
+        let rest = TokenSpan {
+            file: self.file.clone(),
+            local: pos..tokens.len(),
+            tokens: tokens.clone(),
+        };
+
+        let end = match fragment {
+            Fragment::Ident => {
+                let ident = Ident::new(first.span.clone());
+                return (first.kind == Kind::Ident && keyword_check(&ident)).then_some(1);
+            }
+            Fragment::Expr if can_begin_expr(&first.kind) => parse_expression(rest).ok()?.0,
+            Fragment::Type if can_begin_type(&first.kind) => parse_type(rest).ok()?.0,
+            _ => return None,
+        };
+
+        Some(end.local.start - pos).filter(|len| *len > 0)
+    }
+
+    /// Captured expressions stay a single operand wherever the template puts them, as if
+    /// they were written in parentheses
+    fn parenthesized(&self, tokens: &[Token]) -> Token {
+        let (first, last) = (&tokens[0].span, &tokens[tokens.len() - 1].span);
+        let mut span = self
+            .file
+            .slice(first.location_offset()..last.location_offset() + last.len());
+        span.extra = first.extra.clone();
+
+        Token {
+            span,
+            kind: Kind::Tuple(Group::new(tokens.to_vec())),
+            trivia: None,
+        }
+    }
+
+    fn transcribe(
+        &self,
+        template: &Arc<[Token]>,
+        bindings: &Bindings,
+        expn: &ExpnId,
+        acc: &mut Vec<Token>,
+    ) -> Result<(), ErrorChain> {
+        let mut idx = 0;
+
+        while idx < template.len() {
+            let token = &template[idx];
+
+            if let Some(inner) = group_tokens(&token.kind) {
+                let mut inner_acc = vec![];
+                self.transcribe(&inner, bindings, expn, &mut inner_acc)?;
+                acc.push(Token {
+                    span: expanded(&token.span, expn),
+                    kind: regroup(&token.kind, inner_acc.into()),
+                    trivia: None,
+                });
+                idx += 1;
+                continue;
+            }
+
+            if token.kind != Kind::Dollar {
+                acc.push(Token {
+                    span: expanded(&token.span, expn),
+                    kind: token.kind.clone(),
+                    trivia: None,
+                });
+                idx += 1;
+                continue;
+            }
+
+            match template.get(idx + 1).map(|tk| &tk.kind) {
+                Some(Kind::Ident) => {
+                    let sym = Symbol::intern(&template[idx + 1].span);
+                    match bindings.get(&sym) {
+                        Some(Capture::Tokens(tokens)) => acc.extend(tokens.iter().cloned()),
+                        Some(Capture::Repeated(_)) => {
+                            return Err(self.error(
+                                template,
+                                idx + 1,
+                                "metavariable repeats, it can only be used inside `$( ... )*`",
+                            ))
+                        }
+                        None => return Err(self.error(template, idx + 1, "unknown metavariable")),
+                    }
+                    idx += 2;
+                }
+                Some(Kind::Tuple(inner)) => {
+                    let inner = inner.tokens();
+                    let (sep, _, len) = self.repetition(template, idx + 2)?;
+
+                    let repeated: Vec<_> = metavariables_used(&inner)
+                        .into_iter()
+                        .filter_map(|sym| match bindings.get(&sym) {
+                            Some(Capture::Repeated(captures)) => Some((sym, captures)),
+                            _ => None,
+                        })
+                        .collect();
+
+                    let Some((_, first)) = repeated.first() else {
+                        return Err(self.error(
+                            template,
+                            idx,
+                            "repetition doesn't use any repeating metavariable",
+                        ));
+                    };
+                    let count = first.len();
+                    if repeated.iter().any(|(_, captures)| captures.len() != count) {
+                        return Err(self.error(
+                            template,
+                            idx,
+                            "metavariables repeat a different number of times",
+                        ));
+                    }
+
+                    for n in 0..count {
+                        if let (Some(sep), true) = (&sep, n > 0) {
+                            acc.push(Token {
+                                span: expanded(&sep.span, expn),
+                                kind: sep.kind.clone(),
+                                trivia: None,
+                            });
+                        }
+
+                        let mut frame = bindings.clone();
+                        for (sym, captures) in &repeated {
+                            frame.insert(*sym, captures[n].clone());
+                        }
+                        self.transcribe(&inner, &frame, expn, acc)?;
+                    }
+
+                    idx += 2 + len;
+                }
+                _ => {
+                    return Err(self.error(
+                        template,
+                        idx,
+                        "expected a metavariable or a repetition after `$`",
+                    ))
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn error(&self, tokens: &Arc<[Token]>, idx: usize, reason: &'static str) -> ErrorChain {
+        ErrorChain::from(Error::new(
+            TokenSpan {
+                file: self.file.clone(),
+                local: idx..idx + 1,
+                tokens: tokens.clone(),
+            },
+            reason,
+        ))
+    }
+}
+
+/// `span` as part of the expansion `expn`
+fn expanded(span: &Span, expn: &ExpnId) -> Span {
+    let mut span = span.clone();
+    span.extra.expansion = Some(expn.clone());
+    span
+}
+
+fn group_tokens(kind: &Kind) -> Option<Arc<[Token]>> {
+    match kind {
+        Kind::Tuple(inner) | Kind::Block(inner) | Kind::Array(inner) => Some(inner.tokens()),
+        _ => None,
+    }
+}
+
+/// A group of the same kind as `kind` holding `tokens`
+fn regroup(kind: &Kind, tokens: Arc<[Token]>) -> Kind {
+    match kind {
+        Kind::Tuple(_) => Kind::Tuple(Group::new(tokens)),
+        Kind::Block(_) => Kind::Block(Group::new(tokens)),
+        Kind::Array(_) => Kind::Array(Group::new(tokens)),
+        _ => unreachable!("not a group"),
+    }
+}
+
+fn same_token(a: &Token, b: &Token) -> bool {
+    a.kind.cmp_id() == b.kind.cmp_id() && **a.span.fragment() == **b.span.fragment()
+}
+
+fn can_begin_type(kind: &Kind) -> bool {
+    matches!(
+        kind,
+        Kind::Ident | Kind::Tuple(_) | Kind::Array(_) | Kind::Mul | Kind::Not
+    )
+}
+
+/// Metavariables a pattern binds
+fn metavariables(matchers: &[Matcher]) -> Vec<Symbol> {
+    matchers
+        .iter()
+        .flat_map(|matcher| match matcher {
+            Matcher::Token(_) => vec![],
+            Matcher::Group(_, inner) | Matcher::Repeat { inner, .. } => metavariables(inner),
+            Matcher::Fragment(name, _) => vec![name.sym],
+        })
+        .collect()
+}
+
+/// Metavariables a template refers to
+fn metavariables_used(tokens: &[Token]) -> Vec<Symbol> {
+    let mut acc = vec![];
+    for (idx, token) in tokens.iter().enumerate() {
+        if let Some(inner) = group_tokens(&token.kind) {
+            acc.extend(metavariables_used(&inner));
+        } else if token.kind == Kind::Ident && idx > 0 && tokens[idx - 1].kind == Kind::Dollar {
+            acc.push(Symbol::intern(&token.span));
+        }
+    }
+    acc
+}
+
+/// Identifiers a template defines with `:=`, leaving out metavariables
+fn locals(tokens: &[Token]) -> Vec<Symbol> {
+    let mut acc = vec![];
+    for (idx, token) in tokens.iter().enumerate() {
+        if let Some(inner) = group_tokens(&token.kind) {
+            acc.extend(locals(&inner));
+        } else if token.kind == Kind::Ident
+            && tokens
+                .get(idx + 1)
+                .is_some_and(|tk| tk.kind == Kind::Define)
+            && (idx == 0 || tokens[idx - 1].kind != Kind::Dollar)
+        {
+            acc.push(Symbol::intern(&token.span));
+        }
+    }
+    acc
+}
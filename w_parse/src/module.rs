@@ -1,24 +1,69 @@
-use crate::expr::path::ExprPath;
+use crate::error::{take_furthest, ErrorChain};
 use crate::item::{parse_item, Item};
+use crate::recovery::{recover, recovering, skip_item};
+use crate::util::{parse_inner_docs, Docs};
 use crate::{Ident, ParResult, TokenSpan};
+use nom::{Err, Slice};
 
 // Leaving this open for mode things in the future like imports
 
 pub struct ParsedModule {
     pub name: Ident,
+    pub docs: Docs,
     pub items: Vec<Item>,
 }
 
-pub fn parse_module(mut i: TokenSpan, name: Ident) -> ParResult<ParsedModule> {
+/// Parses the entire module, failing with the furthest error the parser ran into
+pub fn parse_module(i: TokenSpan, name: Ident) -> ParResult<ParsedModule> {
+    take_furthest();
+
     let mut items = vec![];
+    let (mut i, docs) = parse_inner_docs(i)?;
 
     while !i.is_empty() {
-        let (ni, item) = parse_item(i)?;
+        let (ni, item) = parse_item(i).map_err(|err| match err {
+            Err::Error(err) => Err::Error(err.with_furthest()),
+            err => err,
+        })?;
 
         items.push(item);
 
         i = ni;
     }
 
-    Ok((i, ParsedModule { name, items }))
+    Ok((i, ParsedModule { name, docs, items }))
+}
+
+/// Parses the entire module without stopping at the first syntax error.
+///
+/// An item that fails to parse becomes an [`Item::Error`] reaching up to where the next item
+/// starts. Statements of blocks that fail to parse become an [`crate::expr::Expr::Error`]
+/// reaching up to the next `;` or the end of the block. Every error is returned, ordered by
+/// where it occurred.
+pub fn parse_module_recovering(i: TokenSpan, name: Ident) -> (ParsedModule, Vec<ErrorChain>) {
+    let (module, mut errors) = recovering(|| {
+        let mut items = vec![];
+        let (mut i, docs) = parse_inner_docs(i.clone()).unwrap_or((i, Docs(vec![])));
+
+        while !i.is_empty() {
+            match parse_item(i.clone()) {
+                Ok((ni, item)) => {
+                    items.push(item);
+                    i = ni;
+                }
+                Err(err @ (Err::Error(_) | Err::Failure(_))) => {
+                    recover(err);
+                    let skipped = skip_item(&i);
+                    items.push(Item::Error((&i.slice(..skipped)).into()));
+                    i = i.slice(skipped..);
+                }
+                Err(Err::Incomplete(_)) => unreachable!("token spans are always complete"),
+            }
+        }
+
+        ParsedModule { name, docs, items }
+    });
+
+    errors.sort_by_key(ErrorChain::offset);
+    (module, errors)
 }
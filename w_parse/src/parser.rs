@@ -1,10 +1,10 @@
-use crate::error::{Error, ErrorChain};
+use crate::error::{describe, expect, Error, ErrorChain};
 use nom::{Compare, CompareResult, Err, IResult, InputLength, InputTake, Offset, Parser, Slice};
 
-use std::ops::{Deref, Range, RangeTo};
-use std::rc::Rc;
+use std::ops::{Deref, Range, RangeFrom, RangeTo};
+use std::sync::Arc;
 
-use w_tokenize::{Kind, Span, Token};
+use w_tokenize::{FileId, Kind, Span, SpanCtx, Token};
 
 pub type ParResult<T = TokenSpan> = IResult<TokenSpan, T, ErrorChain>;
 
@@ -12,11 +12,12 @@ pub type ParResult<T = TokenSpan> = IResult<TokenSpan, T, ErrorChain>;
 pub struct TokenSpan {
     pub(crate) file: Span,
     pub(crate) local: Range<usize>,
-    pub(crate) tokens: Rc<[Token]>,
+    pub(crate) tokens: Arc<[Token]>,
 }
 
 impl TokenSpan {
-    pub fn new(file: Span, tokens: Rc<[Token]>) -> TokenSpan {
+    pub fn new(file: Span, tokens: impl Into<Arc<[Token]>>) -> TokenSpan {
+        let tokens = tokens.into();
         TokenSpan {
             file,
             local: 0..tokens.len(),
@@ -24,6 +25,31 @@ impl TokenSpan {
         }
     }
 
+    /// The file the tokens were read from
+    pub fn file_id(&self) -> FileId {
+        self.file.extra.file
+    }
+
+    /// Offset in the file of the first token, or of the end of the tokens if there are none
+    pub fn file_offset(&self) -> usize {
+        match self.tokens.get(self.local.start) {
+            Some(token) => token.span.location_offset(),
+            None => self
+                .tokens
+                .last()
+                .map_or(0, |last| last.span.location_offset() + last.span.len()),
+        }
+    }
+
+    /// File and macro expansion of the first token, offsets of tokens from different
+    /// origins can't be compared
+    pub(crate) fn origin(&self) -> &SpanCtx {
+        match self.tokens.get(self.local.start).or(self.tokens.last()) {
+            Some(token) => &token.span.extra,
+            None => &self.file.extra,
+        }
+    }
+
     pub fn as_span(&self) -> Span {
         let diff = self.local.end - self.local.start;
         match diff {
@@ -35,7 +61,7 @@ impl TokenSpan {
             1 => self.tokens[self.local.start].span.clone(),
             _ => {
                 let start = self.tokens[self.local.start].span.clone();
-                let end = self.tokens[self.local.end].span.clone();
+                let end = self.tokens[self.local.end - 1].span.clone();
 
                 let so = start.location_offset();
                 let eo = end.location_offset() + end.len();
@@ -75,6 +101,12 @@ impl Slice<Range<usize>> for TokenSpan {
     }
 }
 
+impl Slice<RangeFrom<usize>> for TokenSpan {
+    fn slice(&self, range: RangeFrom<usize>) -> Self {
+        self.slice(range.start..self.local.len())
+    }
+}
+
 impl Slice<RangeTo<usize>> for TokenSpan {
     fn slice(&self, range: RangeTo<usize>) -> Self {
         let offset_end = self.local.start + range.end;
@@ -93,24 +125,24 @@ impl Slice<RangeTo<usize>> for TokenSpan {
 
 impl InputTake for TokenSpan {
     fn take(&self, count: usize) -> Self {
-        if self.local.start + count > self.tokens.len() {
+        if count > self.local.len() {
             panic!("TokenSpan::take: out of bounds");
         }
         Self {
             file: self.file.clone(),
-            local: self.local.start..self.local.end + count,
+            local: self.local.start..self.local.start + count,
             tokens: self.tokens.clone(),
         }
     }
 
     fn take_split(&self, count: usize) -> (Self, Self) {
-        if self.local.start + count > self.tokens.len() {
-            panic!("TokenSpan::take: out of bounds");
+        if count > self.local.len() {
+            panic!("TokenSpan::take_split: out of bounds");
         }
 
         let left = self.local.start;
         let mid = self.local.start + count;
-        let right = self.tokens.len();
+        let right = self.local.end;
 
         (
             Self {
@@ -178,7 +210,7 @@ impl Compare<Weak> for Token {
 
 impl Compare<Strong> for TokenSpan {
     fn compare(&self, t: Strong) -> CompareResult {
-        if self.len() >= 1 {
+        if !self.is_empty() {
             self[0].compare(t)
         } else {
             CompareResult::Error
@@ -186,7 +218,7 @@ impl Compare<Strong> for TokenSpan {
     }
 
     fn compare_no_case(&self, t: Strong) -> CompareResult {
-        if self.len() >= 1 {
+        if !self.is_empty() {
             self[0].compare_no_case(t)
         } else {
             CompareResult::Error
@@ -195,7 +227,7 @@ impl Compare<Strong> for TokenSpan {
 }
 impl Compare<Weak> for TokenSpan {
     fn compare(&self, t: Weak) -> CompareResult {
-        if self.len() >= 1 {
+        if !self.is_empty() {
             self[0].compare(t)
         } else {
             CompareResult::Error
@@ -203,7 +235,7 @@ impl Compare<Weak> for TokenSpan {
     }
 
     fn compare_no_case(&self, t: Weak) -> CompareResult {
-        if self.len() >= 1 {
+        if !self.is_empty() {
             self[0].compare_no_case(t)
         } else {
             CompareResult::Error
@@ -213,45 +245,32 @@ impl Compare<Weak> for TokenSpan {
 
 impl Parser<TokenSpan, Token, ErrorChain> for Strong {
     fn parse(&mut self, input: TokenSpan) -> IResult<TokenSpan, Token, ErrorChain> {
-        if input.is_empty() {
-            Err(Err::Error(ErrorChain::from(Error::new(
-                input,
-                "expected token",
-            ))))
-        } else {
-            let (ni, token) = input.take_split(1);
-            if Compare::compare(&token[0], self.clone()) == CompareResult::Ok {
+        match input.first() {
+            Some(token) if Compare::compare(token, self.clone()) == CompareResult::Ok => {
+                let (ni, token) = input.take_split(1);
                 Ok((ni, token[0].clone()))
-            } else {
-                Err(Err::Error(ErrorChain::from(Error::new(
-                    input,
-                    "expected token",
-                ))))
             }
+            _ => Err(expected_kind(input, &self.0)),
         }
     }
 }
 impl Parser<TokenSpan, Token, ErrorChain> for Weak {
     fn parse(&mut self, input: TokenSpan) -> IResult<TokenSpan, Token, ErrorChain> {
-        if input.is_empty() {
-            Err(Err::Error(ErrorChain::from(Error::new(
-                input,
-                "expected token",
-            ))))
-        } else {
-            let (ni, token) = input.take_split(1);
-            if Compare::compare(&token[0], self.clone()) == CompareResult::Ok {
+        match input.first() {
+            Some(token) if Compare::compare(token, self.clone()) == CompareResult::Ok => {
+                let (ni, token) = input.take_split(1);
                 Ok((ni, token[0].clone()))
-            } else {
-                Err(Err::Error(ErrorChain::from(Error::new(
-                    input,
-                    "expected token",
-                ))))
             }
+            _ => Err(expected_kind(input, &self.0)),
         }
     }
 }
 
+fn expected_kind(input: TokenSpan, kind: &Kind) -> Err<ErrorChain> {
+    expect(&input, describe(kind));
+    Err::Error(ErrorChain::from(Error::new(input, "expected token")))
+}
+
 impl Deref for TokenSpan {
     type Target = [Token];
     fn deref(&self) -> &Self::Target {
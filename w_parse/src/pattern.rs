@@ -0,0 +1,192 @@
+use crate::error::{Error, ErrorChain};
+use crate::expr::many::parse_many0;
+use crate::expr::path::{parse_path, ExprPath};
+use crate::expr::unary::{ExprUnary, UnOp};
+use crate::expr::{parse_literal, Expr};
+use crate::{parse_name, quick_err, Ident, ParResult, TokenSpan, Weak};
+use assert_matches::assert_matches;
+use nom::branch::alt;
+use nom::combinator::{all_consuming, map, opt, verify};
+use nom::sequence::{pair, preceded};
+use nom::{Err, Parser, Slice};
+use w_tokenize::{Group, Kind, Span};
+
+/// Tests the shape of a value and binds its parts to names
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    /// `name`, binds the entire value
+    Bind(Ident),
+    /// `_`, ignores the value
+    Fill(Span),
+    /// `(a, b)`, binds the fields of a tuple
+    Tuple(Span, Vec<Pattern>),
+    /// `option:some(a)` or `option:none`, matches one variant of an enum
+    Variant(PatVariant),
+    /// `point { x, y = 0 }`, matches the fields of a struct
+    Struct(PatStruct),
+    /// A number, float, string, char, byte or byte string literal, matches values equal to it.
+    /// Negative numbers and floats are a [`UnOp::Neg`] of the literal.
+    Literal(Box<Expr>),
+    /// `0..10`, `'a'..='z'` or `..0`, matches values within the range
+    Range(PatRange),
+}
+
+#[derive(Debug, Clone)]
+pub struct PatVariant {
+    /// The enum followed by the variant, only the variant if the enum is left to inference
+    pub path: ExprPath,
+    pub payload: Option<(Span, Vec<Pattern>)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PatStruct {
+    pub path: ExprPath,
+    pub span: Span,
+    /// Fields left out aren't tested
+    pub fields: Vec<PatField>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PatField {
+    pub name: Ident,
+    /// `None` for `name` alone, which binds the field to its own name
+    pub pattern: Option<Pattern>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PatRange {
+    pub span_op: Span,
+    pub start: Option<Box<Expr>>,
+    pub end: Option<Box<Expr>>,
+    pub inclusive: bool,
+}
+
+impl Pattern {
+    /// Whether some values of the type may not match, only patterns that always match can
+    /// define names outside of conditions
+    pub fn is_refutable(&self) -> bool {
+        match self {
+            Pattern::Bind(_) | Pattern::Fill(_) => false,
+            Pattern::Tuple(_, fields) => fields.iter().any(Pattern::is_refutable),
+            Pattern::Struct(PatStruct { fields, .. }) => fields
+                .iter()
+                .filter_map(|field| field.pattern.as_ref())
+                .any(Pattern::is_refutable),
+            Pattern::Variant(_) | Pattern::Literal(_) | Pattern::Range(_) => true,
+        }
+    }
+}
+
+pub fn parse_pattern(i: TokenSpan) -> ParResult<Pattern> {
+    alt((
+        map(parse_pattern_struct, Pattern::Struct),
+        parse_pattern_no_struct,
+    ))(i)
+}
+
+/// Any pattern but a struct, for places where a block follows that would be read as its
+/// fields
+pub fn parse_pattern_no_struct(i: TokenSpan) -> ParResult<Pattern> {
+    alt((
+        map(parse_pattern_range, Pattern::Range),
+        map(parse_pattern_literal, |lit| Pattern::Literal(Box::new(lit))),
+        map(parse_pattern_variant, Pattern::Variant),
+        map(parse_name, Pattern::Bind),
+        map(Weak(Kind::Fill), |tk| Pattern::Fill(tk.span)),
+        map(parse_pattern_tuple, |(span, fields)| {
+            Pattern::Tuple(span, fields)
+        }),
+    ))(i)
+}
+
+fn parse_pattern_tuple(i: TokenSpan) -> ParResult<(Span, Vec<Pattern>)> {
+    let (i, tuple) = Weak(Kind::Tuple(Group::default())).parse(i)?;
+    let span = tuple.span;
+    let tuple =
+        assert_matches!(tuple.kind, Kind::Tuple(vals) => TokenSpan::new(i.file.clone(), vals));
+    let (_, fields) = all_consuming(parse_many0(parse_pattern))(tuple)?;
+
+    Ok((i, (span, fields)))
+}
+
+/// A bare name is a binding, variants without a payload need the enum in front of them
+fn parse_pattern_variant(i: TokenSpan) -> ParResult<PatVariant> {
+    alt((
+        map(pair(parse_path, parse_pattern_tuple), |(path, payload)| {
+            PatVariant {
+                path,
+                payload: Some(payload),
+            }
+        }),
+        map(verify(parse_path, |path| path.path.len() >= 2), |path| {
+            PatVariant {
+                path,
+                payload: None,
+            }
+        }),
+    ))(i)
+}
+
+fn parse_pattern_struct(i: TokenSpan) -> ParResult<PatStruct> {
+    let (i, path) = parse_path(i)?;
+    let (i, block) = Weak(Kind::Block(Group::default())).parse(i)?;
+    let span = block.span;
+    let block =
+        assert_matches!(block.kind, Kind::Block(vals) => TokenSpan::new(i.file.clone(), vals));
+
+    let field = map(
+        pair(parse_name, opt(preceded(Weak(Kind::Assign), parse_pattern))),
+        |(name, pattern)| PatField { name, pattern },
+    );
+    let (_, fields) = all_consuming(parse_many0(field))(block)?;
+
+    Ok((i, PatStruct { path, span, fields }))
+}
+
+fn parse_pattern_range(i: TokenSpan) -> ParResult<PatRange> {
+    let (i, start) = opt(parse_pattern_literal)(i)?;
+    let (ni, op) = alt((Weak(Kind::Range), Weak(Kind::RangeInclusive)))(i.clone())?;
+    let (ni, end) = opt(parse_pattern_literal)(ni)?;
+
+    if start.is_none() && end.is_none() {
+        return quick_err(ni, "range patterns need a start or an end");
+    }
+    let inclusive = op.kind == Kind::RangeInclusive;
+    if inclusive && end.is_none() {
+        return Err(Err::Failure(ErrorChain::from(Error::new(
+            i.slice(..1),
+            "Inclusive ranges must have an end",
+        ))));
+    }
+
+    Ok((
+        ni,
+        PatRange {
+            span_op: op.span,
+            start: start.map(Box::new),
+            end: end.map(Box::new),
+            inclusive,
+        },
+    ))
+}
+
+/// A literal, numbers and floats may be negated with a `-` in front of them
+fn parse_pattern_literal(i: TokenSpan) -> ParResult<Expr> {
+    alt((
+        map(
+            pair(
+                Weak(Kind::Sub),
+                verify(parse_literal, |lit| {
+                    matches!(lit, Expr::Number(_) | Expr::Float(_))
+                }),
+            ),
+            |(sign, lit)| {
+                Expr::Unary(ExprUnary {
+                    op: UnOp::Neg(sign.span),
+                    expr: Box::new(lit),
+                })
+            },
+        ),
+        parse_literal,
+    ))(i)
+}
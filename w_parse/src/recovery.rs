@@ -0,0 +1,63 @@
+//! Recovery from syntax errors, so a single pass reports every error of a module
+
+use crate::error::{take_furthest, ErrorChain};
+use crate::TokenSpan;
+use nom::Err;
+use std::cell::RefCell;
+use w_tokenize::{starts_item, Kind};
+
+thread_local! {
+    /// Errors recovered from while [`recovering`] runs on this thread
+    static RECOVERED: RefCell<Option<Vec<ErrorChain>>> = const { RefCell::new(None) };
+}
+
+/// Runs `f` with recovery enabled, parsers that can recover record their errors instead of
+/// failing. Returns the recorded errors in the order they were found.
+pub(crate) fn recovering<T>(f: impl FnOnce() -> T) -> (T, Vec<ErrorChain>) {
+    let outer = RECOVERED.replace(Some(vec![]));
+    take_furthest();
+    let res = f();
+    let errors = RECOVERED.replace(outer).unwrap_or_default();
+    (res, errors)
+}
+
+pub(crate) fn is_recovering() -> bool {
+    RECOVERED.with_borrow(Option::is_some)
+}
+
+/// Keeps `err` to be reported, backtracking can run into the same error again which is
+/// only kept once. A failure is reported as it is, not behind the furthest failure.
+pub(crate) fn recover(err: Err<ErrorChain>) {
+    let err = match err {
+        Err::Error(err) => err.with_furthest(),
+        Err::Failure(err) => {
+            take_furthest();
+            err
+        }
+        Err::Incomplete(_) => unreachable!("token spans are always complete"),
+    };
+    RECOVERED.with_borrow_mut(|recovered| {
+        let Some(recovered) = recovered else {
+            return;
+        };
+
+        if !recovered.iter().any(|known| known.same_origin(&err)) {
+            recovered.push(err);
+        }
+    })
+}
+
+/// Number of tokens up to the next item, where [`starts_item`] splits them as well. Always
+/// skips at least one token.
+pub(crate) fn skip_item(i: &TokenSpan) -> usize {
+    (1..i.len())
+        .find(|&idx| starts_item(i, idx))
+        .unwrap_or(i.len())
+}
+
+/// Number of tokens up to and including the next `;`, or all of them
+pub(crate) fn skip_statement(i: &TokenSpan) -> usize {
+    i.iter()
+        .position(|tk| tk.kind == Kind::Semicolon)
+        .map_or(i.len(), |idx| idx + 1)
+}
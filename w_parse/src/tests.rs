@@ -0,0 +1,1049 @@
+use crate::attr::Attribute;
+use crate::error::ErrorChain;
+use crate::expr::block::{BlockKind, Statement};
+use crate::expr::jump::{ExprContinue, ExprReturn};
+use crate::expr::loops::ExprWhile;
+use crate::expr::ops::BiOp;
+use crate::expr::unary::UnOp;
+use crate::expr::{parse_expression, Expr, MAX_NESTING};
+use crate::generics::GenericArg;
+use crate::item::named::{ItemNamed, ItemNamedType, NamedKind};
+use crate::item::Item;
+use crate::pattern::{PatRange, PatStruct, PatVariant, Pattern};
+use crate::types::ItemTy;
+use crate::{expand_macros, parse_module, parse_module_recovering, Ident, ParsedModule, TokenSpan};
+use nom::combinator::all_consuming;
+use std::fmt::{Debug, Display};
+use w_rcstr::RcStr;
+use w_tokenize::{tokenize, FileId, Kind, SourceDb, Span, Token};
+
+/// The value inside of `$variant`, panics on any other variant
+macro_rules! unwrap_variant {
+    ($value:expr, $variant:path) => {
+        match $value {
+            $variant(inner) => inner,
+            other => panic!("expected {}, got {other:?}", stringify!($variant)),
+        }
+    };
+}
+
+fn source(src: impl Into<String>) -> Span {
+    Span::new_extra(RcStr::new(src.into()), FileId::DETACHED.into())
+}
+
+fn expr(src: &str) -> Expr {
+    let span = source(src);
+    let (_, tokens) = tokenize(span.clone()).unwrap();
+    let (_, expr) = all_consuming(parse_expression)(TokenSpan::new(span, tokens)).unwrap();
+    expr
+}
+
+#[test]
+fn ranges() {
+    let r = unwrap_variant!(expr("0..5"), Expr::Range);
+    assert!(r.start.is_some() && r.end.is_some() && !r.inclusive);
+
+    let r = unwrap_variant!(expr("0..=5"), Expr::Range);
+    assert!(r.inclusive);
+
+    let r = unwrap_variant!(expr("0.._"), Expr::Range);
+    assert!(r.start.is_some() && r.end.is_none());
+
+    let r = unwrap_variant!(expr(".."), Expr::Range);
+    assert!(r.start.is_none() && r.end.is_none());
+
+    // `a + 1..b - 1` is `(a + 1)..(b - 1)`
+    let r = unwrap_variant!(expr("a + 1..b - 1"), Expr::Range);
+    assert!(matches!(r.start.as_deref(), Some(Expr::Binary(_))));
+    assert!(matches!(r.end.as_deref(), Some(Expr::Binary(_))));
+
+    let r = unwrap_variant!(expr("0..(data.len() - 1)"), Expr::Range);
+    assert!(matches!(r.end.as_deref(), Some(Expr::Tuple(_))));
+
+    let err = first_error("f :: func() u8 { a..= }");
+    assert_eq!(&**err.errors()[0].location[0].span, "..=");
+    assert!(format!("{err:?}").contains("Inclusive ranges must have an end"));
+}
+
+#[test]
+fn slicing() {
+    let reference = unwrap_variant!(expr("&buf[..rl]"), Expr::Unary);
+    let index = unwrap_variant!(*reference.expr, Expr::Index);
+
+    let r = unwrap_variant!(index.args.values.into_iter().next().unwrap(), Expr::Range);
+    assert!(r.start.is_none() && r.end.is_some());
+}
+
+#[test]
+fn inclusive_range_needs_end() {
+    let span = source("0..=");
+    let (_, tokens) = tokenize(span.clone()).unwrap();
+    let res = parse_expression(TokenSpan::new(span, tokens));
+    assert!(matches!(res, Err(nom::Err::Failure(_))));
+}
+
+#[test]
+fn parses_modules_in_parallel() {
+    fn shareable<T: Send + Sync>() {}
+    shareable::<Span>();
+    shareable::<Token>();
+    shareable::<TokenSpan>();
+    shareable::<ParsedModule>();
+
+    let mut db = SourceDb::new();
+    let files = [
+        ("vec.w", "vec3 :: struct {\n    x f32,\n    y f32,\n}\n"),
+        (
+            "shape.w",
+            "shape :: enum {\n    circle(f32),\n    none,\n}\n",
+        ),
+        ("ptr.w", "ptr :: *vec3;\npair :: (u8, u16);\n"),
+        ("add.w", "add :: func(a vec3) vec3 { a }\n"),
+    ]
+    .map(|(path, src)| db.add(path, src.to_string()));
+
+    let parse = |file| {
+        let span = db.span(file);
+        let (_, tokens) = tokenize(span.clone()).unwrap();
+        let name = Ident::new(tokens[0].span.clone());
+        let (_, module) = parse_module(TokenSpan::new(span, tokens), name).unwrap();
+        module
+    };
+
+    let parallel = std::thread::scope(|scope| {
+        let handles = files.map(|file| scope.spawn(move || parse(file)));
+        handles.map(|handle| handle.join().unwrap())
+    });
+
+    for (file, module) in files.into_iter().zip(parallel) {
+        assert_eq!(module.items.len(), parse(file).items.len());
+        assert_eq!(module.name.span.extra.file, file);
+    }
+}
+
+#[test]
+fn nesting_limit() {
+    let nested = |depth: usize| {
+        let span = source(format!("{}x{}", "(".repeat(depth), ")".repeat(depth)));
+        let (_, tokens) = tokenize(span.clone()).unwrap();
+        parse_expression(TokenSpan::new(span, tokens))
+    };
+
+    // the limit is reached well before the stack of a debug build runs out
+    let (within, beyond) = std::thread::Builder::new()
+        .stack_size(32 << 20)
+        .spawn(move || (nested(MAX_NESTING - 1).is_ok(), nested(10 * MAX_NESTING)))
+        .unwrap()
+        .join()
+        .unwrap();
+    assert!(within);
+    let Err(nom::Err::Failure(err)) = beyond else {
+        panic!("expected the nesting to fail");
+    };
+    assert!(format!("{err:?}").contains("Expressions are nested too deeply"));
+
+    // leaving the expressions, failed or not, leaves the count where it was
+    assert!(nested(MAX_NESTING / 8).is_ok());
+}
+
+fn expand(src: &str) -> TokenSpan {
+    let span = source(src);
+    let (_, tokens) = tokenize(span.clone()).unwrap();
+    expand_macros(TokenSpan::new(span, tokens)).unwrap()
+}
+
+/// The tokens separated by spaces
+fn text(tokens: &[Token]) -> String {
+    let words: Vec<String> = tokens
+        .iter()
+        .map(|token| match &token.kind {
+            Kind::Tuple(inner) => format!("({})", text(&inner.tokens())),
+            Kind::Block(inner) => format!("{{{}}}", text(&inner.tokens())),
+            Kind::Array(inner) => format!("[{}]", text(&inner.tokens())),
+            _ => token.span.fragment().to_string(),
+        })
+        .collect();
+    words.join(" ")
+}
+
+fn idents<'a>(tokens: &'a [Token], name: &'a str) -> impl Iterator<Item = Ident> + 'a {
+    tokens.iter().flat_map(move |token| match &token.kind {
+        Kind::Tuple(inner) | Kind::Block(inner) | Kind::Array(inner) => {
+            idents(&inner.tokens(), name).collect::<Vec<_>>()
+        }
+        Kind::Ident if **token.span == *name => vec![Ident::new(token.span.clone())],
+        _ => vec![],
+    })
+}
+
+#[test]
+fn macro_expansion() {
+    let expanded = expand(
+        "/// squares it
+        square :: macro {
+            ($x:expr) -> { $x * $x }
+        }
+        max :: macro {
+            ($a:expr) -> { $a },
+            ($a:expr, $($rest:expr),+) -> { if $a > max!($($rest),+) { $a } else { max!($($rest),+) } };
+        }
+        all :: macro {
+            ($($name:ident $ty:type),*) -> { $($name :: $ty;)* }
+        }
+        f :: func(a u8) u8 { square!(a + 1) + max![1, a] }
+        all!{ x u8, y *vec3 }",
+    );
+
+    assert_eq!(
+        text(&expanded),
+        "f :: func (a u8) u8 {(a + 1) * (a + 1) + if 1 > a {1} else {a}} \
+         x :: u8 ; y :: * vec3 ;"
+    );
+
+    let (_, module) = parse_module(expanded.clone(), Ident::new(source("test"))).unwrap();
+    assert_eq!(module.items.len(), 3);
+
+    // a block is an expression as well
+    let expanded = expand("m :: macro { ($x:expr) -> { $x } }\nf :: func() u8 { m!({ 1 }) }");
+    assert_eq!(text(&expanded), "f :: func () u8 {{1}}");
+}
+
+#[test]
+fn macro_hygiene() {
+    let expanded = expand(
+        "swap :: macro {
+            ($a:ident, $b:ident) -> { t := $a; $a = $b; $b = t; }
+        }
+        f :: func() { t := 1; u := 2; swap!(t, u); }",
+    );
+
+    let t: Vec<_> = idents(&expanded, "t").collect();
+    // the caller's `t`, then `t := t` of the template with the second `t` passed by the caller
+    assert_eq!(t.len(), 5);
+    let (caller, template) = (t[0].sym, t[1].sym);
+    assert_ne!(caller, template);
+    assert_eq!(t.iter().filter(|t| t.sym == template).count(), 2);
+
+    let from_template = &t[1].span;
+    let data = from_template.extra.expansion.as_ref().unwrap().data();
+    assert_eq!(*data.name.fragment(), "swap");
+    assert_eq!(*data.call_site.fragment(), "swap!(t, u)");
+    // tokens passed to the macro keep their spans from the call site
+    assert!(t[2].span.extra.expansion.is_none());
+    assert_eq!(
+        t[2].span.location_offset(),
+        data.call_site.location_offset() + 6
+    );
+}
+
+#[test]
+fn macro_errors() {
+    let fails = |src: &str| {
+        let span = source(src);
+        let (_, tokens) = tokenize(span.clone()).unwrap();
+        expand_macros(TokenSpan::new(span, tokens)).is_err()
+    };
+
+    assert!(fails("m :: macro { ($x:ident) -> { $x } }\nm!(1)"));
+    assert!(fails("m :: macro { ($x:token) -> { $x } }"));
+    assert!(fails("m :: macro { ($x:ident) -> { $y } }\nm!(a)"));
+    assert!(fails("m :: macro { () -> { m!() } }\nm!()"));
+    assert!(!fails("m :: macro { () -> {} }\nm!()"));
+
+    // groups around a call don't count toward the recursion limit
+    let nested = format!("{}m!(){}", "(".repeat(100), ")".repeat(100));
+    assert!(!fails(&format!(
+        "m :: macro {{ () -> {{ 1 }} }}\nf :: {nested};"
+    )));
+}
+
+/// The names of the items in `module`, or what else they are
+fn kinds(module: &ParsedModule) -> Vec<&str> {
+    module
+        .items
+        .iter()
+        .map(|item| match item {
+            Item::Definer(named) => named.name.sym.as_str(),
+            Item::Import(_) => "import",
+            Item::Error(_) => "error",
+        })
+        .collect()
+}
+
+#[test]
+fn recovers_from_syntax_errors() {
+    let span = source(
+        "a :: struct {\n    x f32,\n}\n\
+         b :: 3 3;\n\
+         c :: func() u8 { 1 2; bar(2); y }\n\
+         /// docs\n\
+         d :: enum {\n    e,\n}\n\
+         e :: ;\n\
+         f :: *u8;\n",
+    );
+    let (_, tokens) = tokenize(span.clone()).unwrap();
+    let (module, errors) =
+        parse_module_recovering(TokenSpan::new(span, tokens), Ident::new(source("test")));
+
+    assert_eq!(kinds(&module), ["a", "error", "c", "d", "error", "f"]);
+    assert_eq!(errors.len(), 3);
+
+    let offsets: Vec<_> = errors.iter().map(|err| err.offset()).collect();
+    assert!(offsets.windows(2).all(|pair| pair[0] <= pair[1]));
+
+    let Item::Error(skipped) = &module.items[1] else {
+        unreachable!()
+    };
+    assert_eq!(*skipped.fragment(), "b :: 3 3;");
+
+    let Item::Definer(c) = &module.items[2] else {
+        unreachable!()
+    };
+    let NamedKind::Func(func) = &c.kind else {
+        panic!("expected a function")
+    };
+    let BlockKind::Many { stmts, returning } = &func.body.kind else {
+        panic!("expected a block")
+    };
+    assert!(matches!(&stmts[0].expr, Expr::Error(span) if **span.fragment() == *"1 2;"));
+    assert_eq!(stmts.len(), 2);
+    assert!(returning.is_some());
+
+    // generic items and attributes are where the next item starts as well
+    let span = source("b :: 3 3\niter(T) :: struct { x u8, }\nc :: *u8;\n");
+    let (_, tokens) = tokenize(span.clone()).unwrap();
+    let (module, errors) =
+        parse_module_recovering(TokenSpan::new(span, tokens), Ident::new(source("test")));
+    assert_eq!(kinds(&module), ["error", "iter", "c"]);
+    assert_eq!(errors.len(), 1);
+}
+
+fn first_error(src: &str) -> ErrorChain {
+    let span = source(src);
+    let (_, tokens) = tokenize(span.clone()).unwrap();
+    match parse_module(TokenSpan::new(span, tokens), Ident::new(source("test"))) {
+        Err(nom::Err::Error(err) | nom::Err::Failure(err)) => err,
+        _ => panic!("expected `{src}` to fail"),
+    }
+}
+
+#[test]
+fn expected_tokens() {
+    let cases = [
+        (
+            "f :: func() u8 { a := 1; a @ }",
+            "expected `;`, `}` or an operator, found `@`",
+            "`@`",
+        ),
+        ("f :: ;", "expected `@`, a name or a type, found `;`", "`;`"),
+        (
+            "v :: struct { x f32, y }",
+            "expected a type, found `}`",
+            "`}`",
+        ),
+        (
+            "pair :: (u8 u16);",
+            "expected `::`, `:`, `(` or `,`, found `u16`",
+            "`u16`",
+        ),
+        // `::` continues the path `b` if a name follows
+        ("a :: b :: func;", "expected `(` or `;`, found `::`", "`::`"),
+        (
+            "f :: func() bool { a == b == c }",
+            "Comparisons and ranges can't be chained, add parentheses",
+            "`==`",
+        ),
+    ];
+
+    for (src, message, found) in cases {
+        let err = first_error(src);
+        assert_eq!(err.message(), message);
+        assert_eq!(format!("`{}`", &src[err.errors()[0].range()]), found);
+
+        let span = source(src);
+        let (_, tokens) = tokenize(span.clone()).unwrap();
+        let (_, errors) =
+            parse_module_recovering(TokenSpan::new(span, tokens), Ident::new(source("test")));
+        assert_eq!(errors[0].message(), message, "{src}");
+    }
+
+    struct Cache(ariadne::Source);
+    impl ariadne::Cache<FileId> for Cache {
+        fn fetch(&mut self, _: &FileId) -> Result<&ariadne::Source, Box<dyn Debug + '_>> {
+            Ok(&self.0)
+        }
+
+        fn display<'a>(&self, _: &'a FileId) -> Option<Box<dyn Display + 'a>> {
+            Some(Box::new("test.w"))
+        }
+    }
+
+    let src = "f :: func() u8 { a := 1; a @ }";
+    let mut out = vec![];
+    first_error(src)
+        .report()
+        .write(Cache(ariadne::Source::from(src)), &mut out)
+        .unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("expected `;`, `}` or an operator, found `@`"));
+    assert!(out.contains("unexpected `@`"));
+}
+
+#[test]
+fn expected_tokens_around_expansions() {
+    // the templates come after the mistake in the file, their tokens must not win over it
+    let cases = [
+        (
+            "f :: func() u8 { x := m!(); x y }\nm :: macro { () -> { (1 + 2) } }",
+            "expected `;`, `}` or an operator, found `y`",
+            "`y`",
+        ),
+        (
+            "f :: func() u8 { x := m!() y; }\nm :: macro { () -> { a } }",
+            "expected `;`, `}` or an operator, found `y`",
+            "`y`",
+        ),
+    ];
+
+    for (src, message, found) in cases {
+        let err = match parse_module(expand(src), Ident::new(source("test"))) {
+            Err(nom::Err::Error(err) | nom::Err::Failure(err)) => err,
+            _ => panic!("expected `{src}` to fail"),
+        };
+        assert_eq!(err.message(), message, "{src}");
+        assert_eq!(format!("`{}`", &src[err.errors()[0].range()]), found);
+    }
+}
+
+#[test]
+fn for_loops() {
+    let for_loop = |src| unwrap_variant!(expr(src), Expr::For);
+
+    let l = for_loop("for i in 0..n { a }");
+    assert!(matches!(&*l.pattern, Pattern::Bind(name) if name.sym.as_str() == "i"));
+    assert!(matches!(*l.iterable, Expr::Range(_)));
+
+    let l = for_loop("for _ in 0..=(len - 1) -> a");
+    assert!(matches!(*l.pattern, Pattern::Fill(_)));
+    assert!(unwrap_variant!(*l.iterable, Expr::Range).inclusive);
+
+    let l = for_loop("for (idx, x) in it { x }");
+    assert!(matches!(&*l.pattern, Pattern::Tuple(_, fields) if fields.len() == 2));
+    assert!(matches!(*l.iterable, Expr::Ident(_)));
+
+    // the loop ends with its block, like `while`
+    let (stmts, returning) = func_body("f :: func() u8 { for i in items { a } b }");
+    assert!(matches!(stmts[0].expr, Expr::For(_)) && stmts[0].sim.is_none());
+    assert!(returning.is_some());
+}
+
+fn func_body(src: &str) -> (Vec<Statement>, Option<Box<Expr>>) {
+    let span = source(src);
+    let (_, tokens) = tokenize(span.clone()).unwrap();
+    let (_, module) =
+        parse_module(TokenSpan::new(span, tokens), Ident::new(source("test"))).unwrap();
+    let Some(Item::Definer(ItemNamed {
+        kind: NamedKind::Func(func),
+        ..
+    })) = module.items.into_iter().next()
+    else {
+        panic!("expected a function")
+    };
+    let BlockKind::Many { stmts, returning } = func.body.kind else {
+        panic!("expected a block")
+    };
+    (stmts, returning)
+}
+
+#[test]
+fn jumps() {
+    let Expr::Loop(l) = expr("loop @outer { break @outer x }") else {
+        panic!("expected a loop")
+    };
+    assert_eq!(l.label.unwrap().name.sym.as_str(), "outer");
+    let BlockKind::Many {
+        returning: Some(brk),
+        ..
+    } = l.body.kind
+    else {
+        panic!("expected a block")
+    };
+    let Expr::Break(brk) = *brk else {
+        panic!("expected a break")
+    };
+    assert!(brk.label.is_some() && matches!(brk.value.as_deref(), Some(Expr::Ident(_))));
+
+    assert!(matches!(
+        expr("return"),
+        Expr::Return(ExprReturn { value: None, .. })
+    ));
+    assert!(matches!(
+        expr("return a + 1"),
+        Expr::Return(ExprReturn { value: Some(_), .. })
+    ));
+    assert!(matches!(
+        expr("continue @l"),
+        Expr::Continue(ExprContinue { label: Some(_), .. })
+    ));
+    assert!(matches!(
+        expr("while @l c { a }"),
+        Expr::While(ExprWhile { label: Some(_), .. })
+    ));
+
+    // the block is the body of the branch, not the value of `return`
+    assert!(matches!(expr("if done || return { a }"), Expr::Branch(_)));
+
+    let never = expr("break").never().unwrap();
+    assert_eq!(&**never.0, "break");
+    assert!(expr("loop { a }").never().is_none());
+
+    // jumps are statements that need a `;`, unless they end the block
+    let (stmts, returning) =
+        func_body("f :: func() u8 { loop { if c { continue } break; } return false; }");
+    assert!(matches!(stmts[0].expr, Expr::Loop(_)) && stmts[0].sim.is_none());
+    assert!(matches!(stmts[1].expr, Expr::Return(_)) && stmts[1].sim.is_some());
+    assert!(returning.is_none());
+}
+
+#[test]
+fn defers() {
+    let (stmts, returning) = func_body("f :: func() u8 { defer r.dispose(); defer if c { a }; r }");
+    assert!(matches!(&stmts[0].expr, Expr::Defer(defer) if matches!(*defer.expr, Expr::Call(_))));
+    assert!(matches!(&stmts[1].expr, Expr::Defer(defer) if matches!(*defer.expr, Expr::Branch(_))));
+    assert!(stmts.iter().all(|stmt| stmt.sim.is_some()));
+    assert!(returning.is_some());
+}
+
+#[test]
+fn doc_comments_in_bodies() {
+    let (stmts, returning) = func_body(
+        "f :: func() u8 {\n    /// note\n    a := 1;\n    /// more\n    a\n    /// end\n}",
+    );
+    assert_eq!(stmts.len(), 1);
+    assert!(matches!(returning.as_deref(), Some(Expr::Ident(_))));
+
+    let (stmts, returning) = func_body("f :: func() u8 { a; /// end\n}");
+    assert_eq!(stmts.len(), 1);
+    assert!(returning.is_none());
+}
+
+#[test]
+fn patterns() {
+    let d = unwrap_variant!(expr("option:some(a) := x"), Expr::Define);
+    assert!(d.pattern.is_refutable());
+    let Pattern::Variant(PatVariant {
+        path,
+        payload: Some((_, fields)),
+    }) = d.pattern
+    else {
+        panic!("expected a variant")
+    };
+    assert_eq!(path.path.len(), 2);
+    assert!(matches!(&fields[..], [Pattern::Bind(_)]));
+
+    let d = unwrap_variant!(expr("point { x, y = (_, 0..=9) } := p"), Expr::Define);
+    let Pattern::Struct(PatStruct { fields, .. }) = &d.pattern else {
+        panic!("expected a struct")
+    };
+    assert!(fields[0].pattern.is_none());
+    assert!(matches!(&fields[1].pattern, Some(Pattern::Tuple(_, inner))
+        if matches!(&inner[..], [Pattern::Fill(_), Pattern::Range(PatRange { inclusive: true, .. })])));
+
+    assert!(!unwrap_variant!(expr("(a, _) := p"), Expr::Define)
+        .pattern
+        .is_refutable());
+    assert!(matches!(
+        unwrap_variant!(expr("a := 1 + 2"), Expr::Define)
+            .value
+            .as_ref(),
+        Expr::Binary(_)
+    ));
+
+    let Expr::Branch(branch) = expr("if option:some(a) := self { a }") else {
+        panic!("expected a branch")
+    };
+    assert!(matches!(*branch.cond, Expr::Define(_)));
+    let Expr::While(w) = expr("while option:some(c) := it.next() { c }") else {
+        panic!("expected a while loop")
+    };
+    assert!(matches!(*w.cond, Expr::Define(_)));
+
+    let Expr::Match(m) = expr(
+        "match c { 'a'..='z' -> 1, '0' -> 2, option:none { 3 }, option:none { a }, point { x } -> x, _ -> 4 }",
+    ) else {
+        panic!("expected a match")
+    };
+    let arms: Vec<_> = m.arms.iter().map(|arm| &arm.pattern).collect();
+    assert!(matches!(
+        arms[..],
+        [
+            Pattern::Range(_),
+            Pattern::Literal(_),
+            Pattern::Variant(_),
+            Pattern::Variant(_),
+            Pattern::Struct(_),
+            Pattern::Fill(_)
+        ]
+    ));
+
+    let err = first_error("f :: func() () { match c { 0..= -> 1, _ -> 2 } }");
+    assert_eq!(&**err.errors()[0].location[0].span, "..=");
+    assert!(format!("{err:?}").contains("Inclusive ranges must have an end"));
+}
+
+#[test]
+fn casts() {
+    let call = unwrap_variant!(expr("option:some(cstr as *str)"), Expr::Call);
+    let c = unwrap_variant!(call.args.values.into_iter().next().unwrap(), Expr::Cast);
+    assert!(matches!(*c.expr, Expr::Ident(_)));
+    assert!(matches!(c.ty, ItemTy::Pointer(_)));
+
+    // casts bind tighter than binary operators and looser than unary ones
+    let bin = unwrap_variant!(expr("a * b as u8"), Expr::Binary);
+    assert!(matches!(*bin.right, Expr::Cast(_)));
+    assert!(matches!(
+        *unwrap_variant!(expr("&x as *u8"), Expr::Cast).expr,
+        Expr::Unary(_)
+    ));
+    assert!(matches!(
+        *unwrap_variant!(expr("buf.len() as u32"), Expr::Cast).expr,
+        Expr::Call(_)
+    ));
+
+    // chained casts apply from left to right
+    let c = unwrap_variant!(expr("x as u16 as f32"), Expr::Cast);
+    assert!(matches!(*c.expr, Expr::Cast(_)));
+}
+
+#[test]
+fn negation() {
+    // the spacing around `-` doesn't change what it means
+    for src in ["x-1", "x - 1", "x -1"] {
+        let Expr::Binary(bin) = expr(src) else {
+            panic!("expected a subtraction for {src}")
+        };
+        assert!(matches!(bin.op, BiOp::Sub));
+        assert!(matches!(*bin.right, Expr::Number(_)));
+    }
+
+    let neg = unwrap_variant!(expr("-x"), Expr::Unary);
+    assert!(matches!(neg.op, UnOp::Neg(_)) && matches!(*neg.expr, Expr::Ident(_)));
+    assert!(matches!(
+        unwrap_variant!(expr("~x"), Expr::Unary).op,
+        UnOp::BitNot(_)
+    ));
+    assert!(matches!(
+        unwrap_variant!(expr("!x"), Expr::Unary).op,
+        UnOp::Not(_)
+    ));
+
+    let Expr::Binary(bin) = expr("a - -1") else {
+        panic!("expected a subtraction")
+    };
+    assert!(matches!(
+        unwrap_variant!(*bin.right, Expr::Unary).op,
+        UnOp::Neg(_)
+    ));
+    let Expr::Binary(bin) = expr("-x * y") else {
+        panic!("expected a multiplication")
+    };
+    assert!(matches!(
+        unwrap_variant!(*bin.left, Expr::Unary).op,
+        UnOp::Neg(_)
+    ));
+    assert!(unwrap_variant!(expr("-1..5"), Expr::Range).start.is_some());
+    assert!(matches!(
+        *unwrap_variant!(expr("-x as i8"), Expr::Cast).expr,
+        Expr::Unary(_)
+    ));
+
+    // patterns take the sign as part of the literal
+    let Pattern::Range(PatRange {
+        start: Some(start), ..
+    }) = unwrap_variant!(expr("-5..0 := x"), Expr::Define).pattern
+    else {
+        panic!("expected a range")
+    };
+    assert!(matches!(
+        unwrap_variant!(*start, Expr::Unary).op,
+        UnOp::Neg(_)
+    ));
+
+    // array sizes are unsigned, the parser rejects a negated one
+    let src = "a :: [-1]u8;";
+    let err = first_error(src);
+    assert_eq!(err.message(), "Array sizes can't be negative");
+    assert_eq!(&src[err.errors()[0].range()], "-");
+}
+
+/// The expression with every operator put in parentheses
+fn grouping(expr: &Expr) -> String {
+    fn ty_name(ty: &ItemTy) -> String {
+        match ty {
+            ItemTy::Referred(referred) => referred.path.path.last().unwrap().span.to_string(),
+            ItemTy::Pointer(ptr) => format!("*{}", ty_name(&ptr.ty)),
+            other => panic!("unexpected type {other:?}"),
+        }
+    }
+
+    match expr {
+        Expr::Ident(ident) => ident.span.to_string(),
+        Expr::Number(num) => num.number.to_string(),
+        Expr::Binary(bin) => format!(
+            "({} {} {})",
+            grouping(&bin.left),
+            bin.op_span,
+            grouping(&bin.right)
+        ),
+        Expr::Unary(unary) => {
+            let (UnOp::Ref(op)
+            | UnOp::Deref(op)
+            | UnOp::Not(op)
+            | UnOp::Neg(op)
+            | UnOp::BitNot(op)) = &unary.op;
+            format!("({op}{})", grouping(&unary.expr))
+        }
+        Expr::Cast(cast) => format!("({} as {})", grouping(&cast.expr), ty_name(&cast.ty)),
+        Expr::Range(range) => format!(
+            "({}{}{})",
+            range.start.as_deref().map(grouping).unwrap_or_default(),
+            range.span_op,
+            range.end.as_deref().map(grouping).unwrap_or_default()
+        ),
+        Expr::Assign(asg) => format!("({} = {})", grouping(&asg.assignee), grouping(&asg.value)),
+        Expr::Field(field) => format!("{}.{}", grouping(&field.base), field.field.span),
+        Expr::Call(call) => format!("{}()", grouping(&call.base)),
+        Expr::Tuple(tuple) => {
+            let [value] = &tuple.values[..] else {
+                panic!("expected a parenthesized expression")
+            };
+            grouping(value)
+        }
+        other => panic!("unexpected expression {other:?}"),
+    }
+}
+
+#[test]
+fn precedence() {
+    for (src, expected) in [
+        ("a + b * c", "(a + (b * c))"),
+        ("a * b + c % d", "((a * b) + (c % d))"),
+        ("a << b + c", "(a << (b + c))"),
+        ("a & b << c", "(a & (b << c))"),
+        ("a | b ^ c & d", "(a | (b ^ (c & d)))"),
+        ("a & b == c", "((a & b) == c)"),
+        ("a == b | c", "(a == (b | c))"),
+        ("a < b || c >= d && e", "((a < b) || ((c >= d) && e))"),
+        ("a || b && c", "(a || (b && c))"),
+        ("a && b || c", "((a && b) || c)"),
+        // unary operators bind tighter than binary ones, postfix ones tighter still
+        ("-a * b", "((-a) * b)"),
+        ("!a && b", "((!a) && b)"),
+        ("*p.x + 1", "((*p.x) + 1)"),
+        ("-f() - 1", "((-f()) - 1)"),
+        ("(a + b) * c", "((a + b) * c)"),
+        // casts sit between the unary and the binary operators
+        ("a * b as u8", "(a * (b as u8))"),
+        ("-x as u8 * y", "(((-x) as u8) * y)"),
+        ("&x as *u8", "((&x) as *u8)"),
+        ("x as u16 as f32", "((x as u16) as f32)"),
+        // ranges bind weaker than any other operator but assignments
+        ("a + 1..b - 1", "((a + 1)..(b - 1))"),
+        ("a || b..c", "((a || b)..c)"),
+        ("..n * 2", "(..(n * 2))"),
+        ("a..", "(a..)"),
+        ("0..=n", "(0..=n)"),
+        ("a = 0..n", "(a = (0..n))"),
+        ("a = b + 1", "(a = (b + 1))"),
+        ("a.b = c == d", "(a.b = (c == d))"),
+    ] {
+        assert_eq!(grouping(&expr(src)), expected, "{src}");
+    }
+}
+
+#[test]
+fn associativity() {
+    for (src, expected) in [
+        ("a - b - c", "((a - b) - c)"),
+        ("a / b * c", "((a / b) * c)"),
+        ("a << b >> c", "((a << b) >> c)"),
+        ("a || b || c", "((a || b) || c)"),
+        ("a = b = c", "(a = (b = c))"),
+    ] {
+        assert_eq!(grouping(&expr(src)), expected, "{src}");
+    }
+
+    // comparisons and ranges don't associate
+    for src in ["a < b < c", "a == b != c", "a..b..c", "a < b + 1 == c"] {
+        let span = source(src);
+        let (_, tokens) = tokenize(span.clone()).unwrap();
+        let res = parse_expression(TokenSpan::new(span, tokens));
+        assert!(matches!(res, Err(nom::Err::Failure(_))), "{src}");
+    }
+    assert_eq!(grouping(&expr("(a < b) == c")), "((a < b) == c)");
+
+    // the error points at the second operator
+    let span = source("a == b == c");
+    let (_, tokens) = tokenize(span.clone()).unwrap();
+    let Err(nom::Err::Failure(err)) = parse_expression(TokenSpan::new(span, tokens)) else {
+        panic!("expected chained comparisons to fail");
+    };
+    assert_eq!(
+        format!("{err:?}"),
+        "1:8: Comparisons and ranges can't be chained, add parentheses\n"
+    );
+    assert_eq!(err.errors()[0].range(), 7..9);
+
+    let err = first_error("f :: func() bool { a == b == c }");
+    assert!(format!("{err:?}").contains("1:27: Comparisons and ranges can't be chained"));
+}
+
+#[test]
+fn long_expressions() {
+    // every operator is read once, a long chain parses in linear time
+    let src = (0..20_000)
+        .map(|n| format!("a{n}"))
+        .collect::<Vec<_>>()
+        .join(" + ");
+    let mut expr = expr(&src);
+    let mut depth = 0;
+    while let Expr::Binary(bin) = expr {
+        expr = *bin.left;
+        depth += 1;
+    }
+    assert_eq!(depth, 19_999);
+}
+
+#[test]
+fn conditions_without_ctors() {
+    // the block after the condition is never read as the fields of a constructor
+    let Expr::Branch(branch) = expr("if a { b = c }") else {
+        panic!("expected a branch")
+    };
+    assert!(matches!(*branch.cond, Expr::Ident(_)));
+    let Expr::While(w) = expr("while i < n { i = i + 1 }") else {
+        panic!("expected a while loop")
+    };
+    assert!(matches!(*w.cond, Expr::Binary(_)));
+    assert!(matches!(expr("match p { _ -> 0 }"), Expr::Match(_)));
+
+    // within parentheses they are fine again
+    let Expr::Branch(branch) = expr("if (p { x = 1 }).x { 0 }") else {
+        panic!("expected a branch")
+    };
+    assert!(matches!(*branch.cond, Expr::Field(_)));
+    assert!(matches!(expr("p { x = 1 }"), Expr::Ctor(_)));
+}
+
+#[test]
+fn nested_bodies() {
+    // every condition used to try the body as a constructor first, which parsed each body
+    // twice for every level it was nested in
+    let depth = 16;
+    let src = format!(
+        "f :: func() u8 {{ {}x{}; }}",
+        "if a { b = ".repeat(depth),
+        " }".repeat(depth)
+    );
+    let (stmts, _) = func_body(&src);
+
+    let mut expr = &stmts[0].expr;
+    for _ in 0..depth {
+        let Expr::Branch(branch) = expr else {
+            panic!("expected a branch, got {expr:?}")
+        };
+        let BlockKind::Many {
+            returning: Some(value),
+            ..
+        } = &branch.body.kind
+        else {
+            panic!("expected a returning block")
+        };
+        let Expr::Assign(asg) = &**value else {
+            panic!("expected an assignment, got {value:?}")
+        };
+        expr = &asg.value;
+    }
+    assert!(matches!(expr, Expr::Ident(_)));
+}
+
+fn items(src: &str) -> Vec<ItemNamed> {
+    let span = source(src);
+    let (_, tokens) = tokenize(span.clone()).unwrap();
+    let (_, module) =
+        parse_module(TokenSpan::new(span, tokens), Ident::new(source("test"))).unwrap();
+    module
+        .items
+        .into_iter()
+        .map(|item| unwrap_variant!(item, Item::Definer))
+        .collect()
+}
+
+#[test]
+fn generics() {
+    let items = items(
+        "iter(T) :: struct {\n    data *[]T,\n}\n\
+         option(T) :: enum {\n    some(T),\n    none,\n}\n\
+         sort(T(ord), U(eq, ord)) :: func(data *[]T) () {}\n\
+         add(A=add_t(A, A, A), B=conv_t(B, A), C) :: func(a A, b B) C { a }\n\
+         next :: func(self *iter (T)) option(*T) { none }\n\
+         first :: conv_t(_, u8);\n\
+         plain :: u8;\n",
+    );
+
+    let params = |item: &ItemNamed| -> Vec<(String, usize, bool)> {
+        item.generics
+            .as_ref()
+            .unwrap()
+            .params
+            .iter()
+            .map(|p| (p.name.span.to_string(), p.bounds.len(), p.default.is_some()))
+            .collect()
+    };
+    assert_eq!(params(&items[0]), [("T".into(), 0, false)]);
+    assert_eq!(params(&items[1]), [("T".into(), 0, false)]);
+    assert_eq!(
+        params(&items[2]),
+        [("T".into(), 1, false), ("U".into(), 2, false)]
+    );
+    assert_eq!(
+        params(&items[3]),
+        [
+            ("A".into(), 0, true),
+            ("B".into(), 0, true),
+            ("C".into(), 0, false)
+        ]
+    );
+    assert!(items[4].generics.is_none());
+
+    let Some(ItemTy::Referred(default)) = &items[3].generics.as_ref().unwrap().params[0].default
+    else {
+        panic!("expected a type reference")
+    };
+    assert_eq!(default.args.as_ref().unwrap().args.len(), 3);
+
+    let NamedKind::Func(next) = &items[4].kind else {
+        panic!("expected a function")
+    };
+    let ItemTy::Pointer(this) = &next.func.args[0].ty else {
+        panic!("expected a pointer")
+    };
+    assert_eq!(
+        unwrap_variant!(&*this.ty, ItemTy::Referred)
+            .args
+            .as_ref()
+            .unwrap()
+            .args
+            .len(),
+        1
+    );
+    let ret = unwrap_variant!(&*next.func.ret_ty, ItemTy::Referred)
+        .args
+        .as_ref()
+        .unwrap();
+    assert!(matches!(
+        ret.args[..],
+        [GenericArg::Type(ItemTy::Pointer(_))]
+    ));
+
+    let ty = |item: &ItemNamed| {
+        let named = unwrap_variant!(&item.kind, NamedKind::Type);
+        unwrap_variant!(&named.ty, ItemTy::Referred).clone()
+    };
+    let args = ty(&items[5]).args.unwrap().args;
+    assert!(matches!(
+        args[..],
+        [GenericArg::Fill(_), GenericArg::Type(_)]
+    ));
+    assert!(ty(&items[6]).args.is_none());
+
+    let option = unwrap_variant!(
+        &unwrap_variant!(expr("x as option(u8)"), Expr::Cast).ty,
+        ItemTy::Referred
+    )
+    .clone();
+    assert_eq!(option.args.unwrap().args.len(), 1);
+}
+
+#[test]
+fn item_paths() {
+    let items = items(
+        "iter(T):new :: func(data *[]T) iter(T) {\n    iter { data = data, offset = 0 }\n}\n\
+         vec(T(disposable)):dispose :: func(self *vec(T)) () {\n    free(self.buf);\n}\n\
+         iter(T) :: a:b;\n\
+         next :: func() () {}\n",
+    );
+
+    let path = |item: &ItemNamed| -> Vec<String> {
+        item.path.iter().map(|name| name.span.to_string()).collect()
+    };
+    assert_eq!(items[0].name.span.to_string(), "iter");
+    assert_eq!(path(&items[0]), ["new"]);
+    assert!(matches!(items[0].kind, NamedKind::Func(_)));
+    assert_eq!(items[1].name.span.to_string(), "vec");
+    assert_eq!(path(&items[1]), ["dispose"]);
+    assert_eq!(
+        items[1].generics.as_ref().unwrap().params[0].bounds.len(),
+        1
+    );
+
+    // only a `::` written out ends the path
+    assert!(path(&items[2]).is_empty());
+    let named = unwrap_variant!(&items[2].kind, NamedKind::Type);
+    let ty = unwrap_variant!(&named.ty, ItemTy::Referred);
+    let names: Vec<_> = ty
+        .path
+        .path
+        .iter()
+        .map(|name| name.span.to_string())
+        .collect();
+    assert_eq!(names, ["a", "b"]);
+    assert!(path(&items[3]).is_empty());
+}
+
+#[test]
+fn attributes() {
+    let items = items(
+        "rc :: @(no_copy) struct {\n    @hidden inner *u8,\n}\n\
+         trap :: @extern(\"__buildin__\") func() !;\n\
+         f :: @a @(b, c(1, 2)) func(@d x u8) u8 { x }\n",
+    );
+
+    let attrs = |attrs: &[Attribute]| -> Vec<(String, Option<usize>)> {
+        attrs
+            .iter()
+            .map(|attr| {
+                let args = attr.args.as_ref().map(|args| args.values.len());
+                (attr.name.span.to_string(), args)
+            })
+            .collect()
+    };
+    assert_eq!(attrs(&items[0].attrs), [("no_copy".into(), None)]);
+    assert_eq!(attrs(&items[1].attrs), [("extern".into(), Some(1))]);
+    assert_eq!(
+        attrs(&items[2].attrs),
+        [
+            ("a".into(), None),
+            ("b".into(), None),
+            ("c".into(), Some(2))
+        ]
+    );
+
+    let NamedKind::Type(ItemNamedType {
+        ty: ItemTy::Struct(rc),
+        ..
+    }) = &items[0].kind
+    else {
+        panic!("expected a struct")
+    };
+    assert_eq!(attrs(&rc.fields[0].attrs), [("hidden".into(), None)]);
+    let NamedKind::Func(f) = &items[2].kind else {
+        panic!("expected a function")
+    };
+    assert_eq!(attrs(&f.func.args[0].attrs), [("d".into(), None)]);
+
+    let Expr::String(_, name) = &items[1].attrs[0].args.as_ref().unwrap().values[0] else {
+        panic!("expected a string")
+    };
+    assert_eq!(name, "__buildin__");
+}
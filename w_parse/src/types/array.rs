@@ -4,7 +4,7 @@ use either::Either;
 use nom::branch::alt;
 use nom::combinator::{all_consuming, map, opt, recognize};
 use nom::{Offset, Slice};
-use w_tokenize::{Number, Span};
+use w_tokenize::{Kind, Number, Span, Token};
 
 #[derive(Debug, Clone)]
 pub struct TyArray {
@@ -18,6 +18,39 @@ pub fn parse_ty_array(oi: TokenSpan) -> ParResult<TyArray> {
         tag!(Kind::Array(_), Token { kind: Kind::Array(vals), .. } => vals)(oi.clone())?;
     let array = TokenSpan::new(i.file.clone(), array);
 
+    if matches!(
+        &*array,
+        [Token {
+            kind: Kind::Float(_),
+            ..
+        }]
+    ) {
+        return Err(nom::Err::Failure(ErrorChain::from(Error::new(
+            array,
+            "Array sizes must be integers, not floats",
+        ))));
+    }
+
+    // literals are lexed without a sign, `-1` is a negation of `1`
+    if matches!(
+        &*array,
+        [
+            Token {
+                kind: Kind::Sub,
+                ..
+            },
+            Token {
+                kind: Kind::Number(_),
+                ..
+            }
+        ]
+    ) {
+        return Err(nom::Err::Failure(ErrorChain::from(Error::new(
+            array.slice(..1),
+            "Array sizes can't be negative",
+        ))));
+    }
+
     let (_, size) = all_consuming(opt(alt((
         map(
             tag!(Kind::Number(_), Token { kind: Kind::Number(n), .. } => n),
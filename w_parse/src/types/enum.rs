@@ -1,14 +1,23 @@
+use crate::attr::{parse_attributes, Attribute};
 use crate::expr::parse_many0;
 use crate::types::tuple::{parse_ty_tuple, TyTuple};
+use crate::util::{parse_docs, Docs};
 use crate::{parse_keyword, parse_name, tag, Ident, ParResult, TokenSpan};
 use nom::combinator::{all_consuming, opt};
-use nom::sequence::pair;
 use w_tokenize::Span;
 
 #[derive(Debug, Clone)]
 pub struct TyEnum {
     pub span_enum: Span,
-    pub variants: Vec<(Ident, Option<TyTuple>)>,
+    pub variants: Vec<EnumVariant>,
+}
+
+#[derive(Debug, Clone)]
+pub struct EnumVariant {
+    pub docs: Docs,
+    pub attrs: Vec<Attribute>,
+    pub name: Ident,
+    pub fields: Option<TyTuple>,
 }
 
 pub fn parse_ty_enum(i: TokenSpan) -> ParResult<TyEnum> {
@@ -17,7 +26,7 @@ pub fn parse_ty_enum(i: TokenSpan) -> ParResult<TyEnum> {
     let (i, block) = tag!(Kind::Block(_), Token { kind: Kind::Block(vals), .. } => vals)(i)?;
     let block = TokenSpan::new(i.file.clone(), block);
 
-    let (_, variants) = all_consuming(parse_many0(pair(parse_name, opt(parse_ty_tuple))))(block)?;
+    let (_, variants) = all_consuming(parse_many0(parse_enum_variant))(block)?;
 
     Ok((
         i,
@@ -27,3 +36,20 @@ pub fn parse_ty_enum(i: TokenSpan) -> ParResult<TyEnum> {
         },
     ))
 }
+
+fn parse_enum_variant(i: TokenSpan) -> ParResult<EnumVariant> {
+    let (i, docs) = parse_docs(i)?;
+    let (i, attrs) = parse_attributes(i)?;
+    let (i, name) = parse_name(i)?;
+    let (i, fields) = opt(parse_ty_tuple)(i)?;
+
+    Ok((
+        i,
+        EnumVariant {
+            docs,
+            attrs,
+            name,
+            fields,
+        },
+    ))
+}
@@ -0,0 +1,18 @@
+use crate::expr::path::{parse_path, ExprPath};
+use crate::generics::{parse_generic_args, GenericArgs};
+use crate::{ParResult, TokenSpan};
+use nom::combinator::opt;
+
+/// A type by its path, `option(*T)` applies generic arguments to it
+#[derive(Debug, Clone)]
+pub struct TyReferred {
+    pub path: ExprPath,
+    pub args: Option<GenericArgs>,
+}
+
+pub fn parse_ty_referred(i: TokenSpan) -> ParResult<TyReferred> {
+    let (i, path) = parse_path(i)?;
+    let (i, args) = opt(parse_generic_args)(i)?;
+
+    Ok((i, TyReferred { path, args }))
+}
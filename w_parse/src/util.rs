@@ -1,14 +1,71 @@
-use crate::{parse_name, parse_type, Ident, ItemTy, ParResult, TokenSpan};
+use crate::attr::{parse_attributes, Attribute};
+use crate::{parse_name, parse_type, tag, Ident, ItemTy, ParResult, TokenSpan};
+use nom::combinator::map;
+use nom::multi::many0;
+use w_tokenize::Span;
 
 #[derive(Debug, Clone)]
 pub struct NameTyPair {
+    pub docs: Docs,
+    pub attrs: Vec<Attribute>,
     pub name: Ident,
     pub ty: ItemTy,
 }
 
+/// Consecutive doc comments, one entry per line
+#[derive(Debug, Clone, Default)]
+pub struct Docs(pub Vec<(Span, String)>);
+
+impl Docs {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The documentation with the lines joined back together
+    pub fn text(&self) -> String {
+        self.0
+            .iter()
+            .map(|(_, line)| line.as_str())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
 pub fn parse_name_ty_pair(i: TokenSpan) -> ParResult<NameTyPair> {
+    let (i, docs) = parse_docs(i)?;
+    let (i, attrs) = parse_attributes(i)?;
     let (i, name) = parse_name(i)?;
     let (i, ty) = parse_type(i)?;
 
-    Ok((i, NameTyPair { name, ty }))
+    Ok((
+        i,
+        NameTyPair {
+            docs,
+            attrs,
+            name,
+            ty,
+        },
+    ))
+}
+
+/// `/// TEXT` lines in front of an item, field or variant
+pub fn parse_docs(i: TokenSpan) -> ParResult<Docs> {
+    map(
+        many0(tag!(
+            Kind::DocComment(_),
+            Token { kind: Kind::DocComment(text), span, .. } => (span, text)
+        )),
+        Docs,
+    )(i)
+}
+
+/// `//! TEXT` lines at the start of a module
+pub fn parse_inner_docs(i: TokenSpan) -> ParResult<Docs> {
+    map(
+        many0(tag!(
+            Kind::InnerDocComment(_),
+            Token { kind: Kind::InnerDocComment(text), span, .. } => (span, text)
+        )),
+        Docs,
+    )(i)
 }
@@ -0,0 +1,273 @@
+use crate::expr::block::{BlockKind, ExprBlock};
+use crate::expr::branch::ExprBranch;
+use crate::expr::defer::ExprDefer;
+use crate::expr::define::ExprDefine;
+use crate::expr::jump::{ExprBreak, ExprReturn};
+use crate::expr::loops::{ExprFor, ExprLoop, ExprWhile};
+use crate::expr::r#match::{ExprMatch, MatchArm};
+use crate::expr::Expr;
+use crate::pattern::{PatStruct, PatVariant, Pattern};
+
+/// Walks the expressions of a function body, every method visits the children of what it's
+/// given unless it is overridden. Overrides call the matching `walk_` function to continue
+/// into the children.
+pub trait Visitor {
+    fn visit_expr(&mut self, expr: &Expr) {
+        walk_expr(self, expr)
+    }
+
+    fn visit_block(&mut self, block: &ExprBlock) {
+        walk_block(self, block)
+    }
+
+    fn visit_pattern(&mut self, pattern: &Pattern) {
+        walk_pattern(self, pattern)
+    }
+}
+
+pub fn walk_block<V: Visitor + ?Sized>(v: &mut V, block: &ExprBlock) {
+    match &block.kind {
+        BlockKind::Many { stmts, returning } => {
+            for stmt in stmts {
+                v.visit_expr(&stmt.expr);
+            }
+            if let Some(expr) = returning {
+                v.visit_expr(expr);
+            }
+        }
+        BlockKind::Inline(expr) => v.visit_expr(expr),
+    }
+}
+
+pub fn walk_expr<V: Visitor + ?Sized>(v: &mut V, expr: &Expr) {
+    match expr {
+        Expr::Block(block) => v.visit_block(block),
+        Expr::Branch(ExprBranch {
+            cond,
+            body,
+            body_else,
+            ..
+        }) => {
+            v.visit_expr(cond);
+            v.visit_block(body);
+            if let Some(body_else) = body_else {
+                v.visit_block(body_else);
+            }
+        }
+        Expr::Match(ExprMatch { value, arms, .. }) => {
+            v.visit_expr(value);
+            for MatchArm { pattern, body } in arms {
+                v.visit_pattern(pattern);
+                v.visit_block(body);
+            }
+        }
+        Expr::While(ExprWhile { cond, body, .. }) => {
+            v.visit_expr(cond);
+            v.visit_block(body);
+        }
+        Expr::For(ExprFor {
+            pattern,
+            iterable,
+            body,
+            ..
+        }) => {
+            v.visit_pattern(pattern);
+            v.visit_expr(iterable);
+            v.visit_block(body);
+        }
+        Expr::Loop(ExprLoop { body, .. }) => v.visit_block(body),
+
+        Expr::Break(ExprBreak { value, .. }) | Expr::Return(ExprReturn { value, .. }) => {
+            if let Some(value) = value {
+                v.visit_expr(value);
+            }
+        }
+        Expr::Defer(ExprDefer { expr, .. }) => v.visit_expr(expr),
+        Expr::Define(ExprDefine { pattern, value, .. }) => {
+            v.visit_pattern(pattern);
+            v.visit_expr(value);
+        }
+        Expr::Assign(asg) => {
+            v.visit_expr(&asg.assignee);
+            v.visit_expr(&asg.value);
+        }
+
+        Expr::Tuple(many) => many.values.iter().for_each(|expr| v.visit_expr(expr)),
+        Expr::Array(many) => many.values.iter().for_each(|expr| v.visit_expr(expr)),
+        Expr::Ctor(ctor) => ctor.vals.values.iter().for_each(|(_, e)| v.visit_expr(e)),
+        Expr::Call(call) => {
+            v.visit_expr(&call.base);
+            call.args.values.iter().for_each(|expr| v.visit_expr(expr));
+        }
+        Expr::Index(index) => {
+            v.visit_expr(&index.base);
+            index.args.values.iter().for_each(|expr| v.visit_expr(expr));
+        }
+        Expr::Binary(bin) => {
+            v.visit_expr(&bin.left);
+            v.visit_expr(&bin.right);
+        }
+        Expr::Range(range) => {
+            range.start.iter().for_each(|expr| v.visit_expr(expr));
+            range.end.iter().for_each(|expr| v.visit_expr(expr));
+        }
+        Expr::Unary(unary) => v.visit_expr(&unary.expr),
+        Expr::Cast(cast) => v.visit_expr(&cast.expr),
+        Expr::Field(field) => v.visit_expr(&field.base),
+
+        Expr::Continue(_)
+        | Expr::Path(_)
+        | Expr::Number(_)
+        | Expr::Float(_)
+        | Expr::String(..)
+        | Expr::Char(..)
+        | Expr::Byte(..)
+        | Expr::ByteString(..)
+        | Expr::Ident(_)
+        | Expr::Error(_) => {}
+    }
+}
+
+/// Walks the expressions of a function body like [`Visitor`], allowing them to be replaced
+pub trait VisitorMut {
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        walk_expr_mut(self, expr)
+    }
+
+    fn visit_block_mut(&mut self, block: &mut ExprBlock) {
+        walk_block_mut(self, block)
+    }
+}
+
+pub fn walk_block_mut<V: VisitorMut + ?Sized>(v: &mut V, block: &mut ExprBlock) {
+    match &mut block.kind {
+        BlockKind::Many { stmts, returning } => {
+            for stmt in stmts {
+                v.visit_expr_mut(&mut stmt.expr);
+            }
+            if let Some(expr) = returning {
+                v.visit_expr_mut(expr);
+            }
+        }
+        BlockKind::Inline(expr) => v.visit_expr_mut(expr),
+    }
+}
+
+pub fn walk_expr_mut<V: VisitorMut + ?Sized>(v: &mut V, expr: &mut Expr) {
+    match expr {
+        Expr::Block(block) => v.visit_block_mut(block),
+        Expr::Branch(ExprBranch {
+            cond,
+            body,
+            body_else,
+            ..
+        }) => {
+            v.visit_expr_mut(cond);
+            v.visit_block_mut(body);
+            if let Some(body_else) = body_else {
+                v.visit_block_mut(body_else);
+            }
+        }
+        Expr::Match(ExprMatch { value, arms, .. }) => {
+            v.visit_expr_mut(value);
+            for arm in arms {
+                v.visit_block_mut(&mut arm.body);
+            }
+        }
+        Expr::While(ExprWhile { cond, body, .. }) => {
+            v.visit_expr_mut(cond);
+            v.visit_block_mut(body);
+        }
+        Expr::For(ExprFor { iterable, body, .. }) => {
+            v.visit_expr_mut(iterable);
+            v.visit_block_mut(body);
+        }
+        Expr::Loop(ExprLoop { body, .. }) => v.visit_block_mut(body),
+
+        Expr::Break(ExprBreak { value, .. }) | Expr::Return(ExprReturn { value, .. }) => {
+            if let Some(value) = value {
+                v.visit_expr_mut(value);
+            }
+        }
+        Expr::Defer(ExprDefer { expr, .. }) => v.visit_expr_mut(expr),
+        Expr::Define(ExprDefine { value, .. }) => v.visit_expr_mut(value),
+        Expr::Assign(asg) => {
+            v.visit_expr_mut(&mut asg.assignee);
+            v.visit_expr_mut(&mut asg.value);
+        }
+
+        Expr::Tuple(many) => many
+            .values
+            .iter_mut()
+            .for_each(|expr| v.visit_expr_mut(expr)),
+        Expr::Array(many) => many
+            .values
+            .iter_mut()
+            .for_each(|expr| v.visit_expr_mut(expr)),
+        Expr::Ctor(ctor) => ctor
+            .vals
+            .values
+            .iter_mut()
+            .for_each(|(_, e)| v.visit_expr_mut(e)),
+        Expr::Call(call) => {
+            v.visit_expr_mut(&mut call.base);
+            call.args
+                .values
+                .iter_mut()
+                .for_each(|expr| v.visit_expr_mut(expr));
+        }
+        Expr::Index(index) => {
+            v.visit_expr_mut(&mut index.base);
+            index
+                .args
+                .values
+                .iter_mut()
+                .for_each(|expr| v.visit_expr_mut(expr));
+        }
+        Expr::Binary(bin) => {
+            v.visit_expr_mut(&mut bin.left);
+            v.visit_expr_mut(&mut bin.right);
+        }
+        Expr::Range(range) => {
+            range
+                .start
+                .iter_mut()
+                .for_each(|expr| v.visit_expr_mut(expr));
+            range.end.iter_mut().for_each(|expr| v.visit_expr_mut(expr));
+        }
+        Expr::Unary(unary) => v.visit_expr_mut(&mut unary.expr),
+        Expr::Cast(cast) => v.visit_expr_mut(&mut cast.expr),
+        Expr::Field(field) => v.visit_expr_mut(&mut field.base),
+
+        Expr::Continue(_)
+        | Expr::Path(_)
+        | Expr::Number(_)
+        | Expr::Float(_)
+        | Expr::String(..)
+        | Expr::Char(..)
+        | Expr::Byte(..)
+        | Expr::ByteString(..)
+        | Expr::Ident(_)
+        | Expr::Error(_) => {}
+    }
+}
+
+/// Literals and the ends of ranges aren't visited as expressions
+pub fn walk_pattern<V: Visitor + ?Sized>(v: &mut V, pattern: &Pattern) {
+    match pattern {
+        Pattern::Tuple(_, fields)
+        | Pattern::Variant(PatVariant {
+            payload: Some((_, fields)),
+            ..
+        }) => fields.iter().for_each(|field| v.visit_pattern(field)),
+        Pattern::Struct(PatStruct { fields, .. }) => fields
+            .iter()
+            .filter_map(|field| field.pattern.as_ref())
+            .for_each(|field| v.visit_pattern(field)),
+        Pattern::Bind(_)
+        | Pattern::Fill(_)
+        | Pattern::Variant(_)
+        | Pattern::Literal(_)
+        | Pattern::Range(_) => {}
+    }
+}
@@ -1,34 +1,51 @@
-use nom::error::{ErrorKind, ParseError};
 use nom::{
-    AsBytes, Compare, CompareResult, ExtendInto, FindToken, IResult, InputIter, InputLength,
-    InputTake, InputTakeAtPosition, Needed, Offset, Slice, UnspecializedInput,
+    AsBytes, Compare, CompareResult, ExtendInto, FindToken, InputIter, InputLength, InputTake,
+    Needed, Offset, Slice, UnspecializedInput,
 };
 use std::borrow::Borrow;
 use std::fmt::{Debug, Display, Formatter};
-use std::iter::Enumerate;
-use std::mem::{transmute, MaybeUninit};
-use std::ops::{Deref, RangeBounds, RangeFrom};
-use std::pin::Pin;
-use std::rc::Rc;
-use std::str::{CharIndices, Chars};
+use std::ops::{Bound, Deref, RangeBounds};
 use std::sync::Arc;
 
+/// Part of a shared, immutable source text.
+///
+/// The text itself is never copied, slicing only narrows the byte range the handle refers
+/// to. Handles are cheap to clone and can be sent to and shared between threads.
 #[derive(Clone)]
 pub struct RcStr {
-    /// may not change ever
-    _origin: Arc<String>,
-    fragment: &'static str,
+    origin: Arc<str>,
+    start: usize,
+    end: usize,
 }
 
 impl RcStr {
     pub fn new(str: String) -> Self {
-        let origin = Arc::new(str);
+        let origin = Arc::<str>::from(str);
 
-        let fragment = origin.as_str();
+        Self {
+            start: 0,
+            end: origin.len(),
+            origin,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.origin[self.start..self.end]
+    }
+
+    pub fn offset(&self, second: &str) -> usize {
+        self.as_str().offset(second)
+    }
+
+    /// Narrows the handle to `start..end` of its current text
+    fn sub(&self, start: usize, end: usize) -> Self {
+        // checks the bounds and char boundaries the same way slicing a `str` does
+        let _ = &self.as_str()[start..end];
 
         Self {
-            fragment: unsafe { transmute(fragment) },
-            _origin: origin,
+            origin: self.origin.clone(),
+            start: self.start + start,
+            end: self.start + end,
         }
     }
 }
@@ -37,23 +54,23 @@ impl Deref for RcStr {
     type Target = str;
 
     fn deref(&self) -> &Self::Target {
-        self.fragment
+        self.as_str()
     }
 }
 
 impl AsBytes for RcStr {
     fn as_bytes(&self) -> &[u8] {
-        self.fragment.as_bytes()
+        self.as_str().as_bytes()
     }
 }
 
 impl Compare<RcStr> for RcStr {
-    fn compare(&self, t: RcStr) -> nom::CompareResult {
-        self.fragment.compare(t.fragment)
+    fn compare(&self, t: RcStr) -> CompareResult {
+        self.as_str().compare(t.as_str())
     }
 
-    fn compare_no_case(&self, t: RcStr) -> nom::CompareResult {
-        self.fragment.compare_no_case(t.fragment)
+    fn compare_no_case(&self, t: RcStr) -> CompareResult {
+        self.as_str().compare_no_case(t.as_str())
     }
 }
 
@@ -66,69 +83,85 @@ impl ExtendInto for RcStr {
     }
 
     fn extend_into(&self, acc: &mut Self::Extender) {
-        acc.push_str(self.fragment);
+        acc.push_str(self.as_str());
     }
 }
 
 impl FindToken<char> for RcStr {
     fn find_token(&self, token: char) -> bool {
-        self.fragment.contains(token)
+        self.as_str().contains(token)
+    }
+}
+
+/// Chars of an [`RcStr`] with their byte offsets, keeping the text alive on its own
+pub struct CharIndices {
+    text: RcStr,
+    pos: usize,
+}
+
+impl Iterator for CharIndices {
+    type Item = (usize, char);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let c = self.text.as_str()[self.pos..].chars().next()?;
+        let idx = self.pos;
+        self.pos += c.len_utf8();
+        Some((idx, c))
+    }
+}
+
+/// Chars of an [`RcStr`], keeping the text alive on its own
+pub struct Chars(CharIndices);
+
+impl Iterator for Chars {
+    type Item = char;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(_, c)| c)
     }
 }
 
-// This is not sound and may cause UB
 impl InputIter for RcStr {
     type Item = char;
-    type Iter = CharIndices<'static>;
-    type IterElem = Chars<'static>;
+    type Iter = CharIndices;
+    type IterElem = Chars;
 
     fn iter_indices(&self) -> Self::Iter {
-        self.fragment.char_indices()
+        CharIndices {
+            text: self.clone(),
+            pos: 0,
+        }
     }
 
     fn iter_elements(&self) -> Self::IterElem {
-        self.fragment.chars()
+        Chars(self.iter_indices())
     }
 
     fn position<P>(&self, predicate: P) -> Option<usize>
     where
         P: Fn(Self::Item) -> bool,
     {
-        self.fragment.position(predicate)
+        self.as_str().position(predicate)
     }
 
     fn slice_index(&self, count: usize) -> Result<usize, Needed> {
-        self.fragment.slice_index(count)
+        self.as_str().slice_index(count)
     }
 }
 
 impl InputLength for RcStr {
     fn input_len(&self) -> usize {
-        self.fragment.len()
+        self.len()
     }
 }
 
 impl InputTake for RcStr {
     fn take(&self, count: usize) -> Self {
-        Self {
-            _origin: self._origin.clone(),
-            fragment: self.fragment.take(count),
-        }
+        self.sub(0, count)
     }
 
     fn take_split(&self, count: usize) -> (Self, Self) {
-        let (left, right) = self.fragment.take_split(count);
-
-        (
-            Self {
-                _origin: self._origin.clone(),
-                fragment: left,
-            },
-            Self {
-                _origin: self._origin.clone(),
-                fragment: right,
-            },
-        )
+        (self.sub(count, self.len()), self.sub(0, count))
     }
 }
 
@@ -136,53 +169,53 @@ impl UnspecializedInput for RcStr {}
 
 impl Offset for RcStr {
     fn offset(&self, second: &Self) -> usize {
-        self.fragment.offset(second.fragment)
-    }
-}
-
-impl RcStr {
-    pub fn offset(&self, second: &str) -> usize {
-        self.fragment.offset(second)
+        debug_assert!(Arc::ptr_eq(&self.origin, &second.origin));
+        second.start - self.start
     }
 }
 
 impl PartialEq<str> for RcStr {
     fn eq(&self, other: &str) -> bool {
-        self.fragment == other
+        self.as_str() == other
     }
 }
 
 impl<'a> PartialEq<&'a str> for RcStr {
     fn eq(&self, other: &&'a str) -> bool {
-        self.fragment == *other
+        self.as_str() == *other
     }
 }
 
 impl<'a> Compare<&'a str> for RcStr {
     fn compare(&self, t: &'a str) -> CompareResult {
-        self.fragment.compare(t)
+        self.as_str().compare(t)
     }
 
     fn compare_no_case(&self, t: &'a str) -> CompareResult {
-        self.fragment.compare_no_case(t)
+        self.as_str().compare_no_case(t)
     }
 }
 
-impl<R: RangeBounds<usize>> Slice<R> for RcStr
-where
-    &'static str: Slice<R>,
-{
+impl<R: RangeBounds<usize>> Slice<R> for RcStr {
     fn slice(&self, range: R) -> Self {
-        Self {
-            _origin: self._origin.clone(),
-            fragment: self.fragment.slice(range),
-        }
+        let start = match range.start_bound() {
+            Bound::Included(&n) => n,
+            Bound::Excluded(&n) => n + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&n) => n + 1,
+            Bound::Excluded(&n) => n,
+            Bound::Unbounded => self.len(),
+        };
+
+        self.sub(start, end)
     }
 }
 
 impl PartialEq for RcStr {
     fn eq(&self, other: &Self) -> bool {
-        self.fragment == other.fragment
+        self.as_str() == other.as_str()
     }
 }
 
@@ -190,18 +223,18 @@ impl Eq for RcStr {}
 
 impl Display for RcStr {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.fragment)
+        f.write_str(self.as_str())
     }
 }
 
 impl Debug for RcStr {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.fragment)
+        f.write_str(self.as_str())
     }
 }
 
 impl Borrow<str> for RcStr {
     fn borrow(&self) -> &str {
-        self.fragment
+        self.as_str()
     }
 }
@@ -0,0 +1,68 @@
+//! Compares the scanner behind `tokenize` with the original nom based tokenizer.
+//!
+//! Run with `cargo bench -p w_tokenize --features reference`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use w_rcstr::RcStr;
+use w_tokenize::{reference, tokenize, FileId, Span, TokResult, Token};
+
+const SOURCES: [&str; 4] = [
+    include_str!("../../WIP_tests/mod_test/vec.w"),
+    include_str!("../../WIP_tests/mod_test/bubble_sort.w"),
+    include_str!("../../WIP_tests/mod_test/iter.w"),
+    include_str!("../../WIP_tests/old_1/generics1.w"),
+];
+
+/// Roughly the size of the generated modules that made lexing show up in profiles
+const TARGET_LINES: usize = 50_000;
+
+fn module() -> String {
+    let mut out = String::new();
+    while out.lines().count() < TARGET_LINES {
+        for src in SOURCES {
+            out.push_str(src);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+fn bench(name: &str, src: &str, f: fn(Span) -> TokResult<Vec<Token>>) -> Duration {
+    let runs = 5;
+    let mut best = Duration::MAX;
+
+    for _ in 0..runs {
+        let span = Span::new_extra(RcStr::new(src.to_string()), FileId::DETACHED.into());
+        let start = Instant::now();
+        let (_, tokens) = f(span).expect("benchmark input tokenizes");
+        let elapsed = start.elapsed();
+        black_box(tokens);
+        best = best.min(elapsed);
+    }
+
+    let mb = src.len() as f64 / (1024.0 * 1024.0);
+    println!(
+        "{name:>10}: {:>8.2?} ({:.1} MiB/s)",
+        best,
+        mb / best.as_secs_f64()
+    );
+    best
+}
+
+fn main() {
+    let src = module();
+    println!(
+        "tokenizing {} lines ({} bytes), best of 5",
+        src.lines().count(),
+        src.len()
+    );
+
+    let reference = bench("reference", &src, reference::tokenize);
+    let scanner = bench("scanner", &src, tokenize);
+
+    println!(
+        "   speedup: {:.1}x",
+        reference.as_secs_f64() / scanner.as_secs_f64()
+    );
+}
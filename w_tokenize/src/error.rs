@@ -1,6 +1,6 @@
-use crate::{Span, TokResult};
+use crate::Span;
 use nom::error::{Error, ErrorKind, ParseError};
-use nom::{Err, IResult, Parser};
+use nom::{Err, IResult};
 use std::borrow::Cow;
 use std::fmt::{Debug, Formatter};
 
@@ -23,7 +23,6 @@ pub trait ToTokenError<Err> {
     type Res;
 
     fn reason(self, msg: impl Into<Cow<'static, str>>) -> Self::Res;
-    fn reason_fn(self, f: impl FnOnce(Err) -> Cow<'static, str>) -> Self::Res;
 }
 
 impl TokenError {
@@ -36,16 +35,6 @@ impl TokenError {
     }
 }
 
-pub fn reason<F, O>(
-    mut parser: F,
-    msg: impl Into<Cow<'static, str>> + Clone,
-) -> impl FnMut(Span) -> TokResult<O>
-where
-    F: Parser<Span, O, TokenError>,
-{
-    move |i| parser.parse(i).reason(msg.clone())
-}
-
 // impl<'a, T> ToTokenError<Error<Span<'a>>> for IResult<Span<'a>, T> {
 //     type Res = IResult<Span<'a>, T, TokenError<'a>>;
 //
@@ -116,27 +105,6 @@ impl<T> ToTokenError<TokenError> for IResult<Span, T, TokenError> {
             })),
         }
     }
-
-    fn reason_fn(self, f: impl FnOnce(TokenError) -> Cow<'static, str>) -> Self::Res {
-        if self.is_ok() {
-            return self;
-        }
-        // SAFETY: We check if self is ok before hand
-        // This is required as we do not require T to implement Debug
-        match unsafe { self.unwrap_err_unchecked() } {
-            Err::Incomplete(n) => Err(Err::Incomplete(n)),
-            Err::Error(err) => Err(Err::Error(TokenError {
-                span: err.span.clone(),
-                kind: TokenErrorKind::Other(Box::new(err.clone())),
-                reason: Some(f(err)),
-            })),
-            Err::Failure(err) => Err(Err::Failure(TokenError {
-                span: err.span.clone(),
-                kind: TokenErrorKind::Other(Box::new(err.clone())),
-                reason: Some(f(err)),
-            })),
-        }
-    }
 }
 
 impl From<Error<Span>> for TokenError {
@@ -159,7 +127,10 @@ impl ParseError<Span> for TokenError {
     }
 
     fn append(input: Span, _kind: ErrorKind, other: Self) -> Self {
-        if other.span == input {
+        // comparing the spans themselves would compare the rest of the input
+        if other.span.location_offset() == input.location_offset()
+            && other.span.len() == input.len()
+        {
             other
         } else {
             Self {
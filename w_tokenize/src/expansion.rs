@@ -0,0 +1,73 @@
+use crate::{Span, Symbol};
+use std::fmt::{Debug, Formatter};
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+/// Identifies one expansion of a macro invocation.
+///
+/// Tokens produced by the macro's template keep the spans of the template and carry the id
+/// in their [`crate::SpanCtx`], which leads back to the invocation. The id holds on to the
+/// [`ExpnData`] itself, it lives as long as any span of the expansion does and reading it
+/// takes no lock.
+#[derive(Clone)]
+pub struct ExpnId(Arc<Expansion>);
+
+struct Expansion {
+    id: u32,
+    data: ExpnData,
+}
+
+#[derive(Debug)]
+pub struct ExpnData {
+    /// Name of the macro in its definition
+    pub name: Span,
+    /// The entire invocation, from the name of the macro to its closing delimiter
+    pub call_site: Span,
+    /// Identifiers the template binds itself, they don't see or shadow the call site's names
+    pub locals: Vec<Symbol>,
+}
+
+static NEXT_ID: AtomicU32 = AtomicU32::new(0);
+
+impl ExpnId {
+    pub fn new(data: ExpnData) -> ExpnId {
+        ExpnId(Arc::new(Expansion {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            data,
+        }))
+    }
+
+    pub(crate) fn id(&self) -> u32 {
+        self.0.id
+    }
+
+    pub fn data(&self) -> &ExpnData {
+        &self.0.data
+    }
+
+    /// Whether the template of this expansion binds `sym` on its own
+    pub fn binds(&self, sym: Symbol) -> bool {
+        self.0.data.locals.contains(&sym)
+    }
+}
+
+impl PartialEq for ExpnId {
+    fn eq(&self, other: &Self) -> bool {
+        self.id() == other.id()
+    }
+}
+
+impl Eq for ExpnId {}
+
+impl Hash for ExpnId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id().hash(state);
+    }
+}
+
+impl Debug for ExpnId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ExpnId({})", self.id())
+    }
+}
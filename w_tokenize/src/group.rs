@@ -0,0 +1,148 @@
+use crate::{Float, Kind, Number, Span, Token};
+use nom::Slice;
+use std::sync::Arc;
+
+/// The tokens between a pair of delimiters.
+///
+/// [`retokenize`](crate::retokenize) shares the groups behind an edit with the old tree
+/// instead of copying them. Their tokens keep the positions they were lexed at, the group
+/// remembers how far it moved since. [`Group::tokens`] moves them to where they are now.
+#[derive(Debug, Clone, Default)]
+pub struct Group {
+    tokens: Arc<[Token]>,
+    shift: Option<Shift>,
+}
+
+/// How far tokens moved since they were lexed
+#[derive(Debug, Clone)]
+pub(crate) struct Shift {
+    pub offset: isize,
+    pub lines: isize,
+    /// The entire file the tokens moved in
+    pub source: Span,
+}
+
+impl Group {
+    pub fn new(tokens: impl Into<Arc<[Token]>>) -> Self {
+        Group {
+            tokens: tokens.into(),
+            shift: None,
+        }
+    }
+
+    /// The tokens at their current position. Only copies them if the group moved, groups
+    /// inside of them are shared either way.
+    pub fn tokens(&self) -> Arc<[Token]> {
+        match &self.shift {
+            None => self.tokens.clone(),
+            Some(shift) => self.tokens.iter().map(|token| shift.token(token)).collect(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    /// Whether both groups hold the very same tokens, no matter where they moved
+    pub fn shares(&self, other: &Group) -> bool {
+        Arc::ptr_eq(&self.tokens, &other.tokens)
+    }
+
+    /// Mutable access to the tokens, which are copied if they are shared or moved
+    pub(crate) fn tokens_mut(&mut self) -> &mut [Token] {
+        if self.shift.is_some() {
+            *self = Group::new(self.tokens());
+        }
+        Arc::make_mut(&mut self.tokens)
+    }
+
+    /// The same group moved by `shift`, sharing its tokens
+    fn moved(&self, shift: &Shift) -> Group {
+        let (offset, lines) = match &self.shift {
+            Some(own) => (own.offset + shift.offset, own.lines + shift.lines),
+            None => (shift.offset, shift.lines),
+        };
+
+        Group {
+            tokens: self.tokens.clone(),
+            shift: Some(Shift {
+                offset,
+                lines,
+                source: shift.source.clone(),
+            }),
+        }
+    }
+}
+
+impl From<Vec<Token>> for Group {
+    fn from(tokens: Vec<Token>) -> Self {
+        Group::new(tokens)
+    }
+}
+
+impl From<Arc<[Token]>> for Group {
+    fn from(tokens: Arc<[Token]>) -> Self {
+        Group::new(tokens)
+    }
+}
+
+impl From<Group> for Arc<[Token]> {
+    fn from(group: Group) -> Self {
+        group.tokens()
+    }
+}
+
+impl PartialEq for Group {
+    fn eq(&self, other: &Self) -> bool {
+        self.tokens == other.tokens
+    }
+}
+
+impl Eq for Group {}
+
+impl Shift {
+    /// `token` at its new position, trivia is dropped
+    pub fn token(&self, token: &Token) -> Token {
+        let kind = match &token.kind {
+            Kind::Tuple(inner) => Kind::Tuple(inner.moved(self)),
+            Kind::Block(inner) => Kind::Block(inner.moved(self)),
+            Kind::Array(inner) => Kind::Array(inner.moved(self)),
+            Kind::Number(num) => Kind::Number(Box::new(Number {
+                number: self.span(&num.number),
+                suffix: num.suffix.as_ref().map(|span| self.span(span)),
+                base: num.base.as_ref().map(|span| self.span(span)),
+            })),
+            Kind::Float(num) => Kind::Float(Box::new(Float {
+                integer: self.span(&num.integer),
+                fraction: num.fraction.as_ref().map(|span| self.span(span)),
+                exponent: num.exponent.as_ref().map(|span| self.span(span)),
+                suffix: num.suffix.as_ref().map(|span| self.span(span)),
+            })),
+            kind => kind.clone(),
+        };
+
+        Token {
+            span: self.span(&token.span),
+            kind,
+            trivia: None,
+        }
+    }
+
+    pub fn span(&self, span: &Span) -> Span {
+        let offset = (span.location_offset() as isize + self.offset) as usize;
+        let line = (span.location_line() as isize + self.lines) as u32;
+        span_at(&self.source, offset, span.len(), line)
+    }
+}
+
+/// Span of `source` without recounting the lines in front of it
+pub(crate) fn span_at(source: &Span, offset: usize, len: usize, line: u32) -> Span {
+    let fragment = source.fragment().slice(offset..offset + len);
+    // SAFETY: the fragment is taken from `source` at `offset`, which starts at the very
+    // beginning of the file
+    unsafe { Span::new_from_raw_offset(offset, line, fragment, source.extra.clone()) }
+}
@@ -0,0 +1,355 @@
+use crate::group::{span_at, Shift};
+use crate::scanner::{closer, Lexeme, Scanner};
+use crate::{tokenize, Group, Kind, Span, TokResult, Token};
+use nom::Slice;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// Replacement of the bytes in `range` of the old source by `text`
+#[derive(Debug, Clone)]
+pub struct TextEdit {
+    pub range: Range<usize>,
+    pub text: String,
+}
+
+impl TextEdit {
+    pub fn new(range: Range<usize>, text: impl Into<String>) -> Self {
+        TextEdit {
+            range,
+            text: text.into(),
+        }
+    }
+
+    /// The source after the edit
+    pub fn apply(&self, src: &str) -> String {
+        let mut out = String::with_capacity(src.len() - self.range.len() + self.text.len());
+        out.push_str(&src[..self.range.start]);
+        out.push_str(&self.text);
+        out.push_str(&src[self.range.end..]);
+        out
+    }
+
+    fn delta(&self) -> isize {
+        self.text.len() as isize - self.range.len() as isize
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Retokenized {
+    pub tokens: Vec<Token>,
+    /// Items of the old tree that were dropped, as split by [`items`]
+    pub replaced: Range<usize>,
+    /// Items of the new tree that took their place, they have to be parsed again
+    pub touched: Range<usize>,
+}
+
+/// Updates the tokens of a file after `edit` was applied to it, `source` is the entire
+/// file after the edit.
+///
+/// Only the innermost group containing the edit is lexed again, and only up to the first
+/// token after the edit that ends where an old token ended. Everything in front of the
+/// edit is shared with the old tree. Tokens behind it are moved to their new position
+/// without lexing them again, groups among them are shared as well and only remember how
+/// far they moved, see [`Group`].
+///
+/// Edits that change the nesting of groups fall back to the enclosing group, ultimately to
+/// tokenizing the entire file. Errors are the same [`tokenize`] reports. The old tokens are
+/// expected to come from [`tokenize`] or an earlier call, trivia of tokens that moved is
+/// dropped.
+pub fn retokenize(old: &[Token], source: Span, edit: &TextEdit) -> TokResult<Retokenized> {
+    assert_eq!(
+        source.location_offset(),
+        0,
+        "source must be the entire file"
+    );
+
+    let relexer = Relexer::new(old, &source, edit);
+    let relexed = relexer.relex(old, 0, None);
+
+    let (rest, relexed) = match relexed {
+        Some(relexed) => (source.slice(source.len()..), relexed),
+        None => {
+            let (rest, tokens) = tokenize(source)?;
+            let relexed = Relexed {
+                replaced: 0..old.len(),
+                touched: 0..tokens.len(),
+                tokens,
+            };
+            (rest, relexed)
+        }
+    };
+
+    let (replaced, touched) = touched_items(old, &relexed);
+    Ok((
+        rest,
+        Retokenized {
+            tokens: relexed.tokens,
+            replaced,
+            touched,
+        },
+    ))
+}
+
+/// Splits the top-level tokens of a module into its items.
+///
+/// An item starts after a `;`, at a doc comment, at `name ::`, `name(T) ::`,
+/// `name(T):member ::`, `{imports} ::` or `@(attributes)`, which is where the parser picks
+/// up again after a syntax error. Doc comments and attributes stay with the item behind
+/// them, and nothing behind a `::` starts an item.
+pub fn items(tokens: &[Token]) -> Vec<Range<usize>> {
+    let mut acc = vec![];
+    let mut start = 0;
+
+    for idx in 1..tokens.len() {
+        if starts_item(tokens, idx) {
+            acc.push(start..idx);
+            start = idx;
+        }
+    }
+    if start < tokens.len() {
+        acc.push(start..tokens.len());
+    }
+
+    acc
+}
+
+/// Whether an item starts at `tokens[idx]`, see [`items`]
+pub fn starts_item(tokens: &[Token], idx: usize) -> bool {
+    let Some(prev) = idx.checked_sub(1).map(|prev| &tokens[prev].kind) else {
+        return true;
+    };
+
+    let next = |offset: usize| tokens.get(idx + offset).map(|tk| &tk.kind);
+    let starts = match &tokens[idx].kind {
+        Kind::DocComment(_) => true,
+        // the tokenizer reads `:` as `::`, this covers `name(T):member ::` as well
+        Kind::Ident => match next(1) {
+            Some(Kind::DoubleCol) => true,
+            Some(Kind::Tuple(_)) => next(2) == Some(&Kind::DoubleCol),
+            _ => false,
+        },
+        Kind::Block(_) => next(1) == Some(&Kind::DoubleCol),
+        Kind::At => matches!(next(1), Some(Kind::Tuple(_))),
+        _ => false,
+    };
+
+    // attributes stay with the item behind them, just like doc comments
+    let attributed = matches!(prev, Kind::Tuple(_)) && idx >= 2 && tokens[idx - 2].kind == Kind::At;
+    let continues = matches!(prev, Kind::DocComment(_) | Kind::DoubleCol) || attributed;
+
+    *prev == Kind::Semicolon || (starts && !continues)
+}
+
+/// The items around the tokens that were relexed, items in front of and behind them that
+/// still consist of the same tokens are left out
+fn touched_items(old: &[Token], relexed: &Relexed) -> (Range<usize>, Range<usize>) {
+    let (old_items, new_items) = (items(old), items(&relexed.tokens));
+    let (replaced, touched) = (&relexed.replaced, &relexed.touched);
+
+    let front = old_items
+        .iter()
+        .zip(&new_items)
+        .take_while(|(old, new)| old == new && old.end <= replaced.start)
+        .count();
+
+    let max_back = (old_items.len() - front).min(new_items.len() - front);
+    let back = old_items
+        .iter()
+        .rev()
+        .zip(new_items.iter().rev())
+        .take(max_back)
+        .take_while(|(old, new)| {
+            old.start >= replaced.end
+                && new.start >= touched.end
+                && old.start - replaced.end == new.start - touched.end
+                && old.len() == new.len()
+        })
+        .count();
+
+    (front..old_items.len() - back, front..new_items.len() - back)
+}
+
+/// Top-level tokens replaced by relexing
+struct Relexed {
+    tokens: Vec<Token>,
+    /// Tokens of the old list that were dropped
+    replaced: Range<usize>,
+    /// Tokens of the new list that took their place
+    touched: Range<usize>,
+}
+
+/// The group a list of tokens is read from
+#[derive(Copy, Clone)]
+struct Enclosing {
+    delim: u8,
+    /// Offset of the closing delimiter in the old source
+    close: usize,
+}
+
+struct Relexer<'a> {
+    source: &'a Span,
+    edit: &'a TextEdit,
+    /// Difference in length between the new and the old source
+    delta: isize,
+    /// How far tokens behind the edit moved
+    shift: Shift,
+}
+
+impl<'a> Relexer<'a> {
+    fn new(old: &[Token], source: &'a Span, edit: &'a TextEdit) -> Self {
+        let delta = edit.delta();
+        let lines = first_after(old, edit.range.end).map_or(0, |token| {
+            let pos = moved(token.span.location_offset(), delta);
+            source.slice(pos..).location_line() as isize - token.span.location_line() as isize
+        });
+
+        Relexer {
+            source,
+            edit,
+            delta,
+            shift: Shift {
+                offset: delta,
+                lines,
+                source: source.clone(),
+            },
+        }
+    }
+
+    /// Relexes `list` whose content starts at `start`, `None` if the edit changed where the
+    /// list ends
+    fn relex(&self, list: &[Token], start: usize, group: Option<Enclosing>) -> Option<Relexed> {
+        let Range { start: a, end: b } = self.edit.range;
+
+        let idx = list.partition_point(|token| end(token) <= a);
+        if let Some(token) = list.get(idx) {
+            let (s, e) = (token.span.location_offset(), end(token));
+
+            if let (Some(inner), true) = (group_tokens(&token.kind), s < a && b < e) {
+                let enclosing = Enclosing {
+                    delim: token.span.as_bytes()[0],
+                    close: e - 1,
+                };
+
+                if let Some(relexed) = self.relex(&inner, s + 1, Some(enclosing)) {
+                    let len = moved(token.span.len(), self.delta);
+                    let tokens = Group::new(relexed.tokens);
+                    let token = Token {
+                        span: span_at(self.source, s, len, token.span.location_line()),
+                        kind: match &token.kind {
+                            Kind::Tuple(_) => Kind::Tuple(tokens),
+                            Kind::Block(_) => Kind::Block(tokens),
+                            Kind::Array(_) => Kind::Array(tokens),
+                            _ => unreachable!(),
+                        },
+                        trivia: None,
+                    };
+
+                    return Some(self.splice(list, idx..idx + 1, vec![token]));
+                }
+            }
+        }
+
+        self.resync(list, start, group)
+    }
+
+    /// Lexes from the last token in front of the edit until the new tokens line up with
+    /// the old ones again
+    fn resync(&self, list: &[Token], start: usize, group: Option<Enclosing>) -> Option<Relexed> {
+        let Range { start: a, end: b } = self.edit.range;
+        let edit_end = moved(b, self.delta);
+
+        // the token in front is followed by unchanged trivia, its end is a safe place to start
+        let from_idx = list.partition_point(|token| end(token) < a);
+        let from = from_idx.checked_sub(1).map_or(start, |idx| end(&list[idx]));
+
+        let mut scanner = Scanner::new(self.source.slice(from..));
+        let mut acc = vec![];
+
+        loop {
+            scanner.skip_trivia().ok()?;
+
+            match scanner.next_lexeme().ok()? {
+                Some(Lexeme::Token(token)) => acc.push(token),
+                Some(Lexeme::Open(span, delim)) => {
+                    let inner = scanner.group(Some((&span, delim))).ok()?;
+                    acc.push(scanner.finish_group(span, delim, inner));
+                }
+                Some(Lexeme::Close(span, delim)) => {
+                    let group = group?;
+                    let closes = delim == closer(group.delim)
+                        && span.location_offset() == moved(group.close, self.delta);
+                    return closes.then(|| self.splice(list, from_idx..list.len(), acc));
+                }
+                None if group.is_some() => return None,
+                None => return Some(self.splice(list, from_idx..list.len(), acc)),
+            }
+
+            let pos = scanner.rest().location_offset();
+            if pos >= edit_end {
+                let old_pos = (pos as isize - self.delta) as usize;
+                let idx = list.partition_point(|token| end(token) < old_pos);
+
+                if list.get(idx).is_some_and(|token| end(token) == old_pos) {
+                    return Some(self.splice(list, from_idx..idx + 1, acc));
+                }
+            }
+        }
+    }
+
+    /// Replaces `range` of `list` by `tokens`, moving everything after them
+    fn splice(&self, list: &[Token], range: Range<usize>, tokens: Vec<Token>) -> Relexed {
+        let touched = range.start..range.start + tokens.len();
+
+        let mut acc = Vec::with_capacity(list.len() - range.len() + tokens.len());
+        acc.extend_from_slice(&list[..range.start]);
+        acc.extend(tokens);
+        acc.extend(
+            list[range.end..]
+                .iter()
+                .map(|token| self.moved_token(token)),
+        );
+
+        Relexed {
+            tokens: acc,
+            replaced: range,
+            touched,
+        }
+    }
+
+    fn moved_token(&self, token: &Token) -> Token {
+        if self.shift.offset == 0 && self.shift.lines == 0 {
+            return token.clone();
+        }
+        self.shift.token(token)
+    }
+}
+
+fn end(token: &Token) -> usize {
+    token.span.location_offset() + token.span.len()
+}
+
+fn moved(pos: usize, delta: isize) -> usize {
+    (pos as isize + delta) as usize
+}
+
+/// The tokens of a group at their current position
+fn group_tokens(kind: &Kind) -> Option<Arc<[Token]>> {
+    match kind {
+        Kind::Tuple(inner) | Kind::Block(inner) | Kind::Array(inner) => Some(inner.tokens()),
+        _ => None,
+    }
+}
+
+/// First token of the tree starting at or after `pos`
+fn first_after(tokens: &[Token], pos: usize) -> Option<Token> {
+    let idx = tokens.partition_point(|token| end(token) <= pos);
+    let token = tokens.get(idx)?;
+
+    if token.span.location_offset() >= pos {
+        return Some(token.clone());
+    }
+
+    group_tokens(&token.kind)
+        .and_then(|inner| first_after(&inner, pos))
+        .or_else(|| tokens.get(idx + 1).cloned())
+}
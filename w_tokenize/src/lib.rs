@@ -2,39 +2,49 @@
 
 extern crate core;
 
-use nom::bytes::complete::{is_not, tag, take_while, take_while_m_n};
-use nom::character::complete::char;
-use nom::combinator::{map, not, opt};
-use std::rc::Rc;
+use nom::bytes::complete::take_while;
 
-use nom::branch::alt;
-
-use nom::multi::{fold_many0, many0};
-use nom::sequence::{delimited, pair, terminated};
-use nom::{Err, IResult, InputLength, Offset, Parser, Slice};
+use nom::{Err, IResult, InputLength, Parser, Slice};
 
 mod error;
+mod expansion;
+mod group;
+// only the reference tokenizer still lexes identifiers with nom
+#[cfg(any(test, feature = "reference"))]
 mod identifier;
+mod incremental;
 mod number;
+#[cfg(any(test, feature = "reference"))]
+pub mod reference;
+mod scanner;
+mod source;
 mod string;
+mod symbol;
 #[cfg(test)]
 mod tests;
+mod trivia;
 
 use crate::error::{TokenError, TokenErrorKind};
-use crate::identifier::parse_ident;
-use crate::number::parse_integer;
-pub use crate::number::Number;
-use crate::string::parse_string;
+pub use crate::expansion::{ExpnData, ExpnId};
+pub use crate::group::Group;
+pub use crate::incremental::{items, retokenize, starts_item, Retokenized, TextEdit};
+pub use crate::number::{Float, Number};
+use crate::scanner::{closer, Lexeme, Scanner};
+pub use crate::source::{FileId, SourceDb, SourceFile, SpanCtx};
+pub use crate::symbol::Symbol;
+pub use crate::trivia::{reconstruct, Trivia, TriviaPiece};
 use error::ToTokenError;
 use w_rcstr::RcStr;
 
-pub type Span = nom_locate::LocatedSpan<RcStr>;
+pub type Span = nom_locate::LocatedSpan<RcStr, SpanCtx>;
 pub type TokResult<R = Span> = IResult<Span, R, TokenError>;
 
 #[derive(Debug, Clone)]
 pub struct Token {
     pub span: Span,
     pub kind: Kind,
+    /// Surrounding whitespace and comments, only filled by [`tokenize_lossless`]
+    pub trivia: Option<Box<Trivia>>,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -64,6 +74,14 @@ pub enum Kind {
     Semicolon,
     /// `.`
     Dot,
+    /// `@`
+    At,
+    /// `$`, marks metavariables in macros
+    Dollar,
+    /// `..`
+    Range,
+    /// `..=`
+    RangeInclusive,
 
     // Math operands
     /// `+`
@@ -110,6 +128,8 @@ pub enum Kind {
     OrL,
     /// `!`
     Not,
+    /// `~`
+    BitNot,
 
     // Assignment operands
     /// `+=`
@@ -134,260 +154,195 @@ pub enum Kind {
     ShrAssign,
 
     /// `(TOKENS)`
-    Tuple(Rc<[Token]>),
+    Tuple(Group),
     /// `{TOKENS}`
-    Block(Rc<[Token]>),
+    Block(Group),
     /// `[TOKENS]`
-    Array(Rc<[Token]>),
+    Array(Group),
 
     String(String),
     Number(Box<Number>),
+    Float(Box<Float>),
+    /// `'a'`
+    Char(char),
+    /// `b'a'`
+    Byte(u8),
+    /// `b"..."`
+    ByteString(Vec<u8>),
+
+    /// `/// TEXT`, documents the item that follows
+    DocComment(String),
+    /// `//! TEXT`, documents the enclosing module
+    InnerDocComment(String),
+
+    /// Unreadable input, only produced by [`tokenize_recovering`]
+    Error,
 }
 
-pub fn tokenize(mut i: Span) -> TokResult<Vec<Token>> {
-    let mut tokens = vec![];
-    loop {
-        let (ni, token) = token(i).reason("failed to parse entire file")?;
-        if let Some(token) = token {
-            tokens.push(token);
-        }
-        i = ni;
-
-        if i.len() == 0 {
-            break;
-        }
-    }
-
-    Ok((i, tokens))
+pub fn tokenize(i: Span) -> TokResult<Vec<Token>> {
+    let mut scanner = Scanner::new(i);
+    let res = scanner.tokens();
+    res.map(|tokens| (scanner.rest().clone(), tokens))
+        .reason("failed to parse entire file")
 }
 
-fn token(i: Span) -> TokResult<Option<Token>> {
-    // yeet the whitespaces
-    let (oi, _) = whitespace(i)?;
-
-    let (i, _) = opt(consume_singleline_comments)(oi.clone())?;
-    let (i, _) = opt(consume_multiline_comments)(i)?;
-
-    let comments_pruned = oi.offset(&i) != 0;
-
-    // this had to be done due to limitations with `alt`
-    let res = alt((
-        map(parse_string, |(span, str)| Token {
-            span,
-            kind: Kind::String(str),
-        }),
-        map(parse_integer, |(span, num)| Token {
-            span,
-            kind: Kind::Number(Box::new(num)),
-        }),
-        parse_block,
-        parse_tuple,
-        parse_array,
-        map(parse_ident, |span| Token {
-            span,
-            kind: Kind::Ident,
-        }),
-        // assignment operators
-        op("_", "", || Kind::Fill),
-        op("->", "", || Kind::InlineBlk),
-        alt((
-            op("<<=", "", || Kind::ShlAssign),
-            op(">>=", "", || Kind::ShrAssign),
-            op("+=", "", || Kind::AddAssign),
-            op("-=", "", || Kind::SubAssign),
-            op("*=", "", || Kind::MulAssign),
-            op("/=", "", || Kind::DivAssign),
-            op("%=", "", || Kind::ModAssign),
-            op("&=", "", || Kind::AndAssign),
-            op("|=", "", || Kind::OrAssign),
-            op("^=", "", || Kind::XorAssign),
-        )),
-        // logic operators
-        alt((
-            op("&&", "", || Kind::AndL),
-            op("||", "", || Kind::OrL),
-            op("!", "", || Kind::Not),
-        )),
-        // bitwise operators
-        alt((
-            op("<<", "", || Kind::Shl),
-            op(">>", "", || Kind::Shr),
-            op("&", "", || Kind::And),
-            op("|", "", || Kind::Or),
-            op("^", "", || Kind::Xor),
-        )),
-        // math operations
-        alt((
-            op("+", "", || Kind::Add),
-            op("-", "", || Kind::Sub),
-            op("*", "", || Kind::Mul),
-            op("/", "", || Kind::Div),
-            op("%", "", || Kind::Mod),
-        )),
-        // comparison operators
-        alt((
-            op("==", "", || Kind::Eq),
-            op("!=", "", || Kind::Neq),
-            op("<=", "", || Kind::Le),
-            op(">=", "", || Kind::Ge),
-            op("<", "", || Kind::Lt),
-            op(">", "", || Kind::Gt),
-        )),
-        // other
-        alt((
-            op("::", ":=", || Kind::DoubleCol),
-            op(":=", ":=", || Kind::Define),
-            op(":", "=", || Kind::DoubleCol),
-            op(",", "", || Kind::Comma),
-            op(".", "", || Kind::Dot),
-            op(";", "", || Kind::Semicolon),
-            op("=", "", || Kind::Assign),
-        )),
-    ))(i.clone());
-
-    if res.is_ok() {
-        res.map(|(i, tok)| (i, Some(tok)))
-    } else if comments_pruned {
-        Ok((i, None))
-    } else {
-        res.map(|_| unreachable!())
-    }
+/// Tokenizes the entire file while keeping every whitespace and comment around.
+///
+/// Each token gets its [`Trivia`] attached, the trivia at the very end of the file is
+/// returned alongside the tokens. Passing both to [`reconstruct`] yields the input again.
+pub fn tokenize_lossless(i: Span) -> TokResult<(Vec<Token>, Span)> {
+    let (ni, mut tokens) = tokenize(i.clone())?;
+    let eof = trivia::attach(&i, &mut tokens);
+    Ok((ni, (tokens, eof)))
 }
 
-fn consume_singleline_comments(mut oi: Span) -> TokResult<()> {
-    loop {
-        let (i, _) = tag("//")(oi)?;
-        let (i, _) = terminated(many0(is_not("\r\n")), whitespace)(i)?;
-        oi = i;
-        if tag::<_, _, TokenError>("//")(oi.clone()).is_err() {
-            break;
-        }
-    }
-    Ok((oi, ()))
+/// Tokenizes the entire file without ever bailing out.
+///
+/// Every lexeme that can't be read is turned into a [`Kind::Error`] token and a matching
+/// [`TokenError`], unbalanced delimiters are closed at the point where they can no longer
+/// continue. The token tree is always complete, which allows reporting every lexical
+/// problem of a file in one go.
+pub fn tokenize_recovering(i: Span) -> (Vec<Token>, Vec<TokenError>) {
+    let mut errs = vec![];
+    let tokens = recover_tokens(&mut Scanner::new(i), &mut vec![], &mut errs);
+    (tokens, errs)
 }
 
-fn consume_multiline_comments(i: Span) -> TokResult<()> {
-    delimited(
-        tag("/*"),
-        fold_many0(
-            alt((
-                consume_multiline_comments,
-                map(pair(not(tag("*/")), take_while_m_n(1, 1, |_| true)), |_| ()),
-            )),
-            || (),
-            |_, _| (),
-        ),
-        pair(tag("*/"), whitespace),
-    )(i)
-}
-
-fn parse_tuple(oi: Span) -> TokResult<Token> {
-    let (i, (span, o)) = parsed_delimited(oi, '(', ')')?;
-
-    Ok((
-        i,
-        Token {
-            span,
-            kind: Kind::Tuple(Rc::from(o.into_boxed_slice())),
-        },
-    ))
-}
-
-fn parse_block(oi: Span) -> TokResult<Token> {
-    let (i, (span, o)) = parsed_delimited(oi, '{', '}')?;
-
-    Ok((
-        i,
-        Token {
-            span,
-            kind: Kind::Block(Rc::from(o.into_boxed_slice())),
-        },
-    ))
-}
-
-fn parse_array(oi: Span) -> TokResult<Token> {
-    let (i, (span, o)) = parsed_delimited(oi, '[', ']')?;
-
-    Ok((
-        i,
-        Token {
-            span,
-            kind: Kind::Array(Rc::from(o.into_boxed_slice())),
-        },
-    ))
-}
-
-fn parsed_delimited(oi: Span, start: char, end: char) -> TokResult<(Span, Vec<Token>)> {
-    let (mut i, _) = pair(char(start), whitespace)(oi.clone())?;
+/// Collects tokens until EOF or until a closing delimiter on the `closers` stack is found,
+/// which is left for the caller to consume
+fn recover_tokens(
+    scanner: &mut Scanner,
+    closers: &mut Vec<u8>,
+    errs: &mut Vec<TokenError>,
+) -> Vec<Token> {
     let mut acc = vec![];
 
-    let last_err;
     loop {
-        match token(i.clone()) {
-            Ok((ni, token)) => {
-                if let Some(token) = token {
-                    acc.push(token)
-                };
-                i = ni;
+        if let Err(err) = scanner.skip_trivia() {
+            // an unterminated block comment runs until the end of the file
+            errs.push(err);
+            scanner.advance(scanner.rest().len());
+        }
+
+        let before = scanner.rest().clone();
+        match scanner.next_lexeme() {
+            Ok(None) => break,
+            Ok(Some(Lexeme::Token(token))) => acc.push(token),
+            Ok(Some(Lexeme::Open(open, delim))) => {
+                acc.push(recover_delimited(scanner, open, delim, closers, errs));
+            }
+            Ok(Some(Lexeme::Close(span, delim))) => {
+                if closers.contains(&delim) {
+                    scanner.reset(before);
+                    break;
+                }
+
+                errs.push(TokenError::new(
+                    span.clone(),
+                    format!("unexpected closing delimiter `{}`", delim as char),
+                ));
+                acc.push(Token {
+                    span,
+                    kind: Kind::Error,
+                    trivia: None,
+                });
             }
-            Err(Err::Error(mut err) | Err::Failure(mut err)) => {
-                err.reason = Some(
-                    format!(
-                        "failure to parse at {}:{}",
-                        i.location_line(),
-                        i.location_offset()
-                    )
-                    .into(),
-                );
-                let err = TokenError {
-                    span: oi.clone(),
+            Err(Err::Error(err)) => {
+                let len = bad_lexeme_len(&before, None);
+                let span = scanner.advance(len);
+                errs.push(TokenError {
+                    span: span.clone(),
                     kind: TokenErrorKind::Other(Box::new(err)),
-                    reason: Some(
-                        "Failed to parse delimited section due to unparseable token inside".into(),
-                    ),
-                };
-                last_err = Some(Err(Err::Failure(err)));
-                break;
+                    reason: Some(format!("unrecognized token `{}`", span.fragment()).into()),
+                });
+                acc.push(Token {
+                    span,
+                    kind: Kind::Error,
+                    trivia: None,
+                });
             }
-            err @ Err(_) => {
-                return err.map(|_| unreachable!());
+            Err(Err::Failure(err)) => {
+                let len = bad_lexeme_len(&before, Some(&err.span));
+                let span = scanner.advance(len);
+                errs.push(err);
+                acc.push(Token {
+                    span,
+                    kind: Kind::Error,
+                    trivia: None,
+                });
             }
+            Err(Err::Incomplete(_)) => unreachable!("complete parsers never request more input"),
         }
     }
 
-    let (i, end_p) = opt(char(end))(i)?;
-    if end_p.is_none() {
-        if let Some(err) = last_err {
-            err
-        } else {
-            Err(Err::Error(TokenError::new(i, format!("expected `{end}`"))))
-        }
+    acc
+}
+
+fn recover_delimited(
+    scanner: &mut Scanner,
+    open: Span,
+    delim: u8,
+    closers: &mut Vec<u8>,
+    errs: &mut Vec<TokenError>,
+) -> Token {
+    let close = closer(delim);
+
+    closers.push(close);
+    let inner = recover_tokens(scanner, closers, errs);
+    closers.pop();
+
+    if scanner.rest().as_bytes().first() == Some(&close) {
+        scanner.advance(1);
     } else {
-        let offset = oi.offset(&i);
-        let span = Span::slice(&oi, ..offset);
-        Ok((whitespace(i)?.0, (span, acc)))
+        errs.push(TokenError::new(
+            open.slice(..1),
+            format!(
+                "unclosed delimiter `{}`, expected `{}`",
+                delim as char, close as char
+            ),
+        ));
     }
+
+    scanner.finish_group(open, delim, inner)
 }
 
-fn op(
-    op: &'static str,
-    bound: &'static str,
-    kind: fn() -> Kind,
-) -> impl FnMut(Span) -> TokResult<Token> {
-    map(bounded(tag(op), move |c| bound.contains(c)), move |o| {
-        Token {
-            span: o,
-            kind: kind(),
-        }
-    })
+/// Length of the unreadable lexeme at the start of `i`, which is always at least one char.
+/// The lexeme extends to the end of `culprit` if a parser committed to reading it.
+fn bad_lexeme_len(i: &Span, culprit: Option<&Span>) -> usize {
+    let committed = culprit.map_or(0, |span| {
+        span.location_offset() + span.len() - i.location_offset()
+    });
+    default_lexeme_len(i).max(committed)
 }
 
-fn defines(i: Span) -> TokResult {
-    bounded(tag("::"), |c| c == ':')(i)
+fn default_lexeme_len(i: &Span) -> usize {
+    let prefix = usize::from(i.starts_with("b\"") || i.starts_with("b'"));
+    let mut chars = i[prefix..].char_indices();
+    let (_, first) = chars.next().unwrap();
+
+    let end = match first {
+        // swallow the rest of a broken text literal on the same line
+        '"' | '\'' => {
+            let mut escaped = false;
+            chars
+                .find(|(_, c)| {
+                    let end = !escaped && (*c == first || matches!(c, '\r' | '\n'));
+                    escaped = !escaped && *c == '\\';
+                    end
+                })
+                .map(|(idx, c)| if c == first { idx + 1 } else { idx })
+        }
+        c if c.is_alphanumeric() || c == '_' => chars
+            .find(|(_, c)| !(c.is_alphanumeric() || *c == '_'))
+            .map(|(idx, _)| idx),
+        _ => return first.len_utf8(),
+    };
+
+    end.map_or(i.len(), |end| end + prefix)
 }
 
 fn boundary(i: Span, mut fail: impl FnMut(char) -> bool) -> TokResult<()> {
-    if i.len() == 0 || {
+    if i.is_empty() || {
         let first = i.chars().next().unwrap();
         char::is_whitespace(first) || !fail(first)
     } {
@@ -471,6 +426,80 @@ impl Kind {
             Kind::Colon => 41,
             Kind::Fill => 42,
             Kind::InlineBlk => 43,
+            Kind::Error => 44,
+            Kind::Float(_) => 45,
+            Kind::Char(_) => 46,
+            Kind::Byte(_) => 47,
+            Kind::ByteString(_) => 48,
+            Kind::DocComment(_) => 49,
+            Kind::InnerDocComment(_) => 50,
+            Kind::At => 51,
+            Kind::Range => 52,
+            Kind::RangeInclusive => 53,
+            Kind::Dollar => 54,
+            Kind::BitNot => 55,
+        }
+    }
+
+    /// Name of the variant, without its payload
+    pub fn name(&self) -> &'static str {
+        match self {
+            Kind::Ident => "Ident",
+            Kind::DoubleCol => "DoubleCol",
+            Kind::Define => "Define",
+            Kind::Assign => "Assign",
+            Kind::Comma => "Comma",
+            Kind::Semicolon => "Semicolon",
+            Kind::Dot => "Dot",
+            Kind::Add => "Add",
+            Kind::Sub => "Sub",
+            Kind::Mul => "Mul",
+            Kind::Div => "Div",
+            Kind::Mod => "Mod",
+            Kind::And => "And",
+            Kind::Or => "Or",
+            Kind::Xor => "Xor",
+            Kind::Shl => "Shl",
+            Kind::Shr => "Shr",
+            Kind::Eq => "Eq",
+            Kind::Neq => "Neq",
+            Kind::Lt => "Lt",
+            Kind::Le => "Le",
+            Kind::Gt => "Gt",
+            Kind::Ge => "Ge",
+            Kind::AndL => "AndL",
+            Kind::OrL => "OrL",
+            Kind::Not => "Not",
+            Kind::AddAssign => "AddAssign",
+            Kind::SubAssign => "SubAssign",
+            Kind::MulAssign => "MulAssign",
+            Kind::DivAssign => "DivAssign",
+            Kind::ModAssign => "ModAssign",
+            Kind::AndAssign => "AndAssign",
+            Kind::OrAssign => "OrAssign",
+            Kind::XorAssign => "XorAssign",
+            Kind::ShlAssign => "ShlAssign",
+            Kind::ShrAssign => "ShrAssign",
+            Kind::Tuple(_) => "Tuple",
+            Kind::Block(_) => "Block",
+            Kind::Array(_) => "Array",
+            Kind::String(_) => "String",
+            Kind::Number(_) => "Number",
+            Kind::Colon => "Colon",
+            Kind::Fill => "Fill",
+            Kind::InlineBlk => "InlineBlk",
+            Kind::Error => "Error",
+            Kind::Float(_) => "Float",
+            Kind::Char(_) => "Char",
+            Kind::Byte(_) => "Byte",
+            Kind::ByteString(_) => "ByteString",
+            Kind::DocComment(_) => "DocComment",
+            Kind::InnerDocComment(_) => "InnerDocComment",
+            Kind::At => "At",
+            Kind::Range => "Range",
+            Kind::RangeInclusive => "RangeInclusive",
+            Kind::Dollar => "Dollar",
+            Kind::BitNot => "BitNot",
         }
     }
 }
@@ -1,25 +1,34 @@
-use crate::{bounded, Span, TokResult};
+use crate::{bounded, Span, TokResult, TokenError};
 use nom::branch::alt;
-use nom::bytes::complete::{is_a, tag, take_while, take_while1};
+use nom::bytes::complete::{is_a, tag, tag_no_case, take_while, take_while1};
+use nom::character::complete::char;
 use nom::combinator::{opt, recognize};
-use nom::sequence::pair;
-use nom::{Offset, Slice};
+use nom::sequence::{pair, preceded};
+use nom::{Err, Offset, Slice};
 
 #[derive(Debug, Clone)]
+/// An unsigned integer literal, a `-` in front of it is a token of its own
 pub struct Number {
-    pub sign: Option<Span>,
     pub number: Span,
     pub suffix: Option<Span>,
     pub base: Option<Span>,
 }
 
+#[derive(Debug, Clone)]
+pub struct Float {
+    pub integer: Span,
+    pub fraction: Option<Span>,
+    /// Exponent digits including their sign, without the leading `e`
+    pub exponent: Option<Span>,
+    pub suffix: Option<Span>,
+}
+
 pub fn parse_integer(i: Span) -> TokResult<(Span, Number)> {
     bounded(parse_integer_inner, |c| c.is_alphanumeric())(i)
 }
 
 fn parse_integer_inner(oi: Span) -> TokResult<(Span, Number)> {
-    let (i, sign) = opt(is_a("+-"))(oi.clone())?;
-    let (i, base) = opt(parse_base)(i)?;
+    let (i, base) = opt(parse_base)(oi.clone())?;
     let num_check = match &base {
         Some(span) if **span == "0x" => |c: char| c.is_ascii_hexdigit(),
         Some(span) if **span == "0b" => |c: char| matches!(c, '0'..='1'),
@@ -44,12 +53,82 @@ fn parse_integer_inner(oi: Span) -> TokResult<(Span, Number)> {
                 number: num,
                 suffix,
                 base,
-                sign,
             },
         ),
     ))
 }
 
+pub fn parse_float(i: Span) -> TokResult<(Span, Float)> {
+    bounded(parse_float_inner, |c| c.is_alphanumeric())(i)
+}
+
+fn parse_float_inner(oi: Span) -> TokResult<(Span, Float)> {
+    if parse_base(oi.clone()).is_ok() {
+        return Err(Err::Error(TokenError::new(oi, "Floats can't have a base")));
+    }
+
+    let (i, integer) = parse_decimal(oi.clone())?;
+    let (i, fraction) = opt(preceded(char('.'), parse_decimal))(i)?;
+    let (i, exponent) = opt(parse_exponent)(i)?;
+    let (i, suffix) = opt(parse_float_suffix)(i)?;
+
+    if fraction.is_none() && exponent.is_none() && suffix.is_none() {
+        return Err(Err::Error(TokenError::new(i, "Not a float")));
+    }
+
+    let (_, trailing) = take_while(|c: char| c.is_alphanumeric() || c == '_')(i.clone())?;
+    if !trailing.is_empty() {
+        let msg = if suffix.is_none() && trailing.starts_with(['u', 'i']) {
+            "Integer suffixes can't be used on float literals"
+        } else {
+            "Invalid float suffix, expected `f32` or `f64`"
+        };
+        return Err(Err::Failure(TokenError::new(trailing, msg)));
+    }
+
+    let offset = oi.offset(&i);
+    let span = Span::slice(&oi, ..offset);
+
+    Ok((
+        i,
+        (
+            span,
+            Float {
+                integer,
+                fraction,
+                exponent,
+                suffix,
+            },
+        ),
+    ))
+}
+
+fn parse_decimal(i: Span) -> TokResult {
+    recognize(pair(
+        take_while1(|c: char| c.is_ascii_digit()),
+        take_while(|c: char| c.is_ascii_digit() || c == '_'),
+    ))(i)
+}
+
+fn parse_exponent(oi: Span) -> TokResult {
+    let (i, _) = tag_no_case("e")(oi.clone())?;
+    let (i, exponent) = recognize(pair(opt(is_a("+-")), parse_decimal))(i).map_err(|_| {
+        let len = oi
+            .find(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '+' | '-')))
+            .unwrap_or(oi.len());
+        Err::Failure(TokenError::new(
+            Span::slice(&oi, ..len),
+            "Float exponents must contain at least one digit",
+        ))
+    })?;
+
+    Ok((i, exponent))
+}
+
+fn parse_float_suffix(i: Span) -> TokResult {
+    recognize(pair(tag("f"), alt((tag("32"), tag("64")))))(i)
+}
+
 fn parse_base(i: Span) -> TokResult {
     alt((tag("0x"), tag("0b"), tag("0o")))(i)
 }
@@ -70,3 +149,17 @@ impl PartialEq for Number {
     }
 }
 impl Eq for Number {}
+
+impl PartialEq for Float {
+    fn eq(&self, other: &Self) -> bool {
+        fn frag(span: &Option<Span>) -> Option<&str> {
+            span.as_ref().map(|s| &**s.fragment())
+        }
+
+        *self.integer == *other.integer
+            && frag(&self.fraction) == frag(&other.fraction)
+            && frag(&self.exponent) == frag(&other.exponent)
+            && frag(&self.suffix) == frag(&other.suffix)
+    }
+}
+impl Eq for Float {}
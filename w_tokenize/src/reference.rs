@@ -0,0 +1,333 @@
+//! The original tokenizer built from a cascade of nom parsers.
+//!
+//! It produces the same tokens as [`crate::tokenize`] and is only kept around to test the
+//! scanner against and to benchmark it.
+
+use crate::error::{ToTokenError, TokenError, TokenErrorKind};
+use crate::identifier::parse_ident;
+use crate::number::{parse_float, parse_integer};
+use crate::string::{parse_byte_char, parse_byte_string, parse_char, parse_string};
+use crate::{bounded, whitespace, Group, Kind, Span, TokResult, Token};
+use nom::branch::alt;
+use nom::bytes::complete::{is_not, tag, take_while, take_while_m_n};
+use nom::character::complete::char;
+use nom::combinator::{map, not, opt};
+use nom::multi::{fold_many0, many0};
+use nom::sequence::{delimited, pair, terminated};
+use nom::{Err, Offset, Slice};
+
+pub fn tokenize(mut i: Span) -> TokResult<Vec<Token>> {
+    let mut tokens = vec![];
+    loop {
+        (i, _) = trivia(i)?;
+        if i.is_empty() {
+            break;
+        }
+
+        let (ni, token) = token(i).reason("failed to parse entire file")?;
+        if let Some(token) = token {
+            tokens.push(token);
+        }
+        i = ni;
+    }
+
+    Ok((i, tokens))
+}
+
+/// Skips any whitespace and comments
+fn trivia(i: Span) -> TokResult<()> {
+    let (mut i, _) = whitespace(i)?;
+    loop {
+        let (ni, single) = opt(consume_singleline_comments)(i)?;
+        let (ni, multi) = opt(consume_multiline_comments)(ni)?;
+        i = ni;
+
+        if single.is_none() && multi.is_none() {
+            break;
+        }
+    }
+    Ok((i, ()))
+}
+
+fn token(i: Span) -> TokResult<Option<Token>> {
+    // yeet the whitespaces
+    let (oi, _) = whitespace(i)?;
+
+    let (i, _) = opt(consume_singleline_comments)(oi.clone())?;
+    let (i, _) = opt(consume_multiline_comments)(i)?;
+
+    let comments_pruned = oi.offset(&i) != 0;
+
+    // this had to be done due to limitations with `alt`
+    let res = alt((
+        parse_doc_comment,
+        map(parse_string, |(span, str)| Token {
+            span,
+            kind: Kind::String(str),
+            trivia: None,
+        }),
+        alt((
+            map(parse_char, |(span, c)| Token {
+                span,
+                kind: Kind::Char(c),
+                trivia: None,
+            }),
+            map(parse_byte_char, |(span, b)| Token {
+                span,
+                kind: Kind::Byte(b),
+                trivia: None,
+            }),
+            map(parse_byte_string, |(span, bytes)| Token {
+                span,
+                kind: Kind::ByteString(bytes),
+                trivia: None,
+            }),
+        )),
+        map(parse_float, |(span, num)| Token {
+            span,
+            kind: Kind::Float(Box::new(num)),
+            trivia: None,
+        }),
+        map(parse_integer, |(span, num)| Token {
+            span,
+            kind: Kind::Number(Box::new(num)),
+            trivia: None,
+        }),
+        parse_block,
+        parse_tuple,
+        parse_array,
+        map(parse_ident, |span| Token {
+            span,
+            kind: Kind::Ident,
+            trivia: None,
+        }),
+        // assignment operators
+        op("_", "", || Kind::Fill),
+        op("->", "", || Kind::InlineBlk),
+        alt((
+            op("<<=", "", || Kind::ShlAssign),
+            op(">>=", "", || Kind::ShrAssign),
+            op("+=", "", || Kind::AddAssign),
+            op("-=", "", || Kind::SubAssign),
+            op("*=", "", || Kind::MulAssign),
+            op("/=", "", || Kind::DivAssign),
+            op("%=", "", || Kind::ModAssign),
+            op("&=", "", || Kind::AndAssign),
+            op("|=", "", || Kind::OrAssign),
+            op("^=", "", || Kind::XorAssign),
+        )),
+        // logic operators
+        alt((
+            op("&&", "", || Kind::AndL),
+            op("||", "", || Kind::OrL),
+            op("!", "=", || Kind::Not),
+        )),
+        // bitwise operators
+        alt((
+            op("<<", "", || Kind::Shl),
+            op(">>", "", || Kind::Shr),
+            op("&", "", || Kind::And),
+            op("|", "", || Kind::Or),
+            op("^", "", || Kind::Xor),
+            op("~", "", || Kind::BitNot),
+        )),
+        // math operations
+        alt((
+            op("+", "", || Kind::Add),
+            op("-", "", || Kind::Sub),
+            op("*", "", || Kind::Mul),
+            op("/", "", || Kind::Div),
+            op("%", "", || Kind::Mod),
+        )),
+        // comparison operators
+        alt((
+            op("==", "", || Kind::Eq),
+            op("!=", "", || Kind::Neq),
+            op("<=", "", || Kind::Le),
+            op(">=", "", || Kind::Ge),
+            op("<", "", || Kind::Lt),
+            op(">", "", || Kind::Gt),
+        )),
+        // other
+        alt((
+            op("::", ":=", || Kind::DoubleCol),
+            op(":=", ":=", || Kind::Define),
+            op(":", "=", || Kind::DoubleCol),
+            op(",", "", || Kind::Comma),
+            op("..=", "", || Kind::RangeInclusive),
+            op("..", "", || Kind::Range),
+            op(".", "", || Kind::Dot),
+            op(";", "", || Kind::Semicolon),
+            op("=", "", || Kind::Assign),
+            op("@", "", || Kind::At),
+            op("$", "", || Kind::Dollar),
+        )),
+    ))(i.clone());
+
+    if res.is_ok() {
+        res.map(|(i, tok)| (i, Some(tok)))
+    } else if comments_pruned {
+        Ok((i, None))
+    } else {
+        res.map(|_| unreachable!())
+    }
+}
+
+fn consume_singleline_comments(mut oi: Span) -> TokResult<()> {
+    loop {
+        let (i, _) = not(parse_doc_comment)(oi.clone())?;
+        let (i, _) = tag("//")(i)?;
+        let (i, _) = terminated(many0(is_not("\r\n")), whitespace)(i)?;
+        oi = i;
+        if tag::<_, _, TokenError>("//")(oi.clone()).is_err() {
+            break;
+        }
+    }
+    Ok((oi, ()))
+}
+
+/// `///` or `//!` up to the end of the line, `////` is a regular comment
+fn parse_doc_comment(oi: Span) -> TokResult<Token> {
+    let (i, inner) = alt((
+        map(terminated(tag("///"), not(char('/'))), |_| false),
+        map(tag("//!"), |_| true),
+    ))(oi.clone())?;
+    let (i, text) = take_while(|c| c != '\r' && c != '\n')(i)?;
+
+    let text = text.strip_prefix(' ').unwrap_or(&text).to_string();
+    let kind = if inner {
+        Kind::InnerDocComment(text)
+    } else {
+        Kind::DocComment(text)
+    };
+
+    let offset = oi.offset(&i);
+    Ok((
+        i,
+        Token {
+            span: oi.slice(..offset),
+            kind,
+            trivia: None,
+        },
+    ))
+}
+
+fn consume_multiline_comments(i: Span) -> TokResult<()> {
+    delimited(
+        tag("/*"),
+        fold_many0(
+            alt((
+                consume_multiline_comments,
+                map(pair(not(tag("*/")), take_while_m_n(1, 1, |_| true)), |_| ()),
+            )),
+            || (),
+            |_, _| (),
+        ),
+        pair(tag("*/"), whitespace),
+    )(i)
+}
+
+fn parse_tuple(oi: Span) -> TokResult<Token> {
+    let (i, (span, o)) = parsed_delimited(oi, '(', ')')?;
+
+    Ok((
+        i,
+        Token {
+            span,
+            kind: Kind::Tuple(Group::new(o)),
+            trivia: None,
+        },
+    ))
+}
+
+fn parse_block(oi: Span) -> TokResult<Token> {
+    let (i, (span, o)) = parsed_delimited(oi, '{', '}')?;
+
+    Ok((
+        i,
+        Token {
+            span,
+            kind: Kind::Block(Group::new(o)),
+            trivia: None,
+        },
+    ))
+}
+
+fn parse_array(oi: Span) -> TokResult<Token> {
+    let (i, (span, o)) = parsed_delimited(oi, '[', ']')?;
+
+    Ok((
+        i,
+        Token {
+            span,
+            kind: Kind::Array(Group::new(o)),
+            trivia: None,
+        },
+    ))
+}
+
+fn parsed_delimited(oi: Span, start: char, end: char) -> TokResult<(Span, Vec<Token>)> {
+    let (mut i, _) = pair(char(start), whitespace)(oi.clone())?;
+    let mut acc = vec![];
+
+    let last_err;
+    loop {
+        match token(i.clone()) {
+            Ok((ni, token)) => {
+                if let Some(token) = token {
+                    acc.push(token)
+                };
+                i = ni;
+            }
+            Err(Err::Error(mut err) | Err::Failure(mut err)) => {
+                err.reason = Some(
+                    format!(
+                        "failure to parse at {}:{}",
+                        i.location_line(),
+                        i.location_offset()
+                    )
+                    .into(),
+                );
+                let err = TokenError {
+                    span: oi.clone(),
+                    kind: TokenErrorKind::Other(Box::new(err)),
+                    reason: Some(
+                        "Failed to parse delimited section due to unparseable token inside".into(),
+                    ),
+                };
+                last_err = Some(Err(Err::Failure(err)));
+                break;
+            }
+            err @ Err(_) => {
+                return err.map(|_| unreachable!());
+            }
+        }
+    }
+
+    let (i, end_p) = opt(char(end))(i)?;
+    if end_p.is_none() {
+        if let Some(err) = last_err {
+            err
+        } else {
+            Err(Err::Error(TokenError::new(i, format!("expected `{end}`"))))
+        }
+    } else {
+        let offset = oi.offset(&i);
+        let span = Span::slice(&oi, ..offset);
+        Ok((whitespace(i)?.0, (span, acc)))
+    }
+}
+
+fn op(
+    op: &'static str,
+    bound: &'static str,
+    kind: fn() -> Kind,
+) -> impl FnMut(Span) -> TokResult<Token> {
+    map(bounded(tag(op), move |c| bound.contains(c)), move |o| {
+        Token {
+            span: o,
+            kind: kind(),
+            trivia: None,
+        }
+    })
+}
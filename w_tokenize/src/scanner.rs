@@ -0,0 +1,383 @@
+use crate::error::TokenError;
+use crate::number::{parse_float, parse_integer};
+use crate::string::{parse_byte_char, parse_byte_string, parse_char, parse_string};
+use crate::{Group, Kind, Number, Span, TokResult, Token};
+use nom::{Err, Slice};
+
+/// Single pass tokenizer that decides what to read by looking at the next few bytes.
+///
+/// Punctuation, identifiers, comments and delimiters are read directly from the bytes,
+/// literals are handed to their parsers only once the first bytes settled that one starts.
+/// The input span is only advanced, never re-sliced from the start of the file, as
+/// [`Span`] recounts lines on every slice.
+pub(crate) struct Scanner {
+    rest: Span,
+}
+
+pub(crate) enum Lexeme {
+    Token(Token),
+    /// `(`, `{` or `[`, the span is the rest of the input starting at the delimiter
+    Open(Span, u8),
+    /// `)`, `}` or `]`, the span only covers the delimiter
+    Close(Span, u8),
+}
+
+pub(crate) type ScanResult<T> = Result<T, Err<TokenError>>;
+
+impl Scanner {
+    pub fn new(i: Span) -> Self {
+        Scanner { rest: i }
+    }
+
+    pub fn rest(&self) -> &Span {
+        &self.rest
+    }
+
+    /// Moves the scanner back (or forward) to a span previously taken from [`Scanner::rest`]
+    pub fn reset(&mut self, rest: Span) {
+        self.rest = rest;
+    }
+
+    pub fn advance(&mut self, len: usize) -> Span {
+        let span = self.rest.slice(..len);
+        self.rest = self.rest.slice(len..);
+        span
+    }
+
+    /// Reads the tokens up to the end of the input, nesting groups on the way
+    pub fn tokens(&mut self) -> ScanResult<Vec<Token>> {
+        self.group(None)
+    }
+
+    pub fn group(&mut self, open: Option<(&Span, u8)>) -> ScanResult<Vec<Token>> {
+        let mut acc = vec![];
+
+        loop {
+            self.skip_trivia().map_err(Err::Failure)?;
+
+            match self.next_lexeme()? {
+                Some(Lexeme::Token(token)) => acc.push(token),
+                Some(Lexeme::Open(span, delim)) => {
+                    let inner = self.group(Some((&span, delim)))?;
+                    acc.push(self.finish_group(span, delim, inner));
+                }
+                Some(Lexeme::Close(span, delim)) => match open {
+                    Some((_, open)) if closer(open) == delim => return Ok(acc),
+                    _ => {
+                        return Err(Err::Failure(TokenError::new(
+                            span,
+                            format!("unexpected closing delimiter `{}`", delim as char),
+                        )))
+                    }
+                },
+                None => match open {
+                    Some((span, open)) => {
+                        return Err(Err::Failure(TokenError::new(
+                            span.slice(..1),
+                            format!(
+                                "unclosed delimiter `{}`, expected `{}`",
+                                open as char,
+                                closer(open) as char
+                            ),
+                        )))
+                    }
+                    None => return Ok(acc),
+                },
+            }
+        }
+    }
+
+    /// Builds the token of a group whose closing delimiter was just read
+    pub fn finish_group(&self, open: Span, delim: u8, inner: Vec<Token>) -> Token {
+        let len = self.rest.location_offset() - open.location_offset();
+        let tokens = Group::new(inner);
+
+        Token {
+            span: open.slice(..len),
+            kind: match delim {
+                b'(' => Kind::Tuple(tokens),
+                b'{' => Kind::Block(tokens),
+                b'[' => Kind::Array(tokens),
+                _ => unreachable!(),
+            },
+            trivia: None,
+        }
+    }
+
+    /// Skips whitespace and comments, doc comments are left alone as they are tokens
+    pub fn skip_trivia(&mut self) -> Result<(), TokenError> {
+        let mut len = 0;
+        let bytes = self.rest.as_bytes();
+
+        let res = loop {
+            let rest = &bytes[len..];
+            match rest {
+                [b'/', b'/', ..] if !is_doc_comment(rest) => {
+                    len += rest
+                        .iter()
+                        .position(|b| matches!(b, b'\r' | b'\n'))
+                        .unwrap_or(rest.len());
+                }
+                [b'/', b'*', ..] => match block_comment_len(rest) {
+                    Some(comment) => len += comment,
+                    None => break Err(len),
+                },
+                [b, ..] if b.is_ascii_whitespace() || *b == 0x0b => len += 1,
+                [b, ..] if *b >= 0x80 => match self.rest[len..].chars().next() {
+                    Some(c) if c.is_whitespace() => len += c.len_utf8(),
+                    _ => break Ok(()),
+                },
+                _ => break Ok(()),
+            }
+        };
+
+        if len != 0 {
+            self.advance(len);
+        }
+        res.map_err(|_| TokenError::new(self.rest.slice(..2), "unterminated block comment"))
+    }
+
+    /// Reads the next lexeme, the scanner doesn't move if this fails
+    pub fn next_lexeme(&mut self) -> ScanResult<Option<Lexeme>> {
+        let bytes = self.rest.as_bytes();
+        let (first, second) = match bytes {
+            [] => return Ok(None),
+            [first] => (*first, 0),
+            [first, second, ..] => (*first, *second),
+        };
+
+        let token = match first {
+            b'(' | b'{' | b'[' => {
+                let open = self.rest.clone();
+                self.advance(1);
+                return Ok(Some(Lexeme::Open(open, first)));
+            }
+            b')' | b'}' | b']' => return Ok(Some(Lexeme::Close(self.advance(1), first))),
+
+            b'"' => self.literal(parse_string, Kind::String)?,
+            b'\'' => self.literal(parse_char, Kind::Char)?,
+            b'b' if second == b'\'' => self.literal_or_ident(parse_byte_char, Kind::Byte)?,
+            b'b' if second == b'"' => self.literal_or_ident(parse_byte_string, Kind::ByteString)?,
+            b'r' if matches!(second, b'"' | b'#') => {
+                self.literal_or_ident(parse_string, Kind::String)?
+            }
+
+            b'0'..=b'9' => self.number()?,
+
+            b'/' if is_doc_comment(bytes) => self.doc_comment(),
+
+            b'a'..=b'z' | b'A'..=b'Z' | b'_' => self.ident(),
+            0x80.. if self.rest.chars().next().unwrap().is_alphabetic() => self.ident(),
+
+            _ => self.punctuation()?,
+        };
+
+        Ok(Some(Lexeme::Token(token)))
+    }
+
+    fn literal<O>(
+        &mut self,
+        mut parser: impl FnMut(Span) -> TokResult<(Span, O)>,
+        kind: impl FnOnce(O) -> Kind,
+    ) -> ScanResult<Token> {
+        let (rest, (span, o)) = parser(self.rest.clone())?;
+        self.rest = rest;
+
+        Ok(Token {
+            span,
+            kind: kind(o),
+            trivia: None,
+        })
+    }
+
+    /// `b'`, `b"` and `r#` only start literals if the literal can be read at all
+    fn literal_or_ident<O>(
+        &mut self,
+        parser: impl FnMut(Span) -> TokResult<(Span, O)>,
+        kind: impl FnOnce(O) -> Kind,
+    ) -> ScanResult<Token> {
+        match self.literal(parser, kind) {
+            Err(Err::Error(_)) => Ok(self.ident()),
+            res => res,
+        }
+    }
+
+    fn number(&mut self) -> ScanResult<Token> {
+        let bytes = self.rest.as_bytes();
+        let digits = bytes
+            .iter()
+            .take_while(|b| b.is_ascii_digit() || **b == b'_')
+            .count();
+
+        match bytes.get(digits) {
+            // plain decimal integers make up most literals, they don't need a parser
+            next if next.is_none_or(|b| !(b.is_ascii_alphanumeric() || *b >= 0x80))
+                && next != Some(&b'.') =>
+            {
+                let span = self.advance(digits);
+                Ok(Token {
+                    span: span.clone(),
+                    kind: Kind::Number(Box::new(Number {
+                        number: span,
+                        suffix: None,
+                        base: None,
+                    })),
+                    trivia: None,
+                })
+            }
+            // floats need a fraction, an exponent or a suffix
+            Some(b'.' | b'e' | b'E' | b'f') => {
+                match self.literal(parse_float, |num| Kind::Float(Box::new(num))) {
+                    Err(Err::Error(_)) => self.integer(),
+                    res => res,
+                }
+            }
+            _ => self.integer(),
+        }
+    }
+
+    fn integer(&mut self) -> ScanResult<Token> {
+        self.literal(parse_integer, |num| Kind::Number(Box::new(num)))
+    }
+
+    fn ident(&mut self) -> Token {
+        let len = self
+            .rest
+            .char_indices()
+            .find(|(_, c)| !(c.is_alphanumeric() || *c == '_'))
+            .map_or(self.rest.len(), |(idx, _)| idx);
+
+        let span = self.advance(len);
+        let kind = match *span.fragment() == "_" {
+            true => Kind::Fill,
+            false => Kind::Ident,
+        };
+
+        Token {
+            span,
+            kind,
+            trivia: None,
+        }
+    }
+
+    fn doc_comment(&mut self) -> Token {
+        let len = self.rest.find(['\r', '\n']).unwrap_or(self.rest.len());
+        let span = self.advance(len);
+
+        let text = &span[3..];
+        let text = text.strip_prefix(' ').unwrap_or(text).to_string();
+        let kind = match span.as_bytes()[2] {
+            b'!' => Kind::InnerDocComment(text),
+            _ => Kind::DocComment(text),
+        };
+
+        Token {
+            span,
+            kind,
+            trivia: None,
+        }
+    }
+
+    fn punctuation(&mut self) -> ScanResult<Token> {
+        let bytes = self.rest.as_bytes();
+        let next = |idx: usize| bytes.get(idx).copied().unwrap_or(0);
+        let with_assign = |op, assign| match next(1) {
+            b'=' => (2, assign),
+            _ => (1, op),
+        };
+
+        let (len, kind) = match bytes[0] {
+            b'-' if next(1) == b'>' => (2, Kind::InlineBlk),
+            b'-' => with_assign(Kind::Sub, Kind::SubAssign),
+            b'+' => with_assign(Kind::Add, Kind::AddAssign),
+            b'*' => with_assign(Kind::Mul, Kind::MulAssign),
+            b'/' => with_assign(Kind::Div, Kind::DivAssign),
+            b'%' => with_assign(Kind::Mod, Kind::ModAssign),
+            b'^' => with_assign(Kind::Xor, Kind::XorAssign),
+            b'!' => with_assign(Kind::Not, Kind::Neq),
+            b'~' => (1, Kind::BitNot),
+            b'=' => with_assign(Kind::Assign, Kind::Eq),
+
+            b'&' if next(1) == b'&' => (2, Kind::AndL),
+            b'&' => with_assign(Kind::And, Kind::AndAssign),
+            b'|' if next(1) == b'|' => (2, Kind::OrL),
+            b'|' => with_assign(Kind::Or, Kind::OrAssign),
+
+            b'<' if next(1) == b'<' && next(2) == b'=' => (3, Kind::ShlAssign),
+            b'<' if next(1) == b'<' => (2, Kind::Shl),
+            b'<' => with_assign(Kind::Lt, Kind::Le),
+            b'>' if next(1) == b'>' && next(2) == b'=' => (3, Kind::ShrAssign),
+            b'>' if next(1) == b'>' => (2, Kind::Shr),
+            b'>' => with_assign(Kind::Gt, Kind::Ge),
+
+            // `::` and `:=` may not be followed by another `:` or `=`, a single `:` is
+            // treated as `::` as long as it isn't followed by `=`
+            b':' if next(1) == b':' && !matches!(next(2), b':' | b'=') => (2, Kind::DoubleCol),
+            b':' if next(1) == b'=' && !matches!(next(2), b':' | b'=') => (2, Kind::Define),
+            b':' if next(1) != b'=' => (1, Kind::DoubleCol),
+
+            b'.' if next(1) == b'.' && next(2) == b'=' => (3, Kind::RangeInclusive),
+            b'.' if next(1) == b'.' => (2, Kind::Range),
+            b'.' => (1, Kind::Dot),
+            b',' => (1, Kind::Comma),
+            b';' => (1, Kind::Semicolon),
+            b'@' => (1, Kind::At),
+            b'$' => (1, Kind::Dollar),
+
+            _ => {
+                let len = self.rest.chars().next().map_or(0, char::len_utf8);
+                return Err(Err::Error(TokenError::new(
+                    self.rest.slice(..len),
+                    "Unexpected character",
+                )));
+            }
+        };
+
+        Ok(Token {
+            span: self.advance(len),
+            kind,
+            trivia: None,
+        })
+    }
+}
+
+pub(crate) fn closer(open: u8) -> u8 {
+    match open {
+        b'(' => b')',
+        b'{' => b'}',
+        b'[' => b']',
+        _ => unreachable!(),
+    }
+}
+
+/// `///` but not `////`, or `//!`
+fn is_doc_comment(bytes: &[u8]) -> bool {
+    match bytes {
+        [b'/', b'/', b'/', b'/', ..] => false,
+        [b'/', b'/', b'/' | b'!', ..] => true,
+        _ => false,
+    }
+}
+
+/// Length of a possibly nested block comment, `None` if it never ends
+fn block_comment_len(bytes: &[u8]) -> Option<usize> {
+    let mut depth = 0;
+    let mut idx = 0;
+
+    while idx < bytes.len() {
+        if bytes[idx..].starts_with(b"/*") {
+            depth += 1;
+            idx += 2;
+        } else if bytes[idx..].starts_with(b"*/") {
+            depth -= 1;
+            idx += 2;
+            if depth == 0 {
+                return Some(idx);
+            }
+        } else {
+            idx += 1;
+        }
+    }
+
+    None
+}
@@ -0,0 +1,130 @@
+use crate::{ExpnId, Span};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+use w_rcstr::RcStr;
+
+/// Identifies a file of a [`SourceDb`], every [`Span`] carries the id of its file
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct FileId(u32);
+
+impl FileId {
+    /// Id of sources that aren't part of any [`SourceDb`], like snippets in tests
+    pub const DETACHED: FileId = FileId(u32::MAX);
+}
+
+/// Where a [`Span`] comes from, carried by every span as its extra data
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct SpanCtx {
+    pub file: FileId,
+    /// The macro expansion that produced the token, its span lies in the macro's template
+    pub expansion: Option<ExpnId>,
+}
+
+impl From<FileId> for SpanCtx {
+    fn from(file: FileId) -> Self {
+        SpanCtx {
+            file,
+            expansion: None,
+        }
+    }
+}
+
+pub struct SourceFile {
+    path: PathBuf,
+    text: RcStr,
+    /// Offset of the first byte of every line
+    line_starts: Vec<usize>,
+}
+
+impl SourceFile {
+    fn new(path: PathBuf, text: String) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(idx, _)| idx + 1))
+            .collect();
+
+        SourceFile {
+            path,
+            text: RcStr::new(text),
+            line_starts,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// Line of the byte at `offset`, counting from 1
+    pub fn line(&self, offset: usize) -> usize {
+        self.line_starts.partition_point(|start| *start <= offset)
+    }
+
+    /// Line and column of the byte at `offset`, both counting from 1. Columns count chars.
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let line = self.line(offset);
+        let start = self.line_starts[line - 1];
+        (line, self.text[start..offset].chars().count() + 1)
+    }
+
+    /// Bytes of the line, counting from 1, including its line break
+    pub fn line_range(&self, line: usize) -> Range<usize> {
+        let start = self.line_starts[line - 1];
+        let end = self
+            .line_starts
+            .get(line)
+            .copied()
+            .unwrap_or(self.text.len());
+        start..end
+    }
+}
+
+#[derive(Default)]
+pub struct SourceDb {
+    files: Vec<SourceFile>,
+}
+
+impl SourceDb {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, path: impl Into<PathBuf>, text: String) -> FileId {
+        let id = FileId(self.files.len() as u32);
+        self.files.push(SourceFile::new(path.into(), text));
+        id
+    }
+
+    pub fn load(&mut self, path: impl AsRef<Path>) -> io::Result<FileId> {
+        let text = fs::read_to_string(path.as_ref())?;
+        Ok(self.add(path.as_ref(), text))
+    }
+
+    pub fn get(&self, id: FileId) -> &SourceFile {
+        &self.files[id.0 as usize]
+    }
+
+    /// The entire file, ready to be tokenized
+    pub fn span(&self, id: FileId) -> Span {
+        Span::new_extra(self.get(id).text.clone(), id.into())
+    }
+
+    /// Line and column where `span` starts, see [`SourceFile::line_col`]
+    pub fn line_col(&self, span: &Span) -> (usize, usize) {
+        self.get(span.extra.file).line_col(span.location_offset())
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = (FileId, &SourceFile)> {
+        self.files
+            .iter()
+            .enumerate()
+            .map(|(idx, file)| (FileId(idx as u32), file))
+    }
+}
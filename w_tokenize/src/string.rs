@@ -1,16 +1,133 @@
 use crate::{bounded, Span, ToTokenError, TokResult, TokenError};
 use either::Either;
 use nom::branch::alt;
-use nom::bytes::complete::{tag, take_while1, take_while_m_n};
-use nom::character::complete::char as char_;
-use nom::combinator::value;
+use nom::bytes::complete::{tag, take_while, take_while1, take_while_m_n};
+use nom::character::complete::{anychar, char as char_};
+use nom::combinator::{map, opt, recognize, value, verify};
 use nom::multi::fold_many0;
-use nom::sequence::delimited;
+use nom::sequence::{delimited, pair};
 use nom::{Err, Offset, Slice};
 use w_rcstr::RcStr;
 
 pub fn parse_string(i: Span) -> TokResult<(Span, String)> {
-    bounded(parse_string_inner, |c| c == '"' || c.is_alphanumeric())(i)
+    bounded(
+        alt((parse_multiline_string, parse_raw_string, parse_string_inner)),
+        |c| c == '"' || c == '#' || c.is_alphanumeric(),
+    )(i)
+}
+
+/// `r"..."` or `r#"..."#` with any amount of `#`, no escapes are processed
+fn parse_raw_string(oi: Span) -> TokResult<(Span, String)> {
+    let (i, _) = char_('r')(oi.clone())?;
+    let (i, hashes) = take_while(|c| c == '#')(i)?;
+    let (i, _) = char_('"')(i)?;
+
+    let closing = format!("\"{}", &**hashes.fragment());
+    let len = i.find(closing.as_str()).ok_or_else(|| {
+        let offset = oi.offset(&i);
+        Err::Failure(TokenError::new(
+            Span::slice(&oi, ..offset),
+            format!("Unterminated raw string, expected `{closing}`"),
+        ))
+    })?;
+
+    let content = i.fragment()[..len].to_string();
+    let i = Span::slice(&i, len + closing.len()..);
+
+    let offset = oi.offset(&i);
+    Ok((i, (Span::slice(&oi, ..offset), content)))
+}
+
+/// A string starting with `"""` on its own line and ending with `"""` on its own line.
+///
+/// The indentation in front of the closing `"""` is stripped from every line, each line that
+/// isn't blank has to start with it.
+fn parse_multiline_string(oi: Span) -> TokResult<(Span, String)> {
+    let (i, open) = tag("\"\"\"")(oi.clone())?;
+    let unterminated = || {
+        Err::Failure(TokenError::new(
+            open.clone(),
+            "Unterminated multi-line string, expected `\"\"\"` on its own line",
+        ))
+    };
+
+    let (i, rest) = take_while(|c| c != '\n')(i)?;
+    if !rest.trim().is_empty() {
+        return Err(Err::Failure(TokenError::new(
+            rest,
+            "Multi-line strings must start on a new line after the opening `\"\"\"`",
+        )));
+    }
+    if i.is_empty() {
+        return Err(unterminated());
+    }
+    let mut i = Span::slice(&i, 1..);
+
+    let mut lines = vec![];
+    let (i, indent) = loop {
+        if i.is_empty() {
+            return Err(unterminated());
+        }
+
+        let (ni, line) = take_while(|c| c != '\n')(i.clone())?;
+        let indent = line
+            .find(|c: char| !c.is_whitespace())
+            .unwrap_or(line.len());
+        if line[indent..].starts_with("\"\"\"") {
+            break (Span::slice(&i, indent + 3..), Span::slice(&line, ..indent));
+        }
+
+        lines.push(line);
+        i = if ni.is_empty() {
+            ni
+        } else {
+            Span::slice(&ni, 1..)
+        };
+    };
+
+    let mut content = String::new();
+    for (idx, line) in lines.into_iter().enumerate() {
+        if idx != 0 {
+            content.push('\n');
+        }
+
+        let line = Span::slice(&line, ..line.trim_end_matches('\r').len());
+        if line.trim().is_empty() {
+            continue;
+        }
+        if !line.starts_with(&**indent.fragment()) {
+            return Err(Err::Failure(TokenError::new(
+                line,
+                "Line is indented less than the closing `\"\"\"`",
+            )));
+        }
+
+        unescape_into(Span::slice(&line, indent.len()..), &mut content)?;
+    }
+
+    let offset = oi.offset(&i);
+    Ok((i, (Span::slice(&oi, ..offset), content)))
+}
+
+fn unescape_into(mut i: Span, out: &mut String) -> TokResult<()> {
+    while !i.is_empty() {
+        if i.starts_with('\\') {
+            let (ni, c) = parse_escape(i.clone()).map_err(|_| {
+                let len = i.chars().take(2).map(char::len_utf8).sum::<usize>();
+                Err::Failure(TokenError::new(
+                    Span::slice(&i, ..len),
+                    "Invalid escape sequence",
+                ))
+            })?;
+            out.push(c);
+            i = ni;
+        } else {
+            let (ni, s) = take_while1(|c: char| c != '\\')(i)?;
+            out.push_str(&s);
+            i = ni;
+        }
+    }
+    Ok((i, ()))
 }
 
 fn parse_string_inner(i: Span) -> TokResult<(Span, String)> {
@@ -18,7 +135,7 @@ fn parse_string_inner(i: Span) -> TokResult<(Span, String)> {
         char_('"'),
         fold_many0(parse_partial, String::new, |mut str, frag| {
             match frag {
-                Either::Left(s) => str.push_str(&*s),
+                Either::Left(s) => str.push_str(&s),
                 Either::Right(c) => str.push(c),
             }
             str
@@ -29,8 +146,8 @@ fn parse_string_inner(i: Span) -> TokResult<(Span, String)> {
     Ok((ni, (Span::slice(&i, ..span_end), o)))
 }
 
-fn parse_partial<'a>(i: Span) -> TokResult<Either<RcStr, char>> {
-    if i.len() == 0 {
+fn parse_partial(i: Span) -> TokResult<Either<RcStr, char>> {
+    if i.is_empty() {
         return Err(Err::Failure(TokenError::new(i, "Empty string segment")));
     }
     match i.chars().next().unwrap() {
@@ -44,10 +161,136 @@ fn parse_partial<'a>(i: Span) -> TokResult<Either<RcStr, char>> {
     }
 }
 
+pub fn parse_char(i: Span) -> TokResult<(Span, char)> {
+    bounded(parse_char_inner, |c| c == '\'' || c.is_alphanumeric())(i)
+}
+
+fn parse_char_inner(oi: Span) -> TokResult<(Span, char)> {
+    if oi.starts_with("''") {
+        return Err(Err::Failure(TokenError::new(
+            Span::slice(&oi, ..2),
+            "Empty character literal",
+        )));
+    }
+
+    let (i, _) = char_('\'')(oi.clone())?;
+    let (i, c) = alt((
+        parse_escape,
+        verify(anychar, |c| !matches!(c, '\'' | '\\' | '\r' | '\n')),
+    ))(i)?;
+    let (i, _) = char_('\'')(i)?;
+
+    let offset = oi.offset(&i);
+    Ok((i, (Span::slice(&oi, ..offset), c)))
+}
+
+pub fn parse_byte_char(i: Span) -> TokResult<(Span, u8)> {
+    bounded(parse_byte_char_inner, |c| c == '\'' || c.is_alphanumeric())(i)
+}
+
+fn parse_byte_char_inner(oi: Span) -> TokResult<(Span, u8)> {
+    let (i, _) = tag("b'")(oi.clone())?;
+    let (i, byte) = alt((parse_byte_escape, parse_ascii_byte))(i).map_err(|err| match err {
+        Err::Error(_) => Err::Failure(TokenError::new(
+            Span::slice(&oi, ..2),
+            "Byte literals must contain exactly one byte",
+        )),
+        err => err,
+    })?;
+    let (i, _) = char_('\'')(i.clone()).map_err(|_: Err<TokenError>| {
+        let offset = oi.offset(&i);
+        Err::Failure(TokenError::new(
+            Span::slice(&oi, ..offset),
+            "Byte literals must contain exactly one byte",
+        ))
+    })?;
+
+    let offset = oi.offset(&i);
+    Ok((i, (Span::slice(&oi, ..offset), byte)))
+}
+
+pub fn parse_byte_string(i: Span) -> TokResult<(Span, Vec<u8>)> {
+    bounded(parse_byte_string_inner, |c| c == '"' || c.is_alphanumeric())(i)
+}
+
+fn parse_byte_string_inner(oi: Span) -> TokResult<(Span, Vec<u8>)> {
+    let (i, _) = tag("b\"")(oi.clone())?;
+    let (i, o) = fold_many0(parse_byte_partial, Vec::new, |mut acc, frag| {
+        match frag {
+            Either::Left(s) => acc.extend_from_slice(s.as_bytes()),
+            Either::Right(b) => acc.push(b),
+        }
+        acc
+    })(i)?;
+    let (i, _) = char_('"')(i)?;
+
+    let offset = oi.offset(&i);
+    Ok((i, (Span::slice(&oi, ..offset), o)))
+}
+
+fn parse_byte_partial(i: Span) -> TokResult<Either<RcStr, u8>> {
+    if i.is_empty() {
+        return Err(Err::Failure(TokenError::new(i, "Empty string segment")));
+    }
+    match i.chars().next().unwrap() {
+        '"' => Err(Err::Error(TokenError::new(
+            Span::slice(&i, ..1),
+            "End of string",
+        ))),
+        '\\' => parse_byte_escape(i).map(|(s, b)| (s, Either::Right(b))),
+        _ => {
+            let (ni, s) = take_while1(|c: char| c != '"' && c != '\\')(i)?;
+            if let Some((idx, c)) = s.char_indices().find(|(_, c)| !c.is_ascii()) {
+                return Err(Err::Failure(TokenError::new(
+                    Span::slice(&s, idx..idx + c.len_utf8()),
+                    "Non-ASCII character in byte string, use a \\x escape instead",
+                )));
+            }
+            Ok((ni, Either::Left(s.fragment().clone())))
+        }
+    }
+}
+
+fn parse_ascii_byte(i: Span) -> TokResult<u8> {
+    let (ni, c) = verify(anychar, |c| !matches!(c, '\'' | '\\' | '\r' | '\n'))(i.clone())?;
+    if !c.is_ascii() {
+        return Err(Err::Failure(TokenError::new(
+            Span::slice(&i, ..c.len_utf8()),
+            "Non-ASCII character in byte literal, use a \\x escape instead",
+        )));
+    }
+    Ok((ni, c as u8))
+}
+
 fn parse_escape(i: Span) -> TokResult<char> {
+    alt((parse_byte, parse_unicode, parse_simple_escape))(i)
+}
+
+/// Escapes allowed in byte literals, `\x` may use the entire byte range
+fn parse_byte_escape(i: Span) -> TokResult<u8> {
+    if i.starts_with("\\u") {
+        let (_, span) = recognize(pair(
+            tag("\\u"),
+            opt(delimited(
+                tag("{"),
+                take_while(|c: char| c.is_ascii_hexdigit()),
+                tag("}"),
+            )),
+        ))(i)?;
+        return Err(Err::Failure(TokenError::new(
+            span,
+            "Unicode escapes are not allowed in byte literals",
+        )));
+    }
+
+    alt((
+        map(parse_hex_escape, |(_, val)| val),
+        map(parse_simple_escape, |c| c as u8),
+    ))(i)
+}
+
+fn parse_simple_escape(i: Span) -> TokResult<char> {
     alt((
-        parse_byte,
-        parse_unicode,
         value('\n', tag("\\n")),
         value('\r', tag("\\r")),
         value('\t', tag("\\t")),
@@ -69,7 +312,7 @@ fn parse_unicode(oi: Span) -> TokResult<char> {
     let offset = oi.offset(&i);
     let si = Span::slice(&oi, ..offset);
 
-    let val = u32::from_str_radix(&**hex, 16).unwrap();
+    let val = u32::from_str_radix(&hex, 16).unwrap();
     let val =
         char::try_from(val).map_err(|err| Err::Failure(TokenError::new(si, err.to_string())))?;
 
@@ -77,15 +320,7 @@ fn parse_unicode(oi: Span) -> TokResult<char> {
 }
 
 fn parse_byte(oi: Span) -> TokResult<char> {
-    let (i, _) = tag("\\")(oi.clone())?;
-    let (i, _) = tag("x")(i)?;
-    let (i, hex) = take_while_m_n(2, 2, |c: char| c.is_ascii_hexdigit())(i)
-        .reason("\\x must be followed by 2 hex characters")?;
-
-    let offset = oi.offset(&i);
-    let si = Span::slice(&oi, ..offset);
-
-    let val = u8::from_str_radix(&hex, 16).unwrap();
+    let (i, (si, val)) = parse_hex_escape(oi)?;
     if val >= 0x80 {
         return Err(Err::Failure(TokenError::new(
             si,
@@ -96,6 +331,18 @@ fn parse_byte(oi: Span) -> TokResult<char> {
     Ok((i, char::from(val)))
 }
 
+fn parse_hex_escape(oi: Span) -> TokResult<(Span, u8)> {
+    let (i, _) = tag("\\")(oi.clone())?;
+    let (i, _) = tag("x")(i)?;
+    let (i, hex) = take_while_m_n(2, 2, |c: char| c.is_ascii_hexdigit())(i)
+        .reason("\\x must be followed by 2 hex characters")?;
+
+    let offset = oi.offset(&i);
+    let si = Span::slice(&oi, ..offset);
+
+    Ok((i, (si, u8::from_str_radix(&hex, 16).unwrap())))
+}
+
 // #[cfg(test)]
 // mod tests {
 //     use super::*;
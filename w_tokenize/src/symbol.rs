@@ -0,0 +1,91 @@
+use crate::ExpnId;
+use std::collections::HashMap;
+use std::fmt::{Debug, Display, Formatter};
+use std::sync::{LazyLock, RwLock};
+
+/// Interned identifier text, comparing and hashing symbols never looks at the text.
+///
+/// Symbols are global and live as long as the program, the same text always yields the
+/// same symbol. Their text is leaked on purpose, there are only as many distinct names as
+/// the sources spell out and [`Symbol::as_str`] can hand out `&'static str` without holding
+/// on to the interner.
+///
+/// Looking up a name that was interned before, which is what almost every identifier does,
+/// only takes a read lock, so modules parsed in parallel don't wait on each other.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct Symbol(u32);
+
+#[derive(Default)]
+struct Interner {
+    symbols: HashMap<&'static str, Symbol>,
+    /// Keyed by the id alone, the interner doesn't keep expansions alive
+    hygienic: HashMap<(Symbol, u32), Symbol>,
+    strings: Vec<&'static str>,
+}
+
+static INTERNER: LazyLock<RwLock<Interner>> = LazyLock::new(Default::default);
+
+impl Symbol {
+    pub fn intern(text: &str) -> Symbol {
+        if let Some(symbol) = INTERNER.read().unwrap().symbols.get(text) {
+            return *symbol;
+        }
+
+        let mut interner = INTERNER.write().unwrap();
+        // another thread may have interned it between the two locks
+        if let Some(symbol) = interner.symbols.get(text) {
+            return *symbol;
+        }
+
+        let symbol = Symbol(interner.strings.len() as u32);
+        let text: &'static str = Box::leak(text.into());
+        interner.strings.push(text);
+        interner.symbols.insert(text, symbol);
+        symbol
+    }
+
+    /// A symbol with the same text that only equals itself within the macro expansion
+    pub fn hygienic(self, expn: &ExpnId) -> Symbol {
+        if let Some(symbol) = INTERNER.read().unwrap().hygienic.get(&(self, expn.id())) {
+            return *symbol;
+        }
+
+        let mut interner = INTERNER.write().unwrap();
+        if let Some(symbol) = interner.hygienic.get(&(self, expn.id())) {
+            return *symbol;
+        }
+
+        let symbol = Symbol(interner.strings.len() as u32);
+        let text = interner.strings[self.0 as usize];
+        interner.strings.push(text);
+        interner.hygienic.insert((self, expn.id()), symbol);
+        symbol
+    }
+
+    /// A symbol with the same text that equals no other symbol, for names the compiler
+    /// introduces on its own
+    pub fn fresh(self) -> Symbol {
+        let mut interner = INTERNER.write().unwrap();
+
+        let symbol = Symbol(interner.strings.len() as u32);
+        let text = interner.strings[self.0 as usize];
+        interner.strings.push(text);
+        symbol
+    }
+
+    pub fn as_str(self) -> &'static str {
+        INTERNER.read().unwrap().strings[self.0 as usize]
+    }
+}
+
+impl Debug for Symbol {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl Display for Symbol {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
@@ -1,11 +1,467 @@
-use crate::{tokenize, Span};
+use crate::{
+    items, reconstruct, reference, retokenize, tokenize, tokenize_lossless, tokenize_recovering,
+    FileId, Group, Kind, SourceDb, Span, Symbol, TextEdit, Token, TriviaPiece,
+};
 use w_rcstr::RcStr;
 
+fn source(src: impl Into<String>) -> Span {
+    Span::new_extra(RcStr::new(src.into()), FileId::DETACHED.into())
+}
+
 #[test]
 fn parses() {
-    let span = Span::new(RcStr::new(
-        include_str!("../../WIP_tests/old_1/generics1.w").to_string(),
-    ));
+    let span = source(include_str!("../../WIP_tests/old_1/generics1.w"));
     let (_, tokens) = tokenize(span).unwrap();
     println!("{:#?}", tokens);
 }
+
+#[test]
+fn recovers_from_bad_tokens() {
+    let span = source("rc(T) :: #(no_copy) struct { inner ? *T, ( ]");
+    let (tokens, errs) = tokenize_recovering(span);
+
+    let kinds = tokens.iter().map(|tk| tk.kind.cmp_id()).collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        [
+            Kind::Ident.cmp_id(),
+            Kind::Tuple(Group::default()).cmp_id(),
+            Kind::DoubleCol.cmp_id(),
+            Kind::Error.cmp_id(),
+            Kind::Tuple(Group::default()).cmp_id(),
+            Kind::Ident.cmp_id(),
+            Kind::Block(Group::default()).cmp_id(),
+        ]
+    );
+
+    let block = match &tokens[6].kind {
+        Kind::Block(inner) => inner.tokens(),
+        _ => unreachable!(),
+    };
+    assert!(matches!(block[1].kind, Kind::Error));
+    assert!(matches!(block.last().unwrap().kind, Kind::Tuple(_)));
+
+    // `#`, `?`, the stray `]`, the unclosed `(` and the unclosed `{`
+    assert_eq!(errs.len(), 5);
+    assert_eq!(*errs[0].span.fragment(), "#");
+}
+
+#[test]
+fn lossless_roundtrip() {
+    let src = include_str!("../../WIP_tests/mod_test/vec.w");
+    let span = source(src);
+    let (_, (tokens, eof)) = tokenize_lossless(span).unwrap();
+
+    assert_eq!(reconstruct(&tokens, &eof), src);
+
+    // the regular comment in front of `vec(T(disposable)):dispose` is leading trivia of `vec`
+    let dispose = tokens
+        .iter()
+        .position(|tk| *tk.span.fragment() == "dispose")
+        .unwrap();
+    let trivia = tokens[dispose - 3].trivia.as_ref().unwrap();
+    let pieces = TriviaPiece::split(&trivia.leading);
+    assert!(pieces
+        .iter()
+        .any(|p| matches!(p, TriviaPiece::LineComment(s) if s.starts_with("// experimental"))));
+}
+
+#[test]
+fn trivia_split_stops_at_other_text() {
+    let pieces = TriviaPiece::split(&source("  // a\n x /* b */"));
+    assert_eq!(pieces.len(), 3);
+    assert!(matches!(&pieces[2], TriviaPiece::Whitespace(s) if **s.fragment() == *"\n "));
+}
+
+#[test]
+fn floats() {
+    let span = source("1.5 2e10 1_000.25E-3f32 3f64 0..5 0x1e3");
+    let (_, tokens) = tokenize(span).unwrap();
+
+    let floats = tokens
+        .iter()
+        .filter_map(|tk| match &tk.kind {
+            Kind::Float(f) => Some(f),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(floats.len(), 4);
+    assert_eq!(*floats[2].integer.fragment(), "1_000");
+    assert_eq!(floats[2].fraction.as_ref().unwrap().fragment(), &"25");
+    assert_eq!(floats[2].exponent.as_ref().unwrap().fragment(), &"-3");
+    assert_eq!(floats[3].suffix.as_ref().unwrap().fragment(), &"f64");
+
+    // `0..5` stays a range of integers and hex numbers never turn into floats
+    assert!(matches!(tokens[4].kind, Kind::Number(_)));
+    assert!(matches!(tokens[5].kind, Kind::Range));
+    assert!(matches!(tokens[6].kind, Kind::Number(_)));
+    assert!(matches!(tokens[7].kind, Kind::Number(_)));
+}
+
+#[test]
+fn literals_are_unsigned() {
+    let kinds = |src: &str| {
+        let (_, tokens) = tokenize(source(src)).unwrap();
+        tokens.iter().map(|tk| tk.kind.name()).collect::<Vec<_>>()
+    };
+
+    // the sign is always its own token, the parser decides whether it negates or subtracts
+    assert_eq!(kinds("x-1"), ["Ident", "Sub", "Number"]);
+    assert_eq!(kinds("x - 1"), ["Ident", "Sub", "Number"]);
+    assert_eq!(kinds("-1.5"), ["Sub", "Float"]);
+    assert_eq!(kinds("+-2"), ["Add", "Sub", "Number"]);
+    assert_eq!(kinds("~x"), ["BitNot", "Ident"]);
+}
+
+#[test]
+fn invalid_floats() {
+    for (src, bad) in [("1.5u8", "u8"), ("2.0f16", "f16"), ("1e+ ", "e+")] {
+        let (tokens, errs) = tokenize_recovering(source(src));
+        assert_eq!(tokens.len(), 1);
+        assert!(matches!(tokens[0].kind, Kind::Error));
+        assert_eq!(*errs[0].span.fragment(), bad);
+    }
+}
+
+#[test]
+fn chars_and_bytes() {
+    let span = source(r#"'a' '\n' '\u{1f308}' b'a' b'\xff' b"GET \x80\r\n" b"#);
+    let (_, tokens) = tokenize(span).unwrap();
+
+    let kinds = tokens.iter().map(|tk| tk.kind.clone()).collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        [
+            Kind::Char('a'),
+            Kind::Char('\n'),
+            Kind::Char('🌈'),
+            Kind::Byte(b'a'),
+            Kind::Byte(0xff),
+            Kind::ByteString(b"GET \x80\r\n".to_vec()),
+            Kind::Ident,
+        ]
+    );
+}
+
+#[test]
+fn unicode_in_byte_literals() {
+    for (src, bad) in [
+        (r"b'\u{41}'", r"\u{41}"),
+        (r#"b"ab\u{41}""#, r"\u{41}"),
+        (r#"b"größe""#, "ö"),
+        ("b'é'", "é"),
+    ] {
+        let (tokens, errs) = tokenize_recovering(source(src));
+        assert_eq!(tokens.len(), 1, "{src}: {tokens:?}");
+        assert_eq!(*errs[0].span.fragment(), bad);
+    }
+}
+
+#[test]
+fn raw_strings() {
+    let span = source(r###"r"C:\path" r#"say "hi""# r##"a "# b"## r"""###);
+    let (_, tokens) = tokenize(span).unwrap();
+
+    let kinds = tokens.iter().map(|tk| tk.kind.clone()).collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        [
+            Kind::String(r"C:\path".to_string()),
+            Kind::String(r#"say "hi""#.to_string()),
+            Kind::String(r##"a "# b"##.to_string()),
+            Kind::String(String::new()),
+        ]
+    );
+
+    let src = r##"r#"never closed" "##;
+    let (_, errs) = tokenize_recovering(source(src));
+    assert_eq!(*errs[0].span.fragment(), "r#\"");
+}
+
+#[test]
+fn multiline_strings() {
+    let src = "x := \"\"\"\n    first\n      second\\t\n\n    last\n    \"\"\"\n";
+    let (_, tokens) = tokenize(source(src)).unwrap();
+    assert_eq!(
+        tokens[2].kind,
+        Kind::String("first\n  second\t\n\nlast".to_string())
+    );
+
+    let src = "\"\"\"\n    fine\n  too little\n    \"\"\"";
+    let (_, errs) = tokenize_recovering(source(src));
+    let err = &errs[0];
+    assert_eq!(*err.span.fragment(), "  too little");
+    assert_eq!(err.span.location_line(), 3);
+
+    let src = "\"\"\"\n    never closed\n";
+    let (_, errs) = tokenize_recovering(source(src));
+    assert_eq!(*errs[0].span.fragment(), "\"\"\"");
+}
+
+#[test]
+fn doc_comments() {
+    let src = "//! module\n/// first\n///second\r\n//// plain\n// plain\nx :: int;";
+    let (_, tokens) = tokenize(source(src)).unwrap();
+
+    let kinds = tokens.iter().map(|tk| tk.kind.clone()).collect::<Vec<_>>();
+    assert_eq!(
+        kinds[..3],
+        [
+            Kind::InnerDocComment("module".to_string()),
+            Kind::DocComment("first".to_string()),
+            Kind::DocComment("second".to_string()),
+        ]
+    );
+    assert_eq!(*tokens[2].span.fragment(), "///second");
+    assert_eq!(kinds[3], Kind::Ident);
+}
+
+#[test]
+fn ranges() {
+    let span = source("0..5 0.._ a..=b [..] 1.5..2");
+    let (_, tokens) = tokenize(span).unwrap();
+
+    let spans = tokens
+        .iter()
+        .map(|tk| &**tk.span.fragment())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        spans,
+        ["0", "..", "5", "0", "..", "_", "a", "..=", "b", "[..]", "1.5", "..", "2"]
+    );
+    assert_eq!(tokens[1].kind, Kind::Range);
+    assert_eq!(tokens[7].kind, Kind::RangeInclusive);
+}
+
+fn assert_same_tokens(src: &str, left: &[Token], right: &[Token]) {
+    assert_eq!(left.len(), right.len(), "{src}");
+    for (l, r) in left.iter().zip(right) {
+        assert_eq!(l.span.location_offset(), r.span.location_offset(), "{src}");
+        assert_eq!(l.span.location_line(), r.span.location_line(), "{src}");
+        assert_eq!(*l.span.fragment(), *r.span.fragment(), "{src}");
+
+        match (&l.kind, &r.kind) {
+            (Kind::Tuple(l), Kind::Tuple(r))
+            | (Kind::Block(l), Kind::Block(r))
+            | (Kind::Array(l), Kind::Array(r)) => assert_same_tokens(src, &l.tokens(), &r.tokens()),
+            (l, r) => assert_eq!(l, r, "{src}"),
+        }
+    }
+}
+
+#[test]
+fn scanner_matches_reference() {
+    let sources = [
+        include_str!("../../WIP_tests/types.w"),
+        include_str!("../../WIP_tests/cat.w"),
+        include_str!("../../WIP_tests/test1.w"),
+        include_str!("../../WIP_tests/mod_test/vec.w"),
+        include_str!("../../WIP_tests/mod_test/iter.w"),
+        include_str!("../../WIP_tests/mod_test/trait.w"),
+        include_str!("../../WIP_tests/mod_test/utf8_chk.w"),
+        include_str!("../../WIP_tests/mod_test/bubble_sort.w"),
+        include_str!("../../WIP_tests/mod_test/main.w"),
+        include_str!("../../WIP_tests/bigfail.w"),
+        include_str!("../../WIP_tests/type_fail.w"),
+        include_str!("../../WIP_tests/old_1/array.w"),
+        include_str!("../../WIP_tests/old_1/enum1.w"),
+        include_str!("../../WIP_tests/old_1/generics1.w"),
+        include_str!("../../WIP_tests/old_1/heap1.w"),
+        include_str!("../../WIP_tests/old_1/pointers1.w"),
+        include_str!("../../WIP_tests/old_1/struct1.w"),
+        include_str!("../../WIP_tests/old_1/test1.w"),
+        include_str!("../../WIP_tests/old_1/trait.w"),
+        include_str!("../../WIP_tests/old_1/typed1.w"),
+        "a->b <<= >>= += -= *= /= %= &= |= ^= && || ! ~ << >> & | ^ + - * / % == != <= >= < > \
+         :: := : , ..= .. . ; = @ $ _ _a a_1 x-1 x - -1 +-2 1.5e3f32 0x1f 'c' b'c' b\"b\" \
+         r#\"raw\"# \"s\" /* a /* nested */ comment */ //! inner\n/// outer\n//// not\n\
+         \u{e9}t\u{e9} ({[]})",
+    ];
+
+    for src in sources {
+        let span = source(src);
+        let reference = reference::tokenize(span.clone()).map(|(_, tokens)| tokens);
+        let scanned = tokenize(span).map(|(_, tokens)| tokens);
+
+        match (reference, scanned) {
+            (Ok(reference), Ok(scanned)) => assert_same_tokens(src, &reference, &scanned),
+            (Err(_), Err(_)) => {}
+            (reference, scanned) => panic!("{src}\n{reference:?}\n{scanned:?}"),
+        }
+    }
+}
+
+fn assert_same_columns(src: &str, left: &[Token], right: &[Token]) {
+    for (l, r) in left.iter().zip(right) {
+        assert_eq!(l.span.get_utf8_column(), r.span.get_utf8_column(), "{src}");
+
+        if let (
+            Kind::Tuple(l) | Kind::Block(l) | Kind::Array(l),
+            Kind::Tuple(r) | Kind::Block(r) | Kind::Array(r),
+        ) = (&l.kind, &r.kind)
+        {
+            assert_same_columns(src, &l.tokens(), &r.tokens());
+        }
+    }
+}
+
+#[test]
+fn retokenize_matches_tokenize() {
+    let src = "vec(T) :: struct {\n    /// items\n    ptr *T, len 0x10,\n}\n\n\
+               push :: func(self *vec(T), x T) {\n    /* grow \n */ self.len += 1;\n    \
+               [a, \"s\", 1.5e3](x - -2) -> 'c'\n}\n";
+    let (_, old) = tokenize(source(src)).unwrap();
+
+    let edits = [
+        "", "x", " ", "\n", "(", ")", "}", "1", "/*", "*/", "\"", "//", "a\nb {}",
+    ];
+    for start in 0..=src.len() {
+        for end in [start, start + 1, start + 4] {
+            if end > src.len() || !src.is_char_boundary(end) {
+                continue;
+            }
+
+            for text in edits {
+                let edit = TextEdit::new(start..end, text);
+                let new = edit.apply(src);
+                let case = format!("{:?} -> {:?}", &src[start..end], text);
+
+                let full = tokenize(source(new.clone()));
+                let incremental = retokenize(&old, source(new.clone()), &edit);
+
+                match (full, incremental) {
+                    (Ok((_, full)), Ok((_, incremental))) => {
+                        assert_same_tokens(&case, &full, &incremental.tokens);
+                        assert_same_columns(&case, &full, &incremental.tokens);
+                    }
+                    (Err(_), Err(_)) => {}
+                    (full, incremental) => panic!(
+                        "{case} at {start}: {:?} vs {:?}",
+                        full.map(|_| ()),
+                        incremental.map(|_| ())
+                    ),
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn retokenize_shares_unchanged_groups() {
+    let src = "a :: func() { x := 1; }\nb :: func() { y := 2; }\nc :: func() { z := 3; }\n";
+    let (_, old) = tokenize(source(src)).unwrap();
+
+    let pos = src.find("y").unwrap();
+    let edit = TextEdit::new(pos..pos + 1, "yy");
+    let new = source(edit.apply(src));
+    let (_, res) = retokenize(&old, new, &edit).unwrap();
+
+    let (Kind::Block(before), Kind::Block(after)) = (&old[4].kind, &res.tokens[4].kind) else {
+        panic!("expected the body of `a`");
+    };
+    assert!(before.shares(after));
+
+    // only `b` has to be parsed again
+    assert_eq!(items(&res.tokens), items(&old));
+    assert_eq!(res.replaced, 1..2);
+    assert_eq!(res.touched, 1..2);
+
+    // the body of `c` moved without being copied
+    let (Kind::Block(before), Kind::Block(after)) = (&old[14].kind, &res.tokens[14].kind) else {
+        panic!("expected the body of `c`");
+    };
+    assert!(before.shares(after));
+    assert_eq!(*res.tokens[14].span.fragment(), "{ z := 3; }");
+    assert_eq!(
+        res.tokens[14].span.location_offset(),
+        old[14].span.location_offset() + 1
+    );
+
+    let body = after.tokens();
+    assert_eq!(*body[0].span.fragment(), "z");
+    assert_eq!(
+        body[0].span.location_offset(),
+        before.tokens()[0].span.location_offset() + 1
+    );
+    assert_eq!(body[0].span.location_line(), 3);
+}
+
+#[test]
+fn splits_items() {
+    let src = "a :: b:c\n/// docs\niter(T) :: struct {}\niter(T):next :: d(T)\n\
+               @(x) e :: f;\n{ g } :: h";
+    let (_, tokens) = tokenize(source(src)).unwrap();
+
+    let split: Vec<_> = items(&tokens)
+        .into_iter()
+        .map(|range| {
+            let tokens = &tokens[range];
+            let (first, last) = (&tokens[0].span, &tokens[tokens.len() - 1].span);
+            &src[first.location_offset()..last.location_offset() + last.len()]
+        })
+        .collect();
+    assert_eq!(
+        split,
+        [
+            "a :: b:c",
+            "/// docs\niter(T) :: struct {}",
+            "iter(T):next :: d(T)",
+            "@(x) e :: f;",
+            "{ g } :: h"
+        ]
+    );
+}
+
+#[test]
+fn source_db() {
+    let mut db = SourceDb::new();
+    let main = db.add("main.w", "main :: func() {\n    x := 1;\n}\n".to_string());
+    let other = db.add("other.w", "// föö\nx :: ä;".to_string());
+
+    let (_, tokens) = tokenize(db.span(other)).unwrap();
+    assert!(tokens.iter().all(|tk| tk.span.extra.file == other));
+    assert_eq!(db.line_col(&tokens[2].span), (2, 6));
+    assert_eq!(db.get(other).path().to_str(), Some("other.w"));
+
+    let (_, tokens) = tokenize(db.span(main)).unwrap();
+    let Kind::Block(body) = &tokens[4].kind else {
+        panic!("expected the body of `main`");
+    };
+    let body = body.tokens();
+    assert_eq!(body[0].span.extra.file, main);
+    assert_eq!(db.line_col(&body[0].span), (2, 5));
+
+    let file = db.get(main);
+    assert_eq!(file.line_count(), 4);
+    assert_eq!(&file.text()[file.line_range(2)], "    x := 1;\n");
+}
+
+#[test]
+fn symbols() {
+    let (_, tokens) = tokenize(source("vec :: vec(vec_t)")).unwrap();
+    let first = Symbol::intern(&tokens[0].span);
+    let Kind::Tuple(args) = &tokens[3].kind else {
+        panic!("expected the arguments of `vec`");
+    };
+    let args = args.tokens();
+
+    assert_eq!(first, Symbol::intern(&tokens[2].span));
+    assert_ne!(first, Symbol::intern(&args[0].span));
+    assert_eq!(first.as_str(), "vec");
+    assert_eq!(Symbol::intern(&args[0].span).to_string(), "vec_t");
+}
+
+#[test]
+fn symbols_across_threads() {
+    let names: Vec<_> = (0..64).map(|n| format!("threaded_{}", n % 8)).collect();
+    let symbols: Vec<Vec<Symbol>> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..4)
+            .map(|_| scope.spawn(|| names.iter().map(|name| Symbol::intern(name)).collect()))
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+
+    assert!(symbols.windows(2).all(|pair| pair[0] == pair[1]));
+    assert_eq!(symbols[0][3], symbols[0][11]);
+    assert_eq!(symbols[0][3].as_str(), "threaded_3");
+}
@@ -0,0 +1,208 @@
+use crate::{Kind, Span, Token};
+use nom::Slice;
+
+/// Whitespace and comments surrounding a [`Token`]
+#[derive(Debug, Clone)]
+pub struct Trivia {
+    /// Everything between the previous token (or opening delimiter) and this token
+    pub leading: Span,
+    /// Everything after this token up to the end of its line
+    pub trailing: Span,
+    /// Everything in front of the closing delimiter of a [`Kind::Tuple`], [`Kind::Block`]
+    /// or [`Kind::Array`]
+    pub closing: Option<Span>,
+}
+
+#[derive(Debug, Clone)]
+pub enum TriviaPiece {
+    Whitespace(Span),
+    /// `// ...` without the line break
+    LineComment(Span),
+    /// `/* ... */`, possibly nested
+    BlockComment(Span),
+}
+
+impl TriviaPiece {
+    /// Splits a span of trivia into its individual pieces
+    pub fn split(span: &Span) -> Vec<TriviaPiece> {
+        let mut acc = vec![];
+        let mut rest = span.clone();
+
+        while !rest.is_empty() {
+            let Some((len, kind)) = next_piece(&rest) else {
+                break;
+            };
+            let piece = rest.slice(..len);
+            acc.push(match kind {
+                PieceKind::Whitespace => TriviaPiece::Whitespace(piece),
+                PieceKind::LineComment => TriviaPiece::LineComment(piece),
+                PieceKind::BlockComment => TriviaPiece::BlockComment(piece),
+            });
+            rest = rest.slice(len..);
+        }
+
+        acc
+    }
+
+    pub fn span(&self) -> &Span {
+        match self {
+            TriviaPiece::Whitespace(span)
+            | TriviaPiece::LineComment(span)
+            | TriviaPiece::BlockComment(span) => span,
+        }
+    }
+}
+
+/// Turns the tokens and trailing trivia of [`crate::tokenize_lossless`] back into source code
+pub fn reconstruct(tokens: &[Token], eof: &Span) -> String {
+    let mut out = String::new();
+    write_tokens(tokens, &mut out);
+    out.push_str(eof);
+    out
+}
+
+fn write_tokens(tokens: &[Token], out: &mut String) {
+    for token in tokens {
+        let trivia = token.trivia.as_deref();
+        let span: &str = token.span.fragment();
+
+        if let Some(trivia) = trivia {
+            out.push_str(&trivia.leading);
+        }
+
+        match &token.kind {
+            Kind::Tuple(inner) | Kind::Block(inner) | Kind::Array(inner) => {
+                out.push_str(&span[..1]);
+                write_tokens(&inner.tokens(), out);
+                if let Some(closing) = trivia.and_then(|t| t.closing.as_ref()) {
+                    out.push_str(closing);
+                }
+                out.push_str(&span[span.len() - 1..]);
+            }
+            _ => out.push_str(span),
+        }
+
+        if let Some(trivia) = trivia {
+            out.push_str(&trivia.trailing);
+        }
+    }
+}
+
+/// Fills in the trivia of all tokens, returns the trivia left at the end of `file`
+pub(crate) fn attach(file: &Span, tokens: &mut [Token]) -> Span {
+    let start = file.location_offset();
+    let end = start + file.len();
+
+    let cursor = attach_group(file, tokens, start, end);
+    slice_abs(file, cursor, end)
+}
+
+/// Attaches trivia to the tokens between the absolute offsets `cursor` and `end`,
+/// returns the offset after the trailing trivia of the last token
+fn attach_group(file: &Span, tokens: &mut [Token], mut cursor: usize, end: usize) -> usize {
+    let starts = tokens
+        .iter()
+        .map(|tk| tk.span.location_offset())
+        .collect::<Vec<_>>();
+
+    for (idx, token) in tokens.iter_mut().enumerate() {
+        let start = starts[idx];
+        let token_end = start + token.span.len();
+        let next = starts.get(idx + 1).copied().unwrap_or(end);
+
+        let leading = slice_abs(file, cursor, start);
+
+        let closing = match &mut token.kind {
+            Kind::Tuple(inner) | Kind::Block(inner) | Kind::Array(inner) => {
+                let inner = inner.tokens_mut();
+                let inner_end = token_end - 1;
+                let cursor = attach_group(file, inner, start + 1, inner_end);
+                Some(slice_abs(file, cursor, inner_end))
+            }
+            _ => None,
+        };
+
+        let gap = slice_abs(file, token_end, next);
+        let trailing = gap.slice(..trailing_len(&gap));
+        cursor = token_end + trailing.len();
+
+        token.trivia = Some(Box::new(Trivia {
+            leading,
+            trailing,
+            closing,
+        }));
+    }
+
+    cursor
+}
+
+/// Length of the part of `gap` that stays on the same line as the token in front of it
+fn trailing_len(gap: &str) -> usize {
+    let mut len = 0;
+
+    while len < gap.len() {
+        let rest = &gap[len..];
+        let Some((piece, kind)) = next_piece(rest) else {
+            break;
+        };
+
+        if kind == PieceKind::Whitespace {
+            if let Some(nl) = rest[..piece].find(['\r', '\n']) {
+                return len + nl;
+            }
+        }
+
+        len += piece;
+    }
+
+    len
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum PieceKind {
+    Whitespace,
+    LineComment,
+    BlockComment,
+}
+
+/// `None` if `s` doesn't start with trivia, gaps between tokens should never contain
+/// anything else
+fn next_piece(s: &str) -> Option<(usize, PieceKind)> {
+    if s.starts_with("//") {
+        let len = s.find(['\r', '\n']).unwrap_or(s.len());
+        Some((len, PieceKind::LineComment))
+    } else if s.starts_with("/*") {
+        Some((block_comment_len(s), PieceKind::BlockComment))
+    } else {
+        let len = s.find(|c: char| !c.is_whitespace()).unwrap_or(s.len());
+        (len != 0).then_some((len, PieceKind::Whitespace))
+    }
+}
+
+fn block_comment_len(s: &str) -> usize {
+    let bytes = s.as_bytes();
+    let mut depth = 0;
+    let mut idx = 0;
+
+    while idx < bytes.len() {
+        if bytes[idx..].starts_with(b"/*") {
+            depth += 1;
+            idx += 2;
+        } else if bytes[idx..].starts_with(b"*/") {
+            depth -= 1;
+            idx += 2;
+            if depth == 0 {
+                break;
+            }
+        } else {
+            idx += 1;
+        }
+    }
+
+    idx
+}
+
+fn slice_abs(file: &Span, from: usize, to: usize) -> Span {
+    let base = file.location_offset();
+    file.slice(from - base..to - base)
+}
@@ -3,18 +3,11 @@ pub mod types;
 
 use bevy_ecs::component::Component;
 use bevy_ecs::entity::Entity;
-use bevy_ecs::schedule::Schedule;
-use bevy_ecs::world::{EntityMut, World};
-use slotmap::SlotMap;
-use std::borrow::Cow;
-use std::collections::{BTreeMap, HashMap, HashSet};
-use std::future::Future;
-use std::marker::PhantomData;
-use std::thread::scope;
+use bevy_ecs::world::World;
+use std::collections::HashMap;
 use w_analyze::data::err::{DuplicateImport, ErrorCollector};
 use w_analyze::data::md_raw::RawModuleInfo;
-use w_analyze::data::path::{Path, PathBuf};
-use w_analyze::data::types::TypeKind;
+use w_analyze::data::path::{PathBuf, PathKey};
 use w_parse::expr::path::ExprPath;
 use w_parse::item::import::{Imports, ItemImports};
 use w_parse::item::named::{ItemNamed, ItemNamedType, NamedKind};
@@ -22,11 +15,15 @@ use w_parse::item::Item;
 use w_parse::types::r#struct::TyStruct;
 use w_parse::types::ItemTy;
 use w_parse::Ident;
+use w_tokenize::Symbol;
 
 pub struct VmState {
-    types: HashMap<PathBuf, Entity>,
+    types: HashMap<PathKey, Entity>,
 
+    // not read until missing types are resolved against the root module
+    #[allow(dead_code)]
     root: Ident,
+    #[allow(dead_code)]
     missing: Vec<PathBuf>,
 
     errs: ErrorCollector,
@@ -59,14 +56,17 @@ impl VmState {
             match item {
                 Item::Definer(ItemNamed {
                     name,
+                    path,
                     kind: NamedKind::Type(named_ty),
+                    ..
                 }) => {
                     let mut entity = self.world.spawn();
 
-                    let entity_path = scope.current.join(name);
+                    let mut entity_path = scope.current.join(name);
+                    path.into_iter().for_each(|name| entity_path.append(name));
                     entity.insert(WithPath(entity_path.clone()));
 
-                    self.types.insert(entity_path, entity.id());
+                    self.types.insert(entity_path.key(), entity.id());
 
                     let entity = entity.id();
 
@@ -86,7 +86,7 @@ impl VmState {
     ) {
         match ty {
             ItemTy::Referred(other) => {
-                let other = self.resolve_path(other, scope);
+                let other = self.resolve_path(other.path, scope);
 
                 let path = self.world.get::<WithPath>(ety).unwrap().0.to_string();
 
@@ -100,7 +100,7 @@ impl VmState {
                     });
             }
             ItemTy::Struct(TyStruct { fields, .. }) => {
-                let names = fields
+                let _names = fields
                     .iter()
                     .map(|pair| pair.name.clone())
                     .collect::<Vec<_>>();
@@ -118,7 +118,7 @@ impl VmState {
         let buf = PathBuf::from(path.path);
         let buf = if path.root.is_some() {
             buf
-        } else if let Some(import) = scope.imports.get(buf.first().unwrap()) {
+        } else if let Some(import) = scope.imports.get(&buf.first().unwrap().sym) {
             import.join_path(&buf)
         } else {
             scope.current.join_path(&buf)
@@ -129,7 +129,7 @@ impl VmState {
 
 struct Scope {
     current: PathBuf,
-    imports: HashMap<Ident, PathBuf>,
+    imports: HashMap<Symbol, PathBuf>,
 }
 
 #[derive(Component)]
@@ -162,12 +162,13 @@ pub enum IncompleteType {
 mod marker {
     use bevy_ecs::component::Component;
 
+    #[allow(dead_code)]
     #[derive(Component)]
     pub struct Incomplete;
 }
 
 fn flatten_imports(
-    out: &mut HashMap<Ident, PathBuf>,
+    out: &mut HashMap<Symbol, PathBuf>,
     rel_root: PathBuf,
     base: Option<&ExprPath>,
     imp: &Imports,
@@ -187,14 +188,15 @@ fn flatten_imports(
         Imports::Single(pt) => {
             let tp = PathBuf::from(pt.path.clone());
             let imp = base.join_path(&tp);
-            if let Some(og) = out.get(imp.last().unwrap()) {
+            let name = imp.last().unwrap();
+            if let Some(og) = out.get(&name.sym) {
                 errs.add_error(DuplicateImport {
-                    original: og.last().unwrap().0.clone(),
-                    new: imp.last().unwrap().0.clone(),
+                    original: og.last().unwrap().span.clone(),
+                    new: name.span.clone(),
                 });
                 return;
             }
-            out.insert(imp.last().unwrap().clone(), imp);
+            out.insert(name.sym, imp);
         }
         Imports::Multiple(sub_base, other) => {
             let base = base.join_path(&PathBuf::from(sub_base.path.clone()));
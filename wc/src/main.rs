@@ -1,18 +1,30 @@
 #![cfg_attr(debug_assertions, allow(dead_code))]
 
 use ariadne::{ColorGenerator, Label, Report, ReportKind, Source};
-use w_rcstr::RcStr;
-use w_tokenize::{tokenize, Kind, Span};
+use w_tokenize::{tokenize, Kind, SourceDb};
 
 fn main() {
-    let str = RcStr::new(include_str!("../../WIP_tests/mod_test/main.w").to_string());
-    let file = Span::new(str.clone());
-    let (_, tokens) = tokenize(file).unwrap();
-    // let (_, parsed) = parse(TokenSpan::new(file, Rc::from(tokens))).unwrap();
+    let path = std::env::args().nth(1).unwrap_or_else(|| {
+        concat!(env!("CARGO_MANIFEST_DIR"), "/../WIP_tests/mod_test/main.w").to_string()
+    });
+
+    let mut db = SourceDb::new();
+    let id = match db.load(&path) {
+        Ok(id) => id,
+        Err(err) => {
+            eprintln!("can't read {path}: {err}");
+            std::process::exit(1);
+        }
+    };
+    let file = db.get(id);
+    let name = file.path().display().to_string();
+
+    let (_, tokens) = tokenize(db.span(id)).unwrap();
+    // let (_, parsed) = parse(TokenSpan::new(file, Arc::from(tokens))).unwrap();
 
     let mut colors = ColorGenerator::new();
 
-    let mut rpb = Report::build(ReportKind::Error, "main.w", 0).with_code("oh snap");
+    let mut rpb = Report::build(ReportKind::Error, name.as_str(), 0).with_code("oh snap");
 
     let blockc = colors.next();
 
@@ -25,7 +37,7 @@ fn main() {
 
         rpb = rpb.with_label(
             Label::new((
-                "main.w",
+                name.as_str(),
                 span.location_offset()..span.location_offset() + span.len(),
             ))
             .with_message("that's a block of tokens")
@@ -35,6 +47,6 @@ fn main() {
     rpb.with_message("well something happened here")
         .with_note("your program sucks")
         .finish()
-        .print(("main.w", Source::from(&*str)))
+        .print((name.as_str(), Source::from(file.text())))
         .unwrap();
 }